@@ -0,0 +1,53 @@
+//! Analog inputs sampled by the ADC, such as the joystick axes or the resistor ladder line.
+
+use std::rc::Rc;
+
+use esp_idf_svc::{
+    hal::{
+        adc::{
+            attenuation,
+            oneshot::{config::AdcChannelConfig, AdcChannelDriver, AdcDriver},
+        },
+        gpio::ADCPin,
+        peripheral::Peripheral,
+    },
+    sys::EspError,
+};
+use firmware::analog;
+use log::warn;
+
+/// An ADC unit, shared by all the [`Channel`]s sampling its pins.
+pub type Adc<T> = Rc<AdcDriver<'static, <T as ADCPin>::Adc>>;
+
+/// An [`analog::Channel`] sampling a pin with the oneshot ADC driver.
+pub struct Channel<T: ADCPin> {
+    driver: AdcChannelDriver<'static, T, Adc<T>>,
+    /// The last successful reading, repeated when a reading fails.
+    last: u16,
+}
+
+impl<T: ADCPin> Channel<T> {
+    /// Builds a [`Channel`] sampling the pin on the ADC unit, with the full 0-3.1V range.
+    pub fn new(adc: Adc<T>, pin: impl Peripheral<P = T> + 'static) -> Result<Self, EspError> {
+        let config = AdcChannelConfig {
+            attenuation: attenuation::DB_11,
+            ..AdcChannelConfig::new()
+        };
+
+        let mut driver = AdcChannelDriver::new(adc, pin, &config)?;
+        let last = driver.read_raw()?;
+
+        Ok(Self { driver, last })
+    }
+}
+
+impl<T: ADCPin> analog::Channel for Channel<T> {
+    fn read(&mut self) -> u16 {
+        match self.driver.read_raw() {
+            Ok(reading) => self.last = reading,
+            Err(err) => warn!("adc reading failed, using the last one: {err}"),
+        }
+
+        self.last
+    }
+}
@@ -0,0 +1,40 @@
+//! Module containing the abstraction of an analog input, sampled by an ADC.
+
+/// An analog input signal that can be sampled by an ADC.
+pub trait Channel {
+    /// Returns the current raw ADC reading of the signal.
+    fn read(&mut self) -> u16;
+}
+
+impl<C: Channel + ?Sized> Channel for Box<C> {
+    fn read(&mut self) -> u16 {
+        C::read(self)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+
+    /// A [`Channel`] whose reading is set by the test, shared with the input sampling it.
+    #[derive(Debug, Clone, Default)]
+    pub(crate) struct FakeChannel(Rc<Cell<u16>>);
+
+    impl FakeChannel {
+        pub(crate) fn new(reading: u16) -> Self {
+            Self(Rc::new(Cell::new(reading)))
+        }
+
+        pub(crate) fn set(&self, reading: u16) {
+            self.0.set(reading);
+        }
+    }
+
+    impl Channel for FakeChannel {
+        fn read(&mut self) -> u16 {
+            self.0.get()
+        }
+    }
+}
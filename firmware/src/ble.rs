@@ -1,37 +1,79 @@
-use std::sync::Arc;
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 use embassy_time::{Duration, Timer};
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs};
 use esp32_nimble::{
     enums::{AuthReq, SecurityIOCap},
     utilities::mutex::Mutex,
     BLEAdvertisementData, BLECharacteristic, BLEDevice, BLEError, BLEHIDDevice, BLEServer,
 };
-use futures::{channel::mpsc::Receiver, future::Either, StreamExt, TryFutureExt};
+use futures::{
+    channel::mpsc::{Receiver, Sender},
+    future::Either,
+    SinkExt, StreamExt,
+};
+use firmware::{
+    hid,
+    identity::Identity,
+    proto::kontroller::hid::v1::{KeyboardReportMode, ReportType},
+    status::Status,
+};
 use log::{info, warn};
-use usbd_hid::descriptor::SerializedDescriptor;
-
-use crate::{hid, led, proto::kontroller::hid::v1::ReportType};
 
 pub type HidWriter = Arc<Mutex<BLECharacteristic>>;
 
+/// Battery level reported to the host, in percentage.
+///
+/// No battery gauge is wired to the `kontroller` yet, so the battery is always reported as full.
+pub const BATTERY_LEVEL: u8 = 100;
+
+/// NVS namespace of the BLE server state.
+const NVS_NAMESPACE: &str = "ble";
+
+/// NVS key of the hash of the report descriptor advertised on the last boot.
+const NVS_DESCRIPTOR_HASH: &str = "desc_hash";
+
 #[derive(Debug, Clone)]
 pub struct Config {
-    pub device_name: &'static str,
+    pub identity: Identity,
+    pub keyboard_report_mode: KeyboardReportMode,
+    pub report_types: BTreeSet<ReportType>,
+}
+
+/// The characteristics of the HID service, through which reports are exchanged with the host.
+struct Characteristics {
+    inputs: HashMap<ReportType, HidWriter>,
+    /// Keyboard LEDs output reports, of both the report and boot protocol modes.
+    leds: Vec<HidWriter>,
+    /// Boot keyboard input report, used instead of the input reports in boot protocol mode.
+    boot_input: Option<HidWriter>,
+    protocol_mode: HidWriter,
 }
 
 pub struct Server {
     device: &'static mut BLEDevice,
     #[allow(clippy::struct_field_names)]
     server: &'static mut BLEServer,
-    input_keyboard: HidWriter,
+    hid: Characteristics,
+    /// Whether the host selected the boot protocol mode.
+    boot_protocol: Arc<AtomicBool>,
 }
 
 impl Server {
-    pub fn initialize(config: &Config) -> Result<Self, BLEError> {
-        BLEDevice::set_device_name(config.device_name)?;
+    pub fn initialize(config: &Config, nvs: EspDefaultNvsPartition) -> anyhow::Result<Self> {
+        BLEDevice::set_device_name(&config.identity.device_name)?;
 
         let device = BLEDevice::take();
 
+        let descriptor = hid::report_descriptor(&config.report_types, config.keyboard_report_mode);
+        Self::invalidate_gatt_cache(nvs, &descriptor)?;
+
         device
             .security()
             .set_auth(AuthReq::all())
@@ -49,71 +91,168 @@ impl Server {
             Err(err) => warn!("connection aborted, cause: (code: {} {err}", err.code()),
         });
 
-        let input_keyboard = Self::initialize_hid_keyboard(device, server, config)?;
+        let hid = Self::initialize_hid_device(device, server, config, &descriptor)?;
 
         Ok(Self {
             device,
             server,
-            input_keyboard,
+            hid,
+            boot_protocol: Arc::new(AtomicBool::new(false)),
         })
     }
 
-    fn initialize_hid_keyboard(
+    fn initialize_hid_device(
         device: &mut BLEDevice,
         server: &mut BLEServer,
         config: &Config,
-    ) -> Result<HidWriter, BLEError> {
+        descriptor: &[u8],
+    ) -> Result<Characteristics, BLEError> {
         let mut hid_device = BLEHIDDevice::new(server);
 
-        let input_keyboard = hid_device.input_report(ReportType::Keyboard as u8);
+        let inputs = config
+            .report_types
+            .iter()
+            .copied()
+            .map(|report_type| (report_type, hid_device.input_report(report_type as u8)))
+            .collect();
+
+        // The keyboard collection declares the LEDs output report, and keyboards
+        // also expose the boot keyboard reports, for hosts only supporting those.
+        let keyboard = config.report_types.contains(&ReportType::Keyboard);
+        let leds = if keyboard {
+            vec![
+                hid_device.output_report(ReportType::Keyboard as u8),
+                hid_device.boot_output(),
+            ]
+        } else {
+            Vec::new()
+        };
+        let boot_input = keyboard.then(|| hid_device.boot_input());
+
+        // The digitizer collection declares the contact count maximum feature report,
+        // which multi-touch hosts read to know how many fingers to track.
+        if config.report_types.contains(&ReportType::Digitizer) {
+            hid_device
+                .feature_report(ReportType::Digitizer as u8)
+                .lock()
+                .set_value(&hid::DigitizerReport::feature_bytes());
+        }
 
-        hid_device.manufacturer("test");
+        let identity = &config.identity;
+        hid_device.manufacturer(&identity.manufacturer);
         hid_device.pnp(
-            0x02,
-            hid::APPLE_INC_VENDOR_ID,
-            hid::APPLE_BLUETOOTH_HID_KEYBOARD_PRODUCT_ID,
-            0x0210,
+            identity.vendor_id_source as u8,
+            identity.vendor_id,
+            identity.product_id,
+            identity.product_version,
         );
-        hid_device.set_battery_level(100);
+        hid_device.set_battery_level(BATTERY_LEVEL);
         hid_device.hid_info(0x00, 0x03);
-        hid_device.report_map(hid::Report::desc());
+        let protocol_mode = hid_device.protocol_mode().clone();
+        protocol_mode
+            .lock()
+            .set_value(&[hid::ProtocolMode::Report as u8]);
+        hid_device.report_map(descriptor);
 
         let advertising = device.get_advertising();
 
         advertising.lock().scan_response(false).set_data(
             BLEAdvertisementData::new()
-                .name(config.device_name)
-                .appearance(hid::BLE_APPEARANCE_KEYBOARD)
+                .name(&identity.device_name)
+                .appearance(identity.appearance)
                 .add_service_uuid(hid_device.hid_service().lock().uuid()),
         )?;
 
-        Ok(input_keyboard)
+        Ok(Characteristics {
+            inputs,
+            leds,
+            boot_input,
+            protocol_mode,
+        })
+    }
+
+    /// Removes all the bonded hosts if the report descriptor changed since the last boot,
+    /// or if its hash was never stored, e.g. on the first boot after a firmware update.
+    ///
+    /// Hosts only keep the GATT database of bonded devices across connections: without
+    /// a bond, they have to pair again and read the new report descriptor, instead of
+    /// decoding the reports with the stale one they cached.
+    ///
+    /// Only the local bonds are removed: hosts still hold the long term key of the old
+    /// bond, and fail to encrypt the connection with it. The user must "forget" the
+    /// device on the host before pairing it again.
+    fn invalidate_gatt_cache(nvs: EspDefaultNvsPartition, descriptor: &[u8]) -> anyhow::Result<()> {
+        let mut nvs = EspNvs::new(nvs, NVS_NAMESPACE, true)?;
+        let hash = hid::descriptor_hash(descriptor);
+
+        if nvs.get_u32(NVS_DESCRIPTOR_HASH)? == Some(hash) {
+            return Ok(());
+        }
+
+        warn!(
+            "report descriptor changed, removing bonds to invalidate the GATT cache: \
+             forget the device on the hosts before pairing again"
+        );
+        Self::clear_bonds()?;
+
+        nvs.set_u32(NVS_DESCRIPTOR_HASH, hash)?;
+
+        Ok(())
+    }
+
+    /// Disconnects all the connected hosts, so that the server starts
+    /// advertising again and a new host can pair with it.
+    pub fn enter_pairing_mode() -> Result<(), BLEError> {
+        let server = BLEDevice::take().get_server();
+        let connections: Vec<u16> = server.connections().map(|conn| conn.conn_handle()).collect();
+
+        for conn in connections {
+            server.disconnect(conn)?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes all the bonded hosts.
+    pub fn clear_bonds() -> Result<(), BLEError> {
+        BLEDevice::take().delete_all_bonds()
+    }
+
+    /// Removes all the bonded hosts, and erases the BLE server state persisted in NVS,
+    /// so that the next boot starts from the factory state.
+    pub fn factory_reset(nvs: EspDefaultNvsPartition) -> anyhow::Result<()> {
+        Self::clear_bonds()?;
+
+        let mut nvs = EspNvs::new(nvs, NVS_NAMESPACE, true)?;
+        nvs.remove(NVS_DESCRIPTOR_HASH)?;
+
+        Ok(())
     }
 
     pub async fn start(
         &mut self,
         mut rx: Receiver<hid::Report>,
-        led: &mut led::Blinker<'_>,
+        mut status: Sender<Status>,
     ) -> anyhow::Result<()> {
+        for leds in &self.hid.leds {
+            Self::listen_for_leds(leds, status.clone());
+        }
+        self.listen_for_protocol_mode();
+
         loop {
             info!("advertising started");
 
             self.device.get_advertising().lock().start()?;
+            status.send(Status::Advertising).await?;
 
-            let wait_for_connection = Box::pin(self.wait_for_connection());
-            let quickly_blink_led = Box::pin(Self::quickly_blink_led(led));
-
-            futures::future::try_select(wait_for_connection, quickly_blink_led)
-                .await
-                .map_err(|err| match err {
-                    Either::Right((err, _)) | Either::Left((err, _)) => err,
-                })?;
+            self.wait_for_connection().await?;
 
             self.device.get_advertising().lock().stop()?;
+            status.send(Status::Connected).await?;
 
             info!("advertising stopped");
 
-            let listen_hid_reports = Box::pin(self.listen_for_reports(&mut rx, led));
+            let listen_hid_reports = Box::pin(self.listen_for_reports(&mut rx, &mut status));
             let wait_for_disconnection = Box::pin(self.wait_for_disconnection());
 
             futures::future::try_select(listen_hid_reports, wait_for_disconnection)
@@ -121,15 +260,49 @@ impl Server {
                 .map_err(|err| match err {
                     Either::Right((err, _)) | Either::Left((err, _)) => err,
                 })?;
+
+            // The protocol mode is reset on disconnection, as hosts select the
+            // boot protocol mode again on every connection, if they need it.
+            self.set_protocol_mode(hid::ProtocolMode::Report);
         }
     }
 
-    async fn quickly_blink_led(led: &mut led::Blinker<'_>) -> anyhow::Result<()> {
-        loop {
-            led.short_blink().await?;
-            // TODO(ar3s3ru): do not hardcode.
-            Timer::after(Duration::from_millis(100)).await;
-        }
+    /// Notifies the keyboard LEDs state written by the host as a [`Status`] update.
+    fn listen_for_leds(leds: &HidWriter, mut status: Sender<Status>) {
+        leds.lock().on_write(move |args| {
+            let Some(leds) = hid::HostLeds::from_report(args.recv_data()) else {
+                return;
+            };
+
+            info!("host leds set: {:#04x}", leds.bits());
+
+            // Called from the NimBLE host task, so the update cannot wait for the channel.
+            if let Err(err) = status.try_send(Status::HostLeds(leds)) {
+                warn!("host leds status update dropped: {err}");
+            }
+        });
+    }
+
+    /// Switches the format of the reports sent when the host selects a [`hid::ProtocolMode`].
+    fn listen_for_protocol_mode(&self) {
+        let boot_protocol = self.boot_protocol.clone();
+
+        self.hid.protocol_mode.lock().on_write(move |args| {
+            let Some(mode) = hid::ProtocolMode::from_value(args.recv_data()) else {
+                warn!("unknown protocol mode: {:02x?}", args.recv_data());
+                return;
+            };
+
+            info!("protocol mode selected: {mode:?}");
+            boot_protocol.store(mode == hid::ProtocolMode::Boot, Ordering::Relaxed);
+        });
+    }
+
+    /// Sets the [`hid::ProtocolMode`] the reports are sent with, as read by the host.
+    fn set_protocol_mode(&self, mode: hid::ProtocolMode) {
+        self.hid.protocol_mode.lock().set_value(&[mode as u8]);
+        self.boot_protocol
+            .store(mode == hid::ProtocolMode::Boot, Ordering::Relaxed);
     }
 
     async fn wait_for_connection(&self) -> anyhow::Result<()> {
@@ -145,15 +318,16 @@ impl Server {
     async fn listen_for_reports(
         &self,
         rx: &mut Receiver<hid::Report>,
-        led: &mut led::Blinker<'_>,
+        status: &mut Sender<Status>,
     ) -> anyhow::Result<()> {
         while let Some(report) = rx.next().await {
             info!("report received: {report:?}");
 
-            futures::try_join!(
-                self.send_report(&report),
-                led.short_blink().map_err(anyhow::Error::from)
-            )?;
+            self.send_report(&report).await?;
+
+            // Activity is only shown on a best-effort basis: sending reports
+            // must not be slowed down by the status LED.
+            status.try_send(Status::Activity).ok();
         }
 
         Ok(())
@@ -169,8 +343,29 @@ impl Server {
         }
     }
 
-    async fn send_report<T: Sized>(&self, report: &T) -> anyhow::Result<()> {
-        self.input_keyboard.lock().set_from(report).notify();
+    /// Sends the report through the input report characteristic of its report id.
+    ///
+    /// Reports whose id is not in the report descriptor are dropped, as the host
+    /// would not be able to decode them. In boot protocol mode, keyboard reports
+    /// are sent through the boot keyboard input report instead, and the others dropped.
+    async fn send_report(&self, report: &hid::Report) -> anyhow::Result<()> {
+        let (input, bytes) = if self.boot_protocol.load(Ordering::Relaxed) {
+            let Some((input, boot)) = self.hid.boot_input.as_ref().zip(report.to_boot_keyboard())
+            else {
+                return Ok(());
+            };
+
+            (input, boot.to_bytes().to_vec())
+        } else {
+            let Some(input) = self.hid.inputs.get(&report.report_type()) else {
+                warn!("{:?} report not in the report descriptor, dropped", report.report_type());
+                return Ok(());
+            };
+
+            (input, report.to_bytes())
+        };
+
+        input.lock().set_value(&bytes).notify();
         Timer::after(Duration::from_millis(7)).await;
 
         Ok(())
@@ -0,0 +1,218 @@
+//! Chord text entry, where combinations of the `kontroller` buttons type characters.
+
+use std::collections::BTreeSet;
+
+use crate::{
+    hid,
+    proto::kontroller::{
+        hid::v1::KeyCode,
+        v1::{Button, ChordLayout},
+    },
+};
+
+/// A character typed with a chord: a [`KeyCode`] and the modifiers pressed with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Character {
+    /// The key code of the character.
+    pub key_code: KeyCode,
+    /// Bitmask of the modifiers, as used by the keyboard [`hid::Report`].
+    pub modifier: u8,
+}
+
+/// The outcome of releasing a [`Button`] with the [`Chorder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The chord is not complete yet, as some of its [`Button`]s are still held down.
+    Pending,
+    /// The chord is complete, and types the [`Character`].
+    Typed(Character),
+    /// The chord is complete, but not part of the layout.
+    Unknown,
+}
+
+/// Decodes the chords pressed on the [`Button`]s according to a [`ChordLayout`].
+///
+/// A chord is made of all the [`Button`]s pressed before all of them are released,
+/// so they do not need to be pressed or released at the exact same time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Chorder {
+    chords: Vec<(BTreeSet<Button>, Character)>,
+    /// Buttons pressed since the chord started.
+    chord: BTreeSet<Button>,
+    /// Buttons of the chord still held down.
+    held: BTreeSet<Button>,
+}
+
+impl Chorder {
+    /// Creates a new [`Chorder`] for the specified [`ChordLayout`].
+    ///
+    /// Chords with no [`Button`]s or no [`KeyCode`] are skipped.
+    #[must_use]
+    pub fn new(layout: &ChordLayout) -> Self {
+        let chords = layout
+            .chords
+            .iter()
+            .filter_map(|chord| {
+                let buttons: BTreeSet<Button> = chord
+                    .buttons()
+                    .filter(|button| *button != Button::Unspecified)
+                    .collect();
+
+                let character = Character {
+                    key_code: chord.key_code(),
+                    modifier: chord
+                        .modifiers()
+                        .filter_map(hid::modifier_bit)
+                        .fold(0, |modifier, bit| modifier | bit),
+                };
+
+                (!buttons.is_empty() && character.key_code != KeyCode::Unspecified)
+                    .then_some((buttons, character))
+            })
+            .collect();
+
+        Self {
+            chords,
+            ..Self::default()
+        }
+    }
+
+    /// Adds the pressed [`Button`] to the current chord.
+    pub fn press(&mut self, button: Button) {
+        self.chord.insert(button);
+        self.held.insert(button);
+    }
+
+    /// Releases the [`Button`], completing the chord once all its [`Button`]s are released.
+    ///
+    /// Releasing a [`Button`] that is not part of the chord leaves it [`Outcome::Pending`].
+    pub fn release(&mut self, button: Button) -> Outcome {
+        if !self.held.remove(&button) || !self.held.is_empty() {
+            return Outcome::Pending;
+        }
+
+        let chord = std::mem::take(&mut self.chord);
+
+        self.chords
+            .iter()
+            .find(|(buttons, _)| *buttons == chord)
+            .map_or(Outcome::Unknown, |(_, character)| Outcome::Typed(*character))
+    }
+
+    /// Discards the chord in progress.
+    pub fn reset(&mut self) {
+        self.chord.clear();
+        self.held.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::kontroller::v1::Chord;
+
+    fn chord(buttons: &[Button], key_code: KeyCode, modifiers: &[KeyCode]) -> Chord {
+        Chord {
+            buttons: buttons.iter().map(|button| (*button).into()).collect(),
+            key_code: key_code.into(),
+            modifiers: modifiers.iter().map(|modifier| (*modifier).into()).collect(),
+        }
+    }
+
+    /// Types `a` with Up and Down, and `B` with Enter.
+    fn chorder() -> Chorder {
+        Chorder::new(&ChordLayout {
+            chords: vec![
+                chord(&[Button::Up, Button::Down], KeyCode::A, &[]),
+                chord(&[Button::Enter], KeyCode::B, &[KeyCode::Lshift]),
+                chord(&[], KeyCode::C, &[]),
+                chord(&[Button::Fn1], KeyCode::Unspecified, &[]),
+            ],
+        })
+    }
+
+    const A: Character = Character {
+        key_code: KeyCode::A,
+        modifier: 0,
+    };
+
+    #[test]
+    fn chord_is_typed_once_all_its_buttons_are_released() {
+        let mut chorder = chorder();
+
+        chorder.press(Button::Up);
+        chorder.press(Button::Down);
+        assert_eq!(chorder.release(Button::Down), Outcome::Pending);
+        assert_eq!(chorder.release(Button::Up), Outcome::Typed(A));
+    }
+
+    #[test]
+    fn chord_is_made_of_all_the_buttons_pressed_until_all_are_released() {
+        let mut chorder = chorder();
+
+        // Up is released before Down is pressed, while Enter is still held.
+        chorder.press(Button::Enter);
+        chorder.press(Button::Up);
+        assert_eq!(chorder.release(Button::Up), Outcome::Pending);
+        chorder.press(Button::Down);
+        assert_eq!(chorder.release(Button::Enter), Outcome::Pending);
+        assert_eq!(chorder.release(Button::Down), Outcome::Unknown);
+
+        chorder.press(Button::Up);
+        assert_eq!(chorder.release(Button::Up), Outcome::Unknown);
+    }
+
+    #[test]
+    fn chord_types_its_modifiers() {
+        let mut chorder = chorder();
+
+        chorder.press(Button::Enter);
+        assert_eq!(
+            chorder.release(Button::Enter),
+            Outcome::Typed(Character {
+                key_code: KeyCode::B,
+                modifier: hid::modifier_bit(KeyCode::Lshift).unwrap(),
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_chords_are_skipped() {
+        let mut chorder = chorder();
+
+        chorder.press(Button::Fn1);
+        assert_eq!(chorder.release(Button::Fn1), Outcome::Unknown);
+        assert_eq!(chorder.chords.len(), 2);
+    }
+
+    #[test]
+    fn releasing_a_button_not_pressed_leaves_the_chord_pending() {
+        let mut chorder = chorder();
+
+        assert_eq!(chorder.release(Button::Up), Outcome::Pending);
+
+        chorder.press(Button::Up);
+        chorder.press(Button::Down);
+        assert_eq!(chorder.release(Button::Enter), Outcome::Pending);
+        assert_eq!(chorder.release(Button::Up), Outcome::Pending);
+        assert_eq!(chorder.release(Button::Down), Outcome::Typed(A));
+    }
+
+    #[test]
+    fn reset_discards_the_chord_in_progress() {
+        let mut chorder = chorder();
+
+        chorder.press(Button::Up);
+        chorder.press(Button::Down);
+        chorder.reset();
+
+        // The Buttons held before the reset are no longer part of a chord.
+        assert_eq!(chorder.release(Button::Up), Outcome::Pending);
+        assert_eq!(chorder.release(Button::Down), Outcome::Pending);
+
+        chorder.press(Button::Up);
+        chorder.press(Button::Down);
+        chorder.release(Button::Up);
+        assert_eq!(chorder.release(Button::Down), Outcome::Typed(A));
+    }
+}
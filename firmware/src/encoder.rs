@@ -0,0 +1,274 @@
+//! Module containing the decoding of a quadrature rotary [`Encoder`].
+
+use embassy_time::{Duration, Instant};
+
+use crate::key::Pin;
+
+/// Default number of quadrature pulses between two detents of the [`Encoder`],
+/// matching the most common full-cycle-per-detent encoders.
+pub const DEFAULT_PULSES_PER_DETENT: u8 = 4;
+
+/// Position change for every transition between two quadrature states,
+/// indexed by the previous state followed by the current one.
+///
+/// Invalid transitions, where both signals changed at once, are ignored.
+const TRANSITIONS: [i8; 16] = [0, -1, 1, 0, 1, 0, 0, -1, -1, 0, 0, 1, 0, 1, -1, 0];
+
+/// Quadrature state of the [`Encoder`] at rest, with both signals high.
+const REST: u8 = 0b00;
+
+/// Configuration of the [`Encoder`] decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Number of quadrature pulses between two detents.
+    pub pulses_per_detent: u8,
+    /// Swaps the clockwise and counter-clockwise directions.
+    pub reverse: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            pulses_per_detent: DEFAULT_PULSES_PER_DETENT,
+            reverse: false,
+        }
+    }
+}
+
+/// Rotation direction of an [`Encoder`] [`Step`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// The [`Encoder`] has been rotated clockwise.
+    Clockwise,
+    /// The [`Encoder`] has been rotated counter-clockwise.
+    CounterClockwise,
+}
+
+/// A detent step detected by the [`Encoder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    /// The rotation direction.
+    pub direction: Direction,
+    /// When the step has been detected.
+    pub at: Instant,
+    /// The rotation speed, in detents per second, measured from the previous step.
+    /// Zero for the first step.
+    pub velocity: u32,
+}
+
+/// Logical representation of a quadrature rotary encoder, whose `A` and `B`
+/// signals are connected to two microcontroller pins using pull-up resistors.
+///
+/// Use [`Encoder::new`] to build a new [`Encoder`] instance from any pair of [`Pin`]s.
+pub struct Encoder<P> {
+    a: P,
+    b: P,
+    config: Config,
+    state: u8,
+    pulses: i8,
+    last_step: Option<Instant>,
+}
+
+impl<P: Pin> Encoder<P> {
+    /// Builds an [`Encoder`] instance sampling the given `A` and `B` [`Pin`]s,
+    /// using the specified [`Config`].
+    pub fn new(a: P, b: P, config: Config) -> Self {
+        let mut encoder = Self {
+            a,
+            b,
+            config,
+            state: REST,
+            pulses: 0,
+            last_step: None,
+        };
+
+        encoder.state = encoder.sample();
+        encoder
+    }
+
+    /// Replaces the [`Config`] used by the [`Encoder`].
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+        self.pulses = 0;
+    }
+
+    /// Samples the [`Pin`]s at the specified point in time.
+    ///
+    /// This method should be called often enough not to miss any quadrature transition.
+    ///
+    /// Returns a [`Step`] when the [`Encoder`] reaches the next detent.
+    pub fn update(&mut self, now: Instant) -> Option<Step> {
+        let state = self.sample();
+        let transition = TRANSITIONS[usize::from(self.state << 2 | state)];
+        self.state = state;
+
+        self.pulses = self.pulses.saturating_add(transition);
+
+        let pulses_per_detent = i8::try_from(self.config.pulses_per_detent.max(1)).unwrap_or(i8::MAX);
+        let direction = if self.pulses >= pulses_per_detent {
+            Some(Direction::Clockwise)
+        } else if self.pulses <= -pulses_per_detent {
+            Some(Direction::CounterClockwise)
+        } else {
+            None
+        };
+
+        // Resynchronize on every detent at rest, so that missed pulses
+        // do not shift the position of the following detents.
+        if direction.is_some() || state == REST {
+            self.pulses = 0;
+        }
+
+        let direction = direction?;
+        let velocity = self
+            .last_step
+            .map(|last| now - last)
+            .filter(|elapsed| *elapsed > Duration::MIN)
+            .map_or(0, |elapsed| {
+                u32::try_from(1_000_000 / elapsed.as_micros().max(1)).unwrap_or(u32::MAX)
+            });
+
+        self.last_step = Some(now);
+
+        Some(Step {
+            direction: match (direction, self.config.reverse) {
+                (Direction::Clockwise, false) | (Direction::CounterClockwise, true) => {
+                    Direction::Clockwise
+                }
+                _ => Direction::CounterClockwise,
+            },
+            at: now,
+            velocity,
+        })
+    }
+
+    /// Returns the current quadrature state, with `A` as the high bit
+    /// and `B` as the low bit, set when the signal is low.
+    fn sample(&self) -> u8 {
+        u8::from(self.a.is_low()) << 1 | u8::from(self.b.is_low())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::tests::FakePin;
+
+    const CLOCKWISE: [u8; 4] = [0b10, 0b11, 0b01, 0b00];
+    const COUNTER_CLOCKWISE: [u8; 4] = [0b01, 0b11, 0b10, 0b00];
+
+    /// An [`Encoder`] whose `A` and `B` [`FakePin`]s are driven by the test.
+    struct Harness {
+        encoder: Encoder<FakePin>,
+        a: FakePin,
+        b: FakePin,
+        now: Instant,
+    }
+
+    impl Harness {
+        fn new(config: Config) -> Self {
+            let (a, b) = (FakePin::default(), FakePin::default());
+
+            Self {
+                encoder: Encoder::new(a.clone(), b.clone(), config),
+                a,
+                b,
+                now: Instant::from_ticks(0),
+            }
+        }
+
+        /// Drives the quadrature states, one every millisecond, returning the [`Step`]s.
+        fn turn(&mut self, states: &[u8]) -> Vec<Step> {
+            states
+                .iter()
+                .filter_map(|state| {
+                    self.a.set_low(state & 0b10 != 0);
+                    self.b.set_low(state & 0b01 != 0);
+                    self.now += Duration::from_millis(1);
+                    self.encoder.update(self.now)
+                })
+                .collect()
+        }
+    }
+
+    fn directions(steps: &[Step]) -> Vec<Direction> {
+        steps.iter().map(|step| step.direction).collect()
+    }
+
+    #[test]
+    fn full_quadrature_cycles_step_once_per_detent() {
+        let mut harness = Harness::new(Config::default());
+
+        assert_eq!(
+            harness.turn(&CLOCKWISE),
+            [Step {
+                direction: Direction::Clockwise,
+                at: Instant::from_millis(4),
+                velocity: 0,
+            }]
+        );
+        assert_eq!(
+            harness.turn(&COUNTER_CLOCKWISE),
+            [Step {
+                direction: Direction::CounterClockwise,
+                at: Instant::from_millis(8),
+                velocity: 250,
+            }]
+        );
+    }
+
+    #[test]
+    fn reverse_swaps_the_directions() {
+        let mut harness = Harness::new(Config {
+            reverse: true,
+            ..Config::default()
+        });
+
+        assert_eq!(
+            directions(&harness.turn(&[CLOCKWISE, COUNTER_CLOCKWISE].concat())),
+            [Direction::CounterClockwise, Direction::Clockwise]
+        );
+    }
+
+    #[test]
+    fn half_cycle_encoders_step_twice_per_cycle() {
+        let mut harness = Harness::new(Config {
+            pulses_per_detent: 2,
+            ..Config::default()
+        });
+
+        assert_eq!(
+            directions(&harness.turn(&CLOCKWISE)),
+            [Direction::Clockwise, Direction::Clockwise]
+        );
+    }
+
+    #[test]
+    fn invalid_transitions_are_ignored() {
+        let mut harness = Harness::new(Config::default());
+
+        // Both signals changing at once carry no direction.
+        assert_eq!(harness.turn(&[0b11, 0b00, 0b11, 0b00]), []);
+
+        // A missed state on the way resynchronizes at rest, without stepping.
+        assert_eq!(harness.turn(&[0b10, 0b01, 0b00]), []);
+        assert_eq!(directions(&harness.turn(&CLOCKWISE)), [Direction::Clockwise]);
+    }
+
+    #[test]
+    fn reversing_mid_detent_does_not_step() {
+        let mut harness = Harness::new(Config::default());
+
+        assert_eq!(harness.turn(&[0b10, 0b11, 0b10, 0b00]), []);
+        assert_eq!(
+            directions(&harness.turn(&COUNTER_CLOCKWISE)),
+            [Direction::CounterClockwise]
+        );
+
+        // Reversing three quarters into the detent returns to rest without stepping.
+        assert_eq!(
+            directions(&harness.turn(&[0b10, 0b11, 0b01, 0b11, 0b10, 0b00])),
+            []
+        );
+    }
+}
@@ -0,0 +1,522 @@
+//! I2C GPIO expander scanning, to connect buttons through a two-wire bus,
+//! e.g. from a remote handlebar pod.
+
+use std::{
+    cell::{Cell, RefCell},
+    fmt::Debug,
+    rc::Rc,
+};
+
+use embassy_time::{Duration, Instant};
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::{
+    hal::{delay::TickType, i2c::I2cDriver},
+    sys::EspError,
+};
+
+use crate::key::{self, Pin};
+
+/// An I2C bus, on which the [`Expander`] is the target at a 7-bit address.
+pub trait Bus {
+    /// The error returned when a transaction fails, e.g. when the target does not acknowledge.
+    type Error: Debug;
+
+    /// Writes the bytes to the target at the address.
+    ///
+    /// # Errors
+    ///
+    /// The method fails if the transaction is not completed.
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error>;
+
+    /// Reads enough bytes to fill the buffer from the target at the address.
+    ///
+    /// # Errors
+    ///
+    /// The method fails if the transaction is not completed.
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Writes the bytes to the target at the address, then reads enough bytes
+    /// to fill the buffer within the same transaction, using a repeated start.
+    ///
+    /// # Errors
+    ///
+    /// The method fails if the transaction is not completed.
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8])
+        -> Result<(), Self::Error>;
+}
+
+impl<B: Bus + ?Sized> Bus for Box<B> {
+    type Error = B::Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        B::write(self, address, bytes)
+    }
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        B::read(self, address, buffer)
+    }
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        B::write_read(self, address, bytes, buffer)
+    }
+}
+
+/// A [`Bus`] shared by multiple [`Expander`]s at different addresses.
+impl<B: Bus> Bus for Rc<RefCell<B>> {
+    type Error = B::Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.borrow_mut().write(address, bytes)
+    }
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.borrow_mut().read(address, buffer)
+    }
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        self.borrow_mut().write_read(address, bytes, buffer)
+    }
+}
+
+/// Maximum time to wait for an I2C transaction, so that a disconnected
+/// [`Expander`] does not stall the polling of the other buttons.
+#[cfg(target_os = "espidf")]
+const TIMEOUT_MILLIS: u64 = 5;
+
+#[cfg(target_os = "espidf")]
+impl<'d> Bus for I2cDriver<'d> {
+    type Error = EspError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        I2cDriver::write(self, address, bytes, TickType::new_millis(TIMEOUT_MILLIS).ticks())
+    }
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        I2cDriver::read(self, address, buffer, TickType::new_millis(TIMEOUT_MILLIS).ticks())
+    }
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        let timeout = TickType::new_millis(TIMEOUT_MILLIS).ticks();
+        I2cDriver::write_read(self, address, bytes, buffer, timeout)
+    }
+}
+
+/// Time between two attempts to configure a disconnected [`Expander`], as each
+/// attempt can stall the polling of the other buttons for the I2C timeout.
+pub const RECONNECT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Address of both the MCP23017 and the PCF8574 with all their address pins grounded.
+pub const DEFAULT_ADDRESS: u8 = 0x20;
+
+/// Registers of the MCP23017, with the default `IOCON.BANK = 0` addressing,
+/// where the registers of port `A` and `B` are interleaved.
+pub mod mcp23017 {
+    /// I/O direction of the port `A` pins, set for inputs.
+    pub const IODIRA: u8 = 0x00;
+    /// Interrupt-on-change enable of the port `A` pins.
+    pub const GPINTENA: u8 = 0x04;
+    /// Interrupt-on-change control of the port `A` pins, cleared to compare
+    /// against the previous pin value.
+    pub const INTCONA: u8 = 0x08;
+    /// Expander configuration, shared by both ports.
+    pub const IOCON: u8 = 0x0A;
+    /// Pull-up resistors enable of the port `A` pins.
+    pub const GPPUA: u8 = 0x0C;
+    /// Level of the port `A` pins: reading it clears the interrupt.
+    pub const GPIOA: u8 = 0x12;
+
+    /// `IOCON` bit connecting the `INTA` and `INTB` interrupt pins together.
+    pub const IOCON_MIRROR: u8 = 0x40;
+    /// `IOCON` bit configuring the interrupt pins as open-drain outputs.
+    pub const IOCON_ODR: u8 = 0x04;
+}
+
+/// Model of an I2C GPIO [`Expander`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip {
+    /// Microchip MCP23017, with 16 pins and internal pull-up resistors.
+    Mcp23017,
+    /// NXP/TI PCF8574, with 8 quasi-bidirectional pins.
+    Pcf8574,
+}
+
+impl Chip {
+    /// Returns the number of pins of the [`Chip`].
+    #[must_use]
+    pub fn pins(self) -> usize {
+        match self {
+            Self::Mcp23017 => 16,
+            Self::Pcf8574 => 8,
+        }
+    }
+}
+
+/// An I2C GPIO expander, whose pins are connected to buttons shorting them to ground.
+///
+/// Each pin is read through an [`ExpanderPin`], holding its level from the last scan.
+/// When the interrupt output of the [`Expander`] is wired to a microcontroller pin,
+/// the pins are only read over the [`Bus`] after a change.
+pub struct Expander<B> {
+    bus: B,
+    chip: Chip,
+    address: u8,
+    interrupt: Option<Box<dyn Pin>>,
+    /// Bitmask of the pins read as low in the last scan.
+    low: Rc<Cell<u16>>,
+    configured: bool,
+    /// When to attempt configuring the [`Expander`] again, after a [`Bus`] failure.
+    retry_at: Option<Instant>,
+}
+
+impl<B: Bus> Expander<B> {
+    /// Builds an [`Expander`] of the given [`Chip`], at the address on the [`Bus`].
+    ///
+    /// The [`Chip`] is configured on the first scan, and every [`RECONNECT_INTERVAL`]
+    /// after a [`Bus`] failure, so that a disconnected [`Expander`] resumes working
+    /// once reconnected.
+    #[must_use]
+    pub fn new(bus: B, chip: Chip, address: u8) -> Self {
+        Self {
+            bus,
+            chip,
+            address,
+            interrupt: None,
+            low: Rc::new(Cell::new(0)),
+            configured: false,
+            retry_at: None,
+        }
+    }
+
+    /// Sets the [`Pin`] connected to the active-low interrupt output of the [`Expander`],
+    /// so that the pins are only read after a change.
+    #[must_use]
+    pub fn with_interrupt(mut self, interrupt: impl Pin + 'static) -> Self {
+        self.interrupt = Some(Box::new(interrupt));
+        self
+    }
+
+    /// Returns the [`ExpanderPin`] at the specified index, if part of the [`Chip`].
+    ///
+    /// The pins of the MCP23017 port `B` follow the ones of port `A`.
+    #[must_use]
+    pub fn pin(&self, index: usize) -> Option<ExpanderPin> {
+        (index < self.chip.pins()).then(|| ExpanderPin {
+            low: Rc::clone(&self.low),
+            index,
+        })
+    }
+
+    /// Returns `true` if the [`Expander`] responded to the last scan.
+    #[must_use]
+    pub fn is_connected(&self) -> bool {
+        self.configured
+    }
+
+    fn configure(&mut self) -> Result<(), B::Error> {
+        match self.chip {
+            Chip::Mcp23017 => {
+                use mcp23017::{GPINTENA, GPPUA, INTCONA, IOCON, IOCON_MIRROR, IOCON_ODR, IODIRA};
+
+                // Any pin change pulls the open-drain interrupt output low,
+                // shared between the two ports.
+                self.bus
+                    .write(self.address, &[IOCON, IOCON_MIRROR | IOCON_ODR])?;
+
+                for (register, value) in [
+                    (IODIRA, 0xFF),
+                    (GPPUA, 0xFF),
+                    (INTCONA, 0x00),
+                    (GPINTENA, 0xFF),
+                ] {
+                    // Port B registers follow the port A ones.
+                    self.bus.write(self.address, &[register, value, value])?;
+                }
+
+                Ok(())
+            }
+            // Writing high to the quasi-bidirectional pins turns them into
+            // inputs, with a weak pull-up.
+            Chip::Pcf8574 => self.bus.write(self.address, &[0xFF]),
+        }
+    }
+
+    fn read(&mut self) -> Result<u16, B::Error> {
+        let high = match self.chip {
+            Chip::Mcp23017 => {
+                let mut buffer = [0; 2];
+                self.bus
+                    .write_read(self.address, &[mcp23017::GPIOA], &mut buffer)?;
+                u16::from_le_bytes(buffer)
+            }
+            Chip::Pcf8574 => {
+                let mut buffer = [0; 1];
+                self.bus.read(self.address, &mut buffer)?;
+                u16::from(buffer[0]) | 0xFF00
+            }
+        };
+
+        Ok(!high)
+    }
+}
+
+impl<B: Bus> key::Scan for Expander<B> {
+    fn scan(&mut self, now: Instant) {
+        let changed = !self.interrupt.as_deref().is_some_and(Pin::is_high);
+        if self.configured && !changed {
+            return;
+        }
+
+        if self.retry_at.is_some_and(|retry_at| now < retry_at) {
+            return;
+        }
+
+        let result = if self.configured {
+            self.read()
+        } else {
+            self.configure().and_then(|()| self.read())
+        };
+
+        match result {
+            Ok(low) => {
+                self.configured = true;
+                self.retry_at = None;
+                self.low.set(low);
+            }
+            Err(err) => {
+                if self.configured {
+                    log::warn!("expander at {:#04x} disconnected: {err:?}", self.address);
+                }
+
+                // Buttons of a disconnected expander are released,
+                // rather than being kept pressed until it reconnects.
+                self.configured = false;
+                self.retry_at = Some(now + RECONNECT_INTERVAL);
+                self.low.set(0);
+            }
+        }
+    }
+}
+
+/// A pin of an [`Expander`], reading as low when pressed in the last scan.
+#[derive(Debug, Clone)]
+pub struct ExpanderPin {
+    low: Rc<Cell<u16>>,
+    index: usize,
+}
+
+impl Pin for ExpanderPin {
+    fn is_low(&self) -> bool {
+        self.low.get() & (1 << self.index) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::{tests::FakePin, Scan};
+
+    /// A transaction performed on the [`MockBus`].
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    enum Transaction {
+        Write(Vec<u8>),
+        Read,
+        WriteRead(Vec<u8>),
+    }
+
+    /// An I2C [`Bus`] with a single [`Chip`] at [`DEFAULT_ADDRESS`], whose pins are
+    /// set by the test, and which does not acknowledge anything while disconnected.
+    #[derive(Debug)]
+    struct MockBus {
+        chip: Chip,
+        connected: bool,
+        /// Bitmask of the pins pulled low by the buttons.
+        low: u16,
+        transactions: Vec<Transaction>,
+    }
+
+    /// The target did not acknowledge its address.
+    #[derive(Debug)]
+    struct Nack;
+
+    impl MockBus {
+        fn acknowledge(&mut self, address: u8, transaction: Transaction) -> Result<(), Nack> {
+            self.transactions.push(transaction);
+            (self.connected && address == DEFAULT_ADDRESS)
+                .then_some(())
+                .ok_or(Nack)
+        }
+    }
+
+    impl Bus for MockBus {
+        type Error = Nack;
+
+        fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Nack> {
+            self.acknowledge(address, Transaction::Write(bytes.to_vec()))
+        }
+
+        fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Nack> {
+            self.acknowledge(address, Transaction::Read)?;
+            buffer[0] = !self.low.to_le_bytes()[0];
+            Ok(())
+        }
+
+        fn write_read(
+            &mut self,
+            address: u8,
+            bytes: &[u8],
+            buffer: &mut [u8],
+        ) -> Result<(), Nack> {
+            self.acknowledge(address, Transaction::WriteRead(bytes.to_vec()))?;
+            assert_eq!(self.chip, Chip::Mcp23017);
+            buffer.copy_from_slice(&(!self.low).to_le_bytes());
+            Ok(())
+        }
+    }
+
+    struct Harness {
+        bus: Rc<RefCell<MockBus>>,
+        expander: Expander<Rc<RefCell<MockBus>>>,
+        now: Instant,
+    }
+
+    impl Harness {
+        fn new(chip: Chip) -> Self {
+            let bus = Rc::new(RefCell::new(MockBus {
+                chip,
+                connected: true,
+                low: 0,
+                transactions: Vec::new(),
+            }));
+
+            Self {
+                expander: Expander::new(Rc::clone(&bus), chip, DEFAULT_ADDRESS),
+                bus,
+                now: Instant::from_ticks(0),
+            }
+        }
+
+        /// Scans the [`Expander`] after the specified time, returning the transactions
+        /// performed and the pins read as low.
+        fn scan_after(&mut self, millis: u64) -> (Vec<Transaction>, Vec<usize>) {
+            self.now += Duration::from_millis(millis);
+            self.expander.scan(self.now);
+
+            let transactions = std::mem::take(&mut self.bus.borrow_mut().transactions);
+            let low = (0..16)
+                .filter(|index| self.expander.pin(*index).is_some_and(|pin| pin.is_low()))
+                .collect();
+
+            (transactions, low)
+        }
+    }
+
+    fn read_mcp23017() -> Transaction {
+        Transaction::WriteRead(vec![mcp23017::GPIOA])
+    }
+
+    #[test]
+    fn mcp23017_is_configured_on_the_first_scan() {
+        use mcp23017::{GPINTENA, GPPUA, INTCONA, IOCON, IOCON_MIRROR, IOCON_ODR, IODIRA};
+
+        let mut harness = Harness::new(Chip::Mcp23017);
+        harness.bus.borrow_mut().low = 0b1000_0000_0000_0010;
+
+        assert_eq!(
+            harness.scan_after(1),
+            (
+                vec![
+                    Transaction::Write(vec![IOCON, IOCON_MIRROR | IOCON_ODR]),
+                    Transaction::Write(vec![IODIRA, 0xFF, 0xFF]),
+                    Transaction::Write(vec![GPPUA, 0xFF, 0xFF]),
+                    Transaction::Write(vec![INTCONA, 0x00, 0x00]),
+                    Transaction::Write(vec![GPINTENA, 0xFF, 0xFF]),
+                    read_mcp23017(),
+                ],
+                vec![1, 15]
+            )
+        );
+        assert!(harness.expander.is_connected());
+        assert_eq!(harness.scan_after(1), (vec![read_mcp23017()], vec![1, 15]));
+    }
+
+    #[test]
+    fn pcf8574_pins_are_read_after_turning_them_into_inputs() {
+        let mut harness = Harness::new(Chip::Pcf8574);
+        harness.bus.borrow_mut().low = 0b0001_0000;
+
+        assert_eq!(
+            harness.scan_after(1),
+            (
+                vec![Transaction::Write(vec![0xFF]), Transaction::Read],
+                vec![4]
+            )
+        );
+        assert!(harness.expander.pin(8).is_none());
+    }
+
+    #[test]
+    fn pins_are_only_read_after_an_interrupt() {
+        let mut harness = Harness::new(Chip::Mcp23017);
+        let interrupt = FakePin::default();
+        harness.expander =
+            Expander::new(Rc::clone(&harness.bus), Chip::Mcp23017, DEFAULT_ADDRESS)
+                .with_interrupt(interrupt.clone());
+
+        // The first scan configures the chip, and reads the pins regardless.
+        assert_eq!(harness.scan_after(1).0.last(), Some(&read_mcp23017()));
+
+        harness.bus.borrow_mut().low = 0b100;
+        assert_eq!(harness.scan_after(1), (vec![], vec![]));
+
+        interrupt.set_low(true);
+        assert_eq!(harness.scan_after(1), (vec![read_mcp23017()], vec![2]));
+
+        // Reading the pins clears the interrupt.
+        interrupt.set_low(false);
+        assert_eq!(harness.scan_after(1), (vec![], vec![2]));
+    }
+
+    #[test]
+    fn disconnection_releases_the_pins_and_reconnects_with_a_backoff() {
+        let mut harness = Harness::new(Chip::Pcf8574);
+        harness.bus.borrow_mut().low = 0b1;
+        assert_eq!(harness.scan_after(1).1, [0]);
+
+        harness.bus.borrow_mut().connected = false;
+        assert_eq!(harness.scan_after(1), (vec![Transaction::Read], vec![]));
+        assert!(!harness.expander.is_connected());
+
+        // No transaction is attempted until the reconnect interval elapsed.
+        assert_eq!(harness.scan_after(100), (vec![], vec![]));
+        assert_eq!(
+            harness.scan_after(150),
+            (vec![Transaction::Write(vec![0xFF])], vec![])
+        );
+        assert_eq!(harness.scan_after(200), (vec![], vec![]));
+
+        harness.bus.borrow_mut().connected = true;
+        assert_eq!(
+            harness.scan_after(50),
+            (
+                vec![Transaction::Write(vec![0xFF]), Transaction::Read],
+                vec![0]
+            )
+        );
+        assert!(harness.expander.is_connected());
+    }
+}
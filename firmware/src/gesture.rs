@@ -0,0 +1,83 @@
+//! Detection of gestures performed on the `kontroller` buttons, such as combos or long presses.
+
+use std::collections::BTreeSet;
+
+use embassy_time::{Duration, Instant};
+
+use crate::proto::kontroller::v1::{Button, Gesture};
+
+/// Detects when a [`Gesture`] is performed, given the [`Button`]s currently held down.
+///
+/// A [`Gesture`] is performed when all its [`Button`]s have been held down together
+/// for at least the configured hold time. The [`Detector`] then fires only once,
+/// until any of the [`Button`]s is released.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Detector {
+    buttons: BTreeSet<Button>,
+    hold: Duration,
+    state: State,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    Armed(Instant),
+    Fired,
+}
+
+impl Detector {
+    /// Creates a new [`Detector`] for the specified [`Gesture`].
+    ///
+    /// Returns `None` if the [`Gesture`] does not specify any [`Button`].
+    #[must_use]
+    pub fn new(gesture: &Gesture) -> Option<Self> {
+        let buttons: BTreeSet<Button> = gesture
+            .buttons()
+            .filter(|button| *button != Button::Unspecified)
+            .collect();
+
+        (!buttons.is_empty()).then(|| Self {
+            buttons,
+            hold: Duration::from_millis(gesture.hold_millis.into()),
+            state: State::Idle,
+        })
+    }
+
+    /// Returns the [`Button`]s that are part of the [`Gesture`].
+    #[must_use]
+    pub fn buttons(&self) -> &BTreeSet<Button> {
+        &self.buttons
+    }
+
+    /// Returns the time all the [`Button`]s must be held down to perform the [`Gesture`].
+    #[must_use]
+    pub fn hold(&self) -> Duration {
+        self.hold
+    }
+
+    /// Updates the [`Detector`] with the [`Button`]s currently held down.
+    ///
+    /// Returns `true` if the [`Gesture`] has been performed.
+    pub fn update(&mut self, down: &BTreeSet<Button>, now: Instant) -> bool {
+        if !self.buttons.is_subset(down) {
+            self.state = State::Idle;
+            return false;
+        }
+
+        match self.state {
+            State::Idle if self.hold == Duration::MIN => {
+                self.state = State::Fired;
+                true
+            }
+            State::Idle => {
+                self.state = State::Armed(now);
+                false
+            }
+            State::Armed(since) if now - since >= self.hold => {
+                self.state = State::Fired;
+                true
+            }
+            State::Armed(_) | State::Fired => false,
+        }
+    }
+}
@@ -1,85 +1,1088 @@
 //! HID utilities and implementations, such as report types, descriptors, etc.
 
-use usbd_hid::descriptor::generator_prelude::*;
+use std::collections::BTreeSet;
 
-// Source: <https://developer.nordicsemi.com/nRF5_SDK/nRF51_SDK_v4.x.x/doc/html/group___b_l_e___a_p_p_e_a_r_a_n_c_e_s.html#gac08ceb7b199eceefc4650399a3a7ff75>
+use crate::proto::kontroller::hid::v1::{GamepadButton, KeyCode, KeyboardReportMode, ReportType};
+
+/// BLE appearance value for a HID keyboard.
+///
+/// Source: <https://developer.nordicsemi.com/nRF5_SDK/nRF51_SDK_v4.x.x/doc/html/group___b_l_e___a_p_p_e_a_r_a_n_c_e_s.html#gac08ceb7b199eceefc4650399a3a7ff75>
 pub const BLE_APPEARANCE_KEYBOARD: u16 = 0x03c1;
-// Source: <https://the-sz.com/products/usbid/index.php?v=0x05AC&p=0x820A>
+/// USB vendor id of Apple Inc.
+///
+/// Source: <https://the-sz.com/products/usbid/index.php?v=0x05AC&p=0x820A>
 pub const APPLE_INC_VENDOR_ID: u16 = 0x05ac;
+/// USB product id of the Apple Bluetooth HID keyboard.
 pub const APPLE_BLUETOOTH_HID_KEYBOARD_PRODUCT_ID: u16 = 0x820a;
+/// USB vendor id of pid.codes, which assigns product ids to open source hardware.
+///
+/// Source: <https://pid.codes/1209/>
+pub const PID_CODES_VENDOR_ID: u16 = 0x1209;
+/// Test product id of pid.codes, used until the `kontroller` gets its own.
+///
+/// This is a placeholder: the test product id is shared by every project under
+/// development, so hosts cannot tell the `kontroller` apart from other test devices.
+///
+/// Source: <https://pid.codes/1209/0001/>
+// TODO: request a product id from pid.codes, and use it instead.
+pub const PID_CODES_TEST_PRODUCT_ID: u16 = 0x0001;
 
-/// KeyboardReport describes a report and its companion descriptor that can be
-/// used to send keyboard button presses to a host and receive the status of the
-/// keyboard LEDs.
-#[gen_hid_descriptor(
-    (collection = APPLICATION, usage_page = GENERIC_DESKTOP, usage = KEYBOARD) = {
-        (report_id = 0x01,) = {
-            (usage_page = KEYBOARD, usage_min = 0xE0, usage_max = 0xE7) = {
-                #[packed_bits 8] #[item_settings data,variable,absolute] modifier=input;
-            };
-            (usage_min = 0x00, usage_max = 0xFF) = {
-                #[item_settings constant,variable,absolute] reserved=input;
-            };
-            (usage_page = LEDS, usage_min = 0x01, usage_max = 0x05) = {
-                #[packed_bits 5] #[item_settings data,variable,absolute] leds=output;
-            };
-            (usage_page = KEYBOARD, usage_min = 0x00, usage_max = 0xDD) = {
-                #[item_settings data,array,absolute] keycodes=input;
-            };
-        };
-    },
-    (collection = APPLICATION, usage_page = GENERIC_DESKTOP, usage = MOUSE) = {
-        (collection = PHYSICAL, usage = POINTER) = {
-            (report_id = 0x02,) = {
-                (usage_page = BUTTON, usage_min = BUTTON_1, usage_max = BUTTON_8) = {
-                    #[packed_bits 8] #[item_settings data,variable,absolute] buttons=input;
-                };
-                (usage_page = GENERIC_DESKTOP,) = {
-                    (usage = X,) = {
-                        #[item_settings data,variable,relative] x=input;
-                    };
-                    (usage = Y,) = {
-                        #[item_settings data,variable,relative] y=input;
-                    };
-                    (usage = WHEEL,) = {
-                        #[item_settings data,variable,relative] wheel=input;
-                    };
-                };
-                (usage_page = CONSUMER,) = {
-                    (usage = AC_PAN,) = {
-                        #[item_settings data,variable,relative] pan=input;
-                    };
-                };
-            };
-        };
-    },
-    (collection = APPLICATION, usage_page = CONSUMER, usage = CONSUMER_CONTROL) = {
-        (report_id = 0x03,) = {
-            (usage_page = CONSUMER, usage_min = 0x00, usage_max = 0x514) = {
-            #[item_settings data,array,absolute,not_null] media_usage_id=input;
+/// Number of keyboard usages, starting from zero, covered by the [`NkroKeyboardReport`] bitmap.
+///
+/// The bitmap stops at `LANG8`, so that the whole report fits in a single notification
+/// with the default BLE ATT MTU.
+pub const NKRO_USAGES: usize = 0x98;
+
+/// Report descriptor of the [`KeyboardReport`], with report id [`ReportType::Keyboard`].
+#[rustfmt::skip]
+pub const KEYBOARD_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,       // Usage Page (Generic Desktop)
+    0x09, 0x06,       // Usage (Keyboard)
+    0xA1, 0x01,       // Collection (Application)
+    0x85, 0x01,       //   Report ID (0x01)
+    0x05, 0x07,       //   Usage Page (Keyboard/Keypad)
+    0x19, 0xE0,       //   Usage Minimum (Left Control)
+    0x29, 0xE7,       //   Usage Maximum (Right GUI)
+    0x15, 0x00,       //   Logical Minimum (0)
+    0x25, 0x01,       //   Logical Maximum (1)
+    0x75, 0x01,       //   Report Size (1)
+    0x95, 0x08,       //   Report Count (8)
+    0x81, 0x02,       //   Input (Data, Variable, Absolute): modifier
+    0x75, 0x08,       //   Report Size (8)
+    0x95, 0x01,       //   Report Count (1)
+    0x81, 0x01,       //   Input (Constant): reserved
+    0x05, 0x08,       //   Usage Page (LEDs)
+    0x19, 0x01,       //   Usage Minimum (Num Lock)
+    0x29, 0x05,       //   Usage Maximum (Kana)
+    0x75, 0x01,       //   Report Size (1)
+    0x95, 0x05,       //   Report Count (5)
+    0x91, 0x02,       //   Output (Data, Variable, Absolute): leds
+    0x75, 0x03,       //   Report Size (3)
+    0x95, 0x01,       //   Report Count (1)
+    0x91, 0x01,       //   Output (Constant): leds padding
+    0x05, 0x07,       //   Usage Page (Keyboard/Keypad)
+    0x19, 0x00,       //   Usage Minimum (0)
+    0x29, 0xDD,       //   Usage Maximum (Keypad Hexadecimal)
+    0x15, 0x00,       //   Logical Minimum (0)
+    0x26, 0xDD, 0x00, //   Logical Maximum (221)
+    0x75, 0x08,       //   Report Size (8)
+    0x95, 0x06,       //   Report Count (6)
+    0x81, 0x00,       //   Input (Data, Array, Absolute): keycodes
+    0xC0,             // End Collection
+];
+
+/// Report descriptor of the [`NkroKeyboardReport`], with report id [`ReportType::Keyboard`].
+#[rustfmt::skip]
+pub const NKRO_KEYBOARD_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,       // Usage Page (Generic Desktop)
+    0x09, 0x06,       // Usage (Keyboard)
+    0xA1, 0x01,       // Collection (Application)
+    0x85, 0x01,       //   Report ID (0x01)
+    0x05, 0x07,       //   Usage Page (Keyboard/Keypad)
+    0x19, 0xE0,       //   Usage Minimum (Left Control)
+    0x29, 0xE7,       //   Usage Maximum (Right GUI)
+    0x15, 0x00,       //   Logical Minimum (0)
+    0x25, 0x01,       //   Logical Maximum (1)
+    0x75, 0x01,       //   Report Size (1)
+    0x95, 0x08,       //   Report Count (8)
+    0x81, 0x02,       //   Input (Data, Variable, Absolute): modifier
+    0x05, 0x08,       //   Usage Page (LEDs)
+    0x19, 0x01,       //   Usage Minimum (Num Lock)
+    0x29, 0x05,       //   Usage Maximum (Kana)
+    0x95, 0x05,       //   Report Count (5)
+    0x91, 0x02,       //   Output (Data, Variable, Absolute): leds
+    0x75, 0x03,       //   Report Size (3)
+    0x95, 0x01,       //   Report Count (1)
+    0x91, 0x01,       //   Output (Constant): leds padding
+    0x05, 0x07,       //   Usage Page (Keyboard/Keypad)
+    0x19, 0x00,       //   Usage Minimum (0)
+    0x29, 0x97,       //   Usage Maximum (LANG8)
+    0x75, 0x01,       //   Report Size (1)
+    0x95, 0x98,       //   Report Count (152)
+    0x81, 0x02,       //   Input (Data, Variable, Absolute): keys
+    0xC0,             // End Collection
+];
+
+/// Report descriptor of the [`MouseReport`], with report id [`ReportType::Mouse`].
+#[rustfmt::skip]
+pub const MOUSE_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,       // Usage Page (Generic Desktop)
+    0x09, 0x02,       // Usage (Mouse)
+    0xA1, 0x01,       // Collection (Application)
+    0x85, 0x02,       //   Report ID (0x02)
+    0x09, 0x01,       //   Usage (Pointer)
+    0xA1, 0x00,       //   Collection (Physical)
+    0x05, 0x09,       //     Usage Page (Button)
+    0x19, 0x01,       //     Usage Minimum (Button 1)
+    0x29, 0x08,       //     Usage Maximum (Button 8)
+    0x15, 0x00,       //     Logical Minimum (0)
+    0x25, 0x01,       //     Logical Maximum (1)
+    0x75, 0x01,       //     Report Size (1)
+    0x95, 0x08,       //     Report Count (8)
+    0x81, 0x02,       //     Input (Data, Variable, Absolute): buttons
+    0x05, 0x01,       //     Usage Page (Generic Desktop)
+    0x09, 0x30,       //     Usage (X)
+    0x09, 0x31,       //     Usage (Y)
+    0x09, 0x38,       //     Usage (Wheel)
+    0x15, 0x81,       //     Logical Minimum (-127)
+    0x25, 0x7F,       //     Logical Maximum (127)
+    0x75, 0x08,       //     Report Size (8)
+    0x95, 0x03,       //     Report Count (3)
+    0x81, 0x06,       //     Input (Data, Variable, Relative): x, y, wheel
+    0x05, 0x0C,       //     Usage Page (Consumer)
+    0x0A, 0x38, 0x02, //     Usage (AC Pan)
+    0x95, 0x01,       //     Report Count (1)
+    0x81, 0x06,       //     Input (Data, Variable, Relative): pan
+    0xC0,             //   End Collection
+    0xC0,             // End Collection
+];
+
+/// Report descriptor of the [`MediaReport`], with report id [`ReportType::Media`].
+#[rustfmt::skip]
+pub const MEDIA_DESCRIPTOR: &[u8] = &[
+    0x05, 0x0C,       // Usage Page (Consumer)
+    0x09, 0x01,       // Usage (Consumer Control)
+    0xA1, 0x01,       // Collection (Application)
+    0x85, 0x03,       //   Report ID (0x03)
+    0x19, 0x00,       //   Usage Minimum (0)
+    0x2A, 0x14, 0x05, //   Usage Maximum (0x0514)
+    0x15, 0x00,       //   Logical Minimum (0)
+    0x26, 0x14, 0x05, //   Logical Maximum (0x0514)
+    0x75, 0x10,       //   Report Size (16)
+    0x95, 0x01,       //   Report Count (1)
+    0x81, 0x00,       //   Input (Data, Array, Absolute): usage_id
+    0xC0,             // End Collection
+];
+
+/// Report descriptor of the [`SystemReport`], with report id [`ReportType::System`].
+#[rustfmt::skip]
+pub const SYSTEM_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,       // Usage Page (Generic Desktop)
+    0x09, 0x80,       // Usage (System Control)
+    0xA1, 0x01,       // Collection (Application)
+    0x85, 0x04,       //   Report ID (0x04)
+    0x19, 0x81,       //   Usage Minimum (System Power Down)
+    0x29, 0xB7,       //   Usage Maximum (System Speaker Mute)
+    0x16, 0x81, 0x00, //   Logical Minimum (0x81)
+    0x26, 0xB7, 0x00, //   Logical Maximum (0xB7)
+    0x75, 0x08,       //   Report Size (8)
+    0x95, 0x01,       //   Report Count (1)
+    0x81, 0x40,       //   Input (Data, Array, Absolute, Null State): usage_id
+    0xC0,             // End Collection
+];
+
+/// Report descriptor of the [`GamepadReport`], with report id [`ReportType::Gamepad`].
+#[rustfmt::skip]
+pub const GAMEPAD_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,       // Usage Page (Generic Desktop)
+    0x09, 0x05,       // Usage (Game Pad)
+    0xA1, 0x01,       // Collection (Application)
+    0x85, 0x05,       //   Report ID (0x05)
+    0x09, 0x39,       //   Usage (Hat Switch)
+    0x15, 0x00,       //   Logical Minimum (0)
+    0x25, 0x07,       //   Logical Maximum (7)
+    0x35, 0x00,       //   Physical Minimum (0)
+    0x46, 0x3B, 0x01, //   Physical Maximum (315)
+    0x65, 0x14,       //   Unit (Degrees)
+    0x75, 0x04,       //   Report Size (4)
+    0x95, 0x01,       //   Report Count (1)
+    0x81, 0x42,       //   Input (Data, Variable, Absolute, Null State): hat
+    0x65, 0x00,       //   Unit (None)
+    0x81, 0x01,       //   Input (Constant): hat padding
+    0x05, 0x09,       //   Usage Page (Button)
+    0x19, 0x01,       //   Usage Minimum (Button 1)
+    0x29, 0x10,       //   Usage Maximum (Button 16)
+    0x25, 0x01,       //   Logical Maximum (1)
+    0x45, 0x01,       //   Physical Maximum (1)
+    0x75, 0x01,       //   Report Size (1)
+    0x95, 0x10,       //   Report Count (16)
+    0x81, 0x02,       //   Input (Data, Variable, Absolute): buttons
+    0xC0,             // End Collection
+];
+
+/// Report descriptor of the [`DigitizerReport`], with report id [`ReportType::Digitizer`].
+///
+/// A multi-touch screen with up to [`DigitizerReport::MAX_CONTACTS`] fingers, with
+/// absolute coordinates normalised to [`DigitizerReport::MAX_COORDINATE`], which hosts
+/// map onto their screen. The contact count maximum is read through a feature report.
+#[rustfmt::skip]
+pub const DIGITIZER_DESCRIPTOR: &[u8] = &[
+    0x05, 0x0D,       // Usage Page (Digitizers)
+    0x09, 0x04,       // Usage (Touch Screen)
+    0xA1, 0x01,       // Collection (Application)
+    0x85, 0x06,       //   Report ID (0x06)
+    0x05, 0x0D,       //   Usage Page (Digitizers)
+    0x09, 0x22,       //   Usage (Finger)
+    0xA1, 0x02,       //   Collection (Logical)
+    0x09, 0x42,       //     Usage (Tip Switch)
+    0x15, 0x00,       //     Logical Minimum (0)
+    0x25, 0x01,       //     Logical Maximum (1)
+    0x75, 0x01,       //     Report Size (1)
+    0x95, 0x01,       //     Report Count (1)
+    0x81, 0x02,       //     Input (Data, Variable, Absolute): tip
+    0x95, 0x07,       //     Report Count (7)
+    0x81, 0x01,       //     Input (Constant): padding
+    0x09, 0x51,       //     Usage (Contact Identifier)
+    0x75, 0x08,       //     Report Size (8)
+    0x95, 0x01,       //     Report Count (1)
+    0x81, 0x02,       //     Input (Data, Variable, Absolute): id
+    0x05, 0x01,       //     Usage Page (Generic Desktop)
+    0x09, 0x30,       //     Usage (X)
+    0x09, 0x31,       //     Usage (Y)
+    0x26, 0xE8, 0x03, //     Logical Maximum (1000)
+    0x75, 0x10,       //     Report Size (16)
+    0x95, 0x02,       //     Report Count (2)
+    0x81, 0x02,       //     Input (Data, Variable, Absolute): x, y
+    0xC0,             //   End Collection
+    0x05, 0x0D,       //   Usage Page (Digitizers)
+    0x09, 0x22,       //   Usage (Finger)
+    0xA1, 0x02,       //   Collection (Logical)
+    0x09, 0x42,       //     Usage (Tip Switch)
+    0x15, 0x00,       //     Logical Minimum (0)
+    0x25, 0x01,       //     Logical Maximum (1)
+    0x75, 0x01,       //     Report Size (1)
+    0x95, 0x01,       //     Report Count (1)
+    0x81, 0x02,       //     Input (Data, Variable, Absolute): tip
+    0x95, 0x07,       //     Report Count (7)
+    0x81, 0x01,       //     Input (Constant): padding
+    0x09, 0x51,       //     Usage (Contact Identifier)
+    0x75, 0x08,       //     Report Size (8)
+    0x95, 0x01,       //     Report Count (1)
+    0x81, 0x02,       //     Input (Data, Variable, Absolute): id
+    0x05, 0x01,       //     Usage Page (Generic Desktop)
+    0x09, 0x30,       //     Usage (X)
+    0x09, 0x31,       //     Usage (Y)
+    0x26, 0xE8, 0x03, //     Logical Maximum (1000)
+    0x75, 0x10,       //     Report Size (16)
+    0x95, 0x02,       //     Report Count (2)
+    0x81, 0x02,       //     Input (Data, Variable, Absolute): x, y
+    0xC0,             //   End Collection
+    0x05, 0x0D,       //   Usage Page (Digitizers)
+    0x09, 0x54,       //   Usage (Contact Count)
+    0x25, 0x02,       //   Logical Maximum (2)
+    0x75, 0x08,       //   Report Size (8)
+    0x95, 0x01,       //   Report Count (1)
+    0x81, 0x02,       //   Input (Data, Variable, Absolute): count
+    0x09, 0x55,       //   Usage (Contact Count Maximum)
+    0xB1, 0x02,       //   Feature (Data, Variable, Absolute): max count
+    0xC0,             // End Collection
+];
+
+/// Builds the report descriptor of the [`Report`]s sent by the `kontroller`, with
+/// one application collection per [`ReportType`] in use, identified by its report id.
+///
+/// The keyboard collection follows the [`KeyboardReportMode`], and the byte layout
+/// of each input report matches the `to_bytes` serialization of the corresponding
+/// report struct, without the report id.
+#[must_use]
+pub fn report_descriptor(
+    report_types: &BTreeSet<ReportType>,
+    keyboard: KeyboardReportMode,
+) -> Vec<u8> {
+    report_types
+        .iter()
+        .flat_map(|report_type| match report_type {
+            ReportType::Keyboard if keyboard == KeyboardReportMode::NKey => {
+                NKRO_KEYBOARD_DESCRIPTOR
             }
+            ReportType::Keyboard => KEYBOARD_DESCRIPTOR,
+            ReportType::Mouse => MOUSE_DESCRIPTOR,
+            ReportType::Media => MEDIA_DESCRIPTOR,
+            ReportType::System => SYSTEM_DESCRIPTOR,
+            ReportType::Gamepad => GAMEPAD_DESCRIPTOR,
+            ReportType::Digitizer => DIGITIZER_DESCRIPTOR,
+            ReportType::Unspecified => &[],
+        })
+        .copied()
+        .collect()
+}
+
+/// Returns a hash of the report descriptor, to detect when it changes between boots.
+///
+/// Uses the 32-bit FNV-1a hash, which is stable across builds and platforms.
+#[must_use]
+pub fn descriptor_hash(descriptor: &[u8]) -> u32 {
+    descriptor.iter().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(*byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Returns the bit of the keyboard reports `modifier` bitmask matching the [`KeyCode`],
+/// or `None` if the [`KeyCode`] is not a modifier.
+#[must_use]
+pub fn modifier_bit(key_code: KeyCode) -> Option<u8> {
+    (KeyCode::Lctrl as u8..=KeyCode::Rgui as u8)
+        .contains(&(key_code as u8))
+        .then(|| 1 << (key_code as u8 - KeyCode::Lctrl as u8))
+}
+
+/// State of the keyboard LEDs set by the host, through the keyboard output report.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct HostLeds(u8);
+
+impl HostLeds {
+    /// Bit of the Num Lock LED.
+    pub const NUM_LOCK: u8 = 1 << 0;
+    /// Bit of the Caps Lock LED.
+    pub const CAPS_LOCK: u8 = 1 << 1;
+    /// Bit of the Scroll Lock LED.
+    pub const SCROLL_LOCK: u8 = 1 << 2;
+    /// Bit of the Compose LED.
+    pub const COMPOSE: u8 = 1 << 3;
+    /// Bit of the Kana LED.
+    pub const KANA: u8 = 1 << 4;
+
+    /// Parses the keyboard output report written by the host, ignoring the padding bits.
+    ///
+    /// The report is a single byte, possibly prefixed by the [`ReportType::Keyboard`]
+    /// report id, as some hosts write it. Returns `None` for any other report.
+    #[must_use]
+    pub fn from_report(report: &[u8]) -> Option<Self> {
+        let bits = match *report {
+            [bits] => bits,
+            [report_id, bits] if report_id == ReportType::Keyboard as u8 => bits,
+            _ => return None,
         };
-    },
-    (collection = APPLICATION, usage_page = GENERIC_DESKTOP, usage = SYSTEM_CONTROL) = {
-        (report_id = 0x04,) = {
-            (usage_min = 0x81, usage_max = 0xB7, logical_min = 1) = {
-                #[item_settings data,array,absolute,not_null] system_usage_id=input;
-            };
-        };
+
+        Some(Self(bits & 0x1F))
+    }
+
+    /// Returns the bitmask of the LEDs turned on.
+    #[must_use]
+    pub fn bits(self) -> u8 {
+        self.0
     }
-)]
-#[allow(dead_code)]
-#[derive(Default)]
-pub struct Report {
+
+    /// Returns `true` if the LED with the given bit is turned on, e.g. [`HostLeds::CAPS_LOCK`].
+    #[must_use]
+    pub fn is_on(self, led: u8) -> bool {
+        self.0 & led != 0
+    }
+}
+
+/// Protocol mode selected by the host, through the Protocol Mode characteristic.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ProtocolMode {
+    /// The host ignores the report descriptor, and only decodes the boot keyboard
+    /// report, with the fixed layout of the [`KeyboardReport`] and no report id.
+    Boot = 0,
+    /// The host decodes the reports following the report descriptor.
+    #[default]
+    Report = 1,
+}
+
+impl ProtocolMode {
+    /// Parses the Protocol Mode characteristic value written by the host.
+    ///
+    /// Returns `None` if the value is empty, or not a known protocol mode.
+    #[must_use]
+    pub fn from_value(value: &[u8]) -> Option<Self> {
+        match value.first()? {
+            0 => Some(Self::Boot),
+            1 => Some(Self::Report),
+            _ => None,
+        }
+    }
+}
+
+/// Input report of the keyboard, with report id [`ReportType::Keyboard`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyboardReport {
+    /// Bitmask of the keyboard modifiers currently pressed.
     pub modifier: u8,
+    /// Reserved byte, always zero.
     pub reserved: u8,
-    pub leds: u8,
+    /// Keyboard key codes currently pressed.
     pub keycodes: [u8; 6],
+}
+
+impl KeyboardReport {
+    /// Size of the serialized report, in bytes.
+    pub const SIZE: usize = 8;
+
+    /// Serializes the report, following the layout of the [`KEYBOARD_DESCRIPTOR`].
+    #[must_use]
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0] = self.modifier;
+        bytes[1] = self.reserved;
+        bytes[2..].copy_from_slice(&self.keycodes);
+        bytes
+    }
+}
+
+/// N-key rollover input report of the keyboard, with report id [`ReportType::Keyboard`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NkroKeyboardReport {
+    /// Bitmask of the keyboard modifiers currently pressed.
+    pub modifier: u8,
+    /// Bitmap of the keyboard key codes currently pressed, least significant bit first.
+    pub keys: [u8; NKRO_USAGES / 8],
+}
+
+impl NkroKeyboardReport {
+    /// Size of the serialized report, in bytes.
+    pub const SIZE: usize = 1 + NKRO_USAGES / 8;
+
+    /// Marks the key code as pressed in the bitmap.
+    ///
+    /// Returns `false` if the key code is not covered by the bitmap, see [`NKRO_USAGES`].
+    pub fn press(&mut self, key_code: u8) -> bool {
+        let index = usize::from(key_code);
+        if index >= NKRO_USAGES {
+            return false;
+        }
+
+        self.keys[index / 8] |= 1 << (index % 8);
+        true
+    }
+
+    /// Returns `true` if the key code is marked as pressed in the bitmap.
+    #[must_use]
+    pub fn is_pressed(&self, key_code: u8) -> bool {
+        let index = usize::from(key_code);
+        index < NKRO_USAGES && self.keys[index / 8] & (1 << (index % 8)) != 0
+    }
+
+    /// Converts the report into the [`KeyboardReport`] layout of the boot protocol,
+    /// signalling a phantom state when more keys are pressed than it fits.
+    #[must_use]
+    pub fn to_boot(&self) -> KeyboardReport {
+        let mut report = KeyboardReport {
+            modifier: self.modifier,
+            ..KeyboardReport::default()
+        };
+
+        let pressed: Vec<u8> = (0..=u8::MAX)
+            .filter(|key_code| self.is_pressed(*key_code))
+            .collect();
+
+        if pressed.len() > report.keycodes.len() {
+            report.keycodes = [KeyCode::ErrorRollover as u8; 6];
+        } else {
+            report.keycodes[..pressed.len()].copy_from_slice(&pressed);
+        }
+
+        report
+    }
+
+    /// Serializes the report, following the layout of the [`NKRO_KEYBOARD_DESCRIPTOR`].
+    #[must_use]
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0] = self.modifier;
+        bytes[1..].copy_from_slice(&self.keys);
+        bytes
+    }
+}
+
+/// Input report of the mouse, with report id [`ReportType::Mouse`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MouseReport {
+    /// Bitmask of the mouse buttons currently pressed.
     pub buttons: u8,
+    /// Relative mouse movement on the horizontal axis.
     pub x: i8,
+    /// Relative mouse movement on the vertical axis.
     pub y: i8,
-    pub wheel: i8, // Scroll down (negative) or up (positive) this many units
-    pub pan: i8,   // Scroll left (negative) or right (positive) this many units
-    pub media_usage_id: u16,
-    pub system_usage_id: u8,
+    /// Scroll down (negative) or up (positive) this many units.
+    pub wheel: i8,
+    /// Scroll left (negative) or right (positive) this many units.
+    pub pan: i8,
+}
+
+impl MouseReport {
+    /// Size of the serialized report, in bytes.
+    pub const SIZE: usize = 5;
+
+    /// Serializes the report, following the layout of the [`MOUSE_DESCRIPTOR`].
+    #[must_use]
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        [
+            self.buttons,
+            self.x.to_le_bytes()[0],
+            self.y.to_le_bytes()[0],
+            self.wheel.to_le_bytes()[0],
+            self.pan.to_le_bytes()[0],
+        ]
+    }
+
+    /// Returns `true` if the report carries any relative movement.
+    #[must_use]
+    pub fn is_moving(&self) -> bool {
+        self.x != 0 || self.y != 0 || self.wheel != 0 || self.pan != 0
+    }
+}
+
+/// Input report of the consumer controls, with report id [`ReportType::Media`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MediaReport {
+    /// Consumer control usage id currently pressed, zero when none.
+    pub usage_id: u16,
+}
+
+impl MediaReport {
+    /// Size of the serialized report, in bytes.
+    pub const SIZE: usize = 2;
+
+    /// Serializes the report, following the layout of the [`MEDIA_DESCRIPTOR`].
+    #[must_use]
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        self.usage_id.to_le_bytes()
+    }
+}
+
+/// Input report of the system controls, with report id [`ReportType::System`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SystemReport {
+    /// System control usage id currently pressed, zero when none.
+    pub usage_id: u8,
+}
+
+impl SystemReport {
+    /// Size of the serialized report, in bytes.
+    pub const SIZE: usize = 1;
+
+    /// Serializes the report, following the layout of the [`SYSTEM_DESCRIPTOR`].
+    #[must_use]
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        [self.usage_id]
+    }
+}
+
+/// Input report of the gamepad, with report id [`ReportType::Gamepad`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GamepadReport {
+    /// Direction of the hat switch, clockwise from up in steps of 45 degrees,
+    /// or [`GamepadReport::HAT_CENTERED`] when no direction is pressed.
+    pub hat: u8,
+    /// Bitmask of the numbered gamepad buttons currently pressed.
+    pub buttons: u16,
+}
+
+impl Default for GamepadReport {
+    fn default() -> Self {
+        Self {
+            hat: Self::HAT_CENTERED,
+            buttons: 0,
+        }
+    }
+}
+
+impl GamepadReport {
+    /// Size of the serialized report, in bytes.
+    pub const SIZE: usize = 3;
+
+    /// Value of the hat switch when no direction is pressed, outside of its logical range.
+    pub const HAT_CENTERED: u8 = 8;
+
+    /// Builds the report from the [`GamepadButton`]s currently pressed, combining
+    /// the hat switch directions, e.g. up and right into up-right.
+    ///
+    /// Opposite hat switch directions cancel each other out.
+    #[must_use]
+    pub fn from_pressed(pressed: impl IntoIterator<Item = GamepadButton>) -> Self {
+        let mut report = Self::default();
+        let (mut x, mut y) = (0_i8, 0_i8);
+
+        for button in pressed {
+            match button {
+                GamepadButton::Unspecified => {}
+                GamepadButton::HatUp => y -= 1,
+                GamepadButton::HatDown => y += 1,
+                GamepadButton::HatLeft => x -= 1,
+                GamepadButton::HatRight => x += 1,
+                button => report.buttons |= 1 << (button as i32 - GamepadButton::B1 as i32),
+            }
+        }
+
+        report.hat = match (x.signum(), y.signum()) {
+            (0, -1) => 0,
+            (1, -1) => 1,
+            (1, 0) => 2,
+            (1, 1) => 3,
+            (0, 1) => 4,
+            (-1, 1) => 5,
+            (-1, 0) => 6,
+            (-1, -1) => 7,
+            _ => Self::HAT_CENTERED,
+        };
+
+        report
+    }
+
+    /// Serializes the report, following the layout of the [`GAMEPAD_DESCRIPTOR`].
+    #[must_use]
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let [low, high] = self.buttons.to_le_bytes();
+        [self.hat & 0x0F, low, high]
+    }
+}
+
+/// A finger on the touch screen, as reported by the [`DigitizerReport`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Contact {
+    /// Whether the finger touches the screen.
+    pub tip: bool,
+    /// Absolute horizontal position, from the left edge of the screen.
+    pub x: u16,
+    /// Absolute vertical position, from the top edge of the screen.
+    pub y: u16,
+}
+
+impl Contact {
+    /// Size of the serialized contact, in bytes.
+    pub const SIZE: usize = 6;
+
+    /// Serializes the contact with the specified identifier, following the layout
+    /// of a finger collection of the [`DIGITIZER_DESCRIPTOR`].
+    #[must_use]
+    pub fn to_bytes(&self, id: u8) -> [u8; Self::SIZE] {
+        let [x_low, x_high] = self.x.to_le_bytes();
+        let [y_low, y_high] = self.y.to_le_bytes();
+
+        [self.tip.into(), id, x_low, x_high, y_low, y_high]
+    }
+}
+
+/// Input report of the touch screen digitizer, with report id [`ReportType::Digitizer`].
+///
+/// Each [`Contact`] is identified by its index, and must be reported once
+/// more when the finger leaves the screen, for the host to release it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DigitizerReport {
+    /// The fingers reported, `None` for the slots not in use.
+    pub contacts: [Option<Contact>; Self::MAX_CONTACTS],
+}
+
+impl DigitizerReport {
+    /// Size of the serialized report, in bytes.
+    pub const SIZE: usize = Contact::SIZE * Self::MAX_CONTACTS + 1;
+
+    /// Maximum number of fingers touching the screen at once.
+    pub const MAX_CONTACTS: usize = 2;
+
+    /// Coordinate of the right and bottom edges of the screen.
+    pub const MAX_COORDINATE: u16 = 1000;
+
+    /// Returns the number of [`Contact`]s reported.
+    #[must_use]
+    pub fn count(&self) -> u8 {
+        self.contacts
+            .iter()
+            .flatten()
+            .count()
+            .try_into()
+            .unwrap_or(u8::MAX)
+    }
+
+    /// Returns `true` if any finger touches the screen.
+    #[must_use]
+    pub fn is_touching(&self) -> bool {
+        self.contacts.iter().flatten().any(|contact| contact.tip)
+    }
+
+    /// Serializes the report, following the layout of the [`DIGITIZER_DESCRIPTOR`].
+    ///
+    /// The slots not in use are left zeroed, and excluded from the contact count.
+    #[must_use]
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+
+        for ((id, contact), chunk) in (0..)
+            .zip(&self.contacts)
+            .zip(bytes.chunks_exact_mut(Contact::SIZE))
+        {
+            if let Some(contact) = contact {
+                chunk.copy_from_slice(&contact.to_bytes(id));
+            }
+        }
+
+        bytes[Self::SIZE - 1] = self.count();
+        bytes
+    }
+
+    /// Serializes the feature report declaring the contact count maximum.
+    #[must_use]
+    pub fn feature_bytes() -> [u8; 1] {
+        [u8::try_from(Self::MAX_CONTACTS).unwrap_or(u8::MAX)]
+    }
+}
+
+/// An input report sent to the host, one variant per [`ReportType`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Report {
+    /// A [`KeyboardReport`].
+    Keyboard(KeyboardReport),
+    /// A [`NkroKeyboardReport`], sharing the report id of the [`KeyboardReport`].
+    NkroKeyboard(NkroKeyboardReport),
+    /// A [`MouseReport`].
+    Mouse(MouseReport),
+    /// A [`MediaReport`].
+    Media(MediaReport),
+    /// A [`SystemReport`].
+    System(SystemReport),
+    /// A [`GamepadReport`].
+    Gamepad(GamepadReport),
+    /// A [`DigitizerReport`].
+    Digitizer(DigitizerReport),
+}
+
+impl Report {
+    /// Returns the [`ReportType`] of the report, whose value is the report id.
+    #[must_use]
+    pub fn report_type(&self) -> ReportType {
+        match self {
+            Self::Keyboard(_) | Self::NkroKeyboard(_) => ReportType::Keyboard,
+            Self::Mouse(_) => ReportType::Mouse,
+            Self::Media(_) => ReportType::Media,
+            Self::System(_) => ReportType::System,
+            Self::Gamepad(_) => ReportType::Gamepad,
+            Self::Digitizer(_) => ReportType::Digitizer,
+        }
+    }
+
+    /// Serializes the report, following the layout of the [`report_descriptor`].
+    ///
+    /// The report id is not included, as it is carried by the BLE characteristic
+    /// the report is sent through.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Keyboard(report) => report.to_bytes().to_vec(),
+            Self::NkroKeyboard(report) => report.to_bytes().to_vec(),
+            Self::Mouse(report) => report.to_bytes().to_vec(),
+            Self::Media(report) => report.to_bytes().to_vec(),
+            Self::System(report) => report.to_bytes().to_vec(),
+            Self::Gamepad(report) => report.to_bytes().to_vec(),
+            Self::Digitizer(report) => report.to_bytes().to_vec(),
+        }
+    }
+
+    /// Returns the boot keyboard report to send instead of this one in the boot
+    /// [`ProtocolMode`], or `None` if it is not a keyboard report.
+    #[must_use]
+    pub fn to_boot_keyboard(&self) -> Option<KeyboardReport> {
+        match self {
+            Self::Keyboard(report) => Some(*report),
+            Self::NkroKeyboard(report) => Some(report.to_boot()),
+            Self::Mouse(_)
+            | Self::Media(_)
+            | Self::System(_)
+            | Self::Gamepad(_)
+            | Self::Digitizer(_) => None,
+        }
+    }
+
+    /// Returns `true` if the report has no key pressed, no movement and no touch.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::Keyboard(report) => *report == KeyboardReport::default(),
+            Self::NkroKeyboard(report) => *report == NkroKeyboardReport::default(),
+            Self::Mouse(report) => *report == MouseReport::default(),
+            Self::Media(report) => *report == MediaReport::default(),
+            Self::System(report) => *report == SystemReport::default(),
+            Self::Gamepad(report) => *report == GamepadReport::default(),
+            Self::Digitizer(report) => !report.is_touching(),
+        }
+    }
+}
+
+impl From<KeyboardReport> for Report {
+    fn from(report: KeyboardReport) -> Self {
+        Self::Keyboard(report)
+    }
+}
+
+impl From<NkroKeyboardReport> for Report {
+    fn from(report: NkroKeyboardReport) -> Self {
+        Self::NkroKeyboard(report)
+    }
+}
+
+impl From<MouseReport> for Report {
+    fn from(report: MouseReport) -> Self {
+        Self::Mouse(report)
+    }
+}
+
+impl From<MediaReport> for Report {
+    fn from(report: MediaReport) -> Self {
+        Self::Media(report)
+    }
+}
+
+impl From<SystemReport> for Report {
+    fn from(report: SystemReport) -> Self {
+        Self::System(report)
+    }
+}
+
+impl From<GamepadReport> for Report {
+    fn from(report: GamepadReport) -> Self {
+        Self::Gamepad(report)
+    }
+}
+
+impl From<DigitizerReport> for Report {
+    fn from(report: DigitizerReport) -> Self {
+        Self::Digitizer(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    const REPORT_TYPES: [ReportType; 6] = [
+        ReportType::Keyboard,
+        ReportType::Mouse,
+        ReportType::Media,
+        ReportType::System,
+        ReportType::Gamepad,
+        ReportType::Digitizer,
+    ];
+
+    const MODES: [KeyboardReportMode; 3] = [
+        KeyboardReportMode::Unspecified,
+        KeyboardReportMode::SixKey,
+        KeyboardReportMode::NKey,
+    ];
+
+    /// Parses the short items of the descriptor, returning the size of each input
+    /// report in bytes by report id, and checking that the collections are balanced.
+    fn input_sizes(descriptor: &[u8]) -> BTreeMap<u8, usize> {
+        let mut bits = BTreeMap::new();
+        let (mut report_id, mut size, mut count, mut depth) = (0, 0, 0, 0);
+
+        let mut rest = descriptor;
+        while let Some((&prefix, tail)) = rest.split_first() {
+            let len = usize::from(prefix & 0b11);
+            assert_ne!(len, 3, "unexpected 4-byte item {prefix:#04x}");
+            let value = tail[..len]
+                .iter()
+                .rev()
+                .fold(0, |value, byte| value << 8 | usize::from(*byte));
+            rest = &tail[len..];
+
+            match prefix & !0b11 {
+                0x84 => report_id = u8::try_from(value).unwrap(),
+                0x74 => size = value,
+                0x94 => count = value,
+                0x80 => *bits.entry(report_id).or_default() += size * count,
+                0xA0 => depth += 1,
+                0xC0 => {
+                    assert!(depth > 0, "end collection without collection");
+                    depth -= 1;
+                }
+                _ => {}
+            }
+        }
+
+        assert_eq!(depth, 0, "unbalanced collections");
+        bits.into_iter()
+            .map(|(report_id, bits): (u8, usize)| (report_id, bits.div_ceil(8)))
+            .collect()
+    }
+
+    /// Returns the size of the input report serialized for the [`ReportType`].
+    fn report_size(report_type: ReportType, keyboard: KeyboardReportMode) -> usize {
+        match report_type {
+            ReportType::Keyboard if keyboard == KeyboardReportMode::NKey => {
+                NkroKeyboardReport::SIZE
+            }
+            ReportType::Keyboard => KeyboardReport::SIZE,
+            ReportType::Mouse => MouseReport::SIZE,
+            ReportType::Media => MediaReport::SIZE,
+            ReportType::System => SystemReport::SIZE,
+            ReportType::Gamepad => GamepadReport::SIZE,
+            ReportType::Digitizer => DigitizerReport::SIZE,
+            ReportType::Unspecified => 0,
+        }
+    }
+
+    #[test]
+    fn descriptor_declares_the_reports_in_use() {
+        for keyboard in MODES {
+            for report_type in REPORT_TYPES {
+                let descriptor = report_descriptor(&BTreeSet::from([report_type]), keyboard);
+                let expected = BTreeMap::from([(
+                    report_type as u8,
+                    report_size(report_type, keyboard),
+                )]);
+
+                assert_eq!(
+                    input_sizes(&descriptor),
+                    expected,
+                    "{report_type:?} {keyboard:?}"
+                );
+            }
+
+            let all = BTreeSet::from(REPORT_TYPES);
+            let expected: BTreeMap<u8, usize> = REPORT_TYPES
+                .iter()
+                .map(|report_type| (*report_type as u8, report_size(*report_type, keyboard)))
+                .collect();
+            assert_eq!(input_sizes(&report_descriptor(&all, keyboard)), expected);
+        }
+    }
+
+    #[test]
+    fn descriptor_skips_unspecified_report_type() {
+        let report_types = BTreeSet::from([ReportType::Unspecified, ReportType::Media]);
+
+        assert_eq!(
+            report_descriptor(&report_types, KeyboardReportMode::SixKey),
+            MEDIA_DESCRIPTOR
+        );
+    }
+
+    #[test]
+    fn keyboard_reports_to_bytes() {
+        let report = KeyboardReport {
+            modifier: 0x02,
+            reserved: 0,
+            keycodes: [0x04, 0x05, 0, 0, 0, 0],
+        };
+        assert_eq!(report.to_bytes(), [0x02, 0, 0x04, 0x05, 0, 0, 0, 0]);
+
+        let mut nkro = NkroKeyboardReport {
+            modifier: 0x20,
+            ..NkroKeyboardReport::default()
+        };
+        assert!(nkro.press(0x04));
+        assert!(nkro.press(0x97));
+        assert!(!nkro.press(0x98));
+
+        let mut expected = [0; NkroKeyboardReport::SIZE];
+        expected[0] = 0x20;
+        expected[1] = 0x10;
+        expected[NkroKeyboardReport::SIZE - 1] = 0x80;
+        assert_eq!(nkro.to_bytes(), expected);
+    }
+
+    #[test]
+    fn mouse_and_control_reports_to_bytes() {
+        let mouse = MouseReport {
+            buttons: 0x01,
+            x: -1,
+            y: 2,
+            wheel: -3,
+            pan: 4,
+        };
+        assert_eq!(mouse.to_bytes(), [0x01, 0xFF, 0x02, 0xFD, 0x04]);
+
+        assert_eq!(MediaReport { usage_id: 0x00E9 }.to_bytes(), [0xE9, 0x00]);
+        assert_eq!(SystemReport { usage_id: 0x81 }.to_bytes(), [0x81]);
+    }
+
+    #[test]
+    fn gamepad_report_to_bytes() {
+        assert_eq!(GamepadReport::default().to_bytes(), [0x08, 0, 0]);
+
+        let report = GamepadReport::from_pressed([
+            GamepadButton::HatUp,
+            GamepadButton::HatRight,
+            GamepadButton::B1,
+            GamepadButton::B10,
+        ]);
+        assert_eq!(report.to_bytes(), [0x01, 0x01, 0x02]);
+    }
+
+    #[test]
+    fn digitizer_report_to_bytes() {
+        let report = DigitizerReport {
+            contacts: [
+                Some(Contact {
+                    tip: true,
+                    x: 0x0102,
+                    y: 0x0304,
+                }),
+                None,
+            ],
+        };
+
+        assert_eq!(
+            report.to_bytes(),
+            [0x01, 0x00, 0x02, 0x01, 0x04, 0x03, 0, 0, 0, 0, 0, 0, 0x01]
+        );
+        assert_eq!(DigitizerReport::feature_bytes(), [0x02]);
+    }
+
+    #[test]
+    fn host_leds_from_report() {
+        assert_eq!(HostLeds::from_report(&[]), None);
+
+        let caps_lock = HostLeds::from_report(&[HostLeds::CAPS_LOCK]).unwrap();
+        assert!(caps_lock.is_on(HostLeds::CAPS_LOCK));
+        assert!(!caps_lock.is_on(HostLeds::NUM_LOCK));
+
+        let prefixed = [ReportType::Keyboard as u8, HostLeds::CAPS_LOCK];
+        assert_eq!(HostLeds::from_report(&prefixed), Some(caps_lock));
+        assert_eq!(HostLeds::from_report(&[ReportType::Mouse as u8, 0x02]), None);
+        assert_eq!(HostLeds::from_report(&[0x01, 0x02, 0x03]), None);
+    }
+
+    #[test]
+    fn host_leds_padding_bits_are_masked() {
+        let leds = HostLeds::from_report(&[0xE0 | HostLeds::NUM_LOCK | HostLeds::KANA]);
+
+        assert_eq!(leds.map(HostLeds::bits), Some(0x11));
+    }
+
+    #[test]
+    fn protocol_mode_from_value() {
+        assert_eq!(ProtocolMode::from_value(&[0]), Some(ProtocolMode::Boot));
+        assert_eq!(ProtocolMode::from_value(&[1]), Some(ProtocolMode::Report));
+        assert_eq!(ProtocolMode::from_value(&[1, 0]), Some(ProtocolMode::Report));
+        assert_eq!(ProtocolMode::from_value(&[2]), None);
+        assert_eq!(ProtocolMode::from_value(&[]), None);
+    }
+
+    #[test]
+    fn nkro_report_to_boot() {
+        let mut nkro = NkroKeyboardReport {
+            modifier: 0x02,
+            ..NkroKeyboardReport::default()
+        };
+        for key_code in [KeyCode::B, KeyCode::A, KeyCode::Enter] {
+            nkro.press(key_code as u8);
+        }
+
+        // Key codes are listed in increasing order.
+        assert_eq!(
+            nkro.to_boot().to_bytes(),
+            [0x02, 0, KeyCode::A as u8, KeyCode::B as u8, KeyCode::Enter as u8, 0, 0, 0]
+        );
+        assert_eq!(
+            Report::from(nkro).to_boot_keyboard(),
+            Some(nkro.to_boot())
+        );
+        assert_eq!(Report::from(MouseReport::default()).to_boot_keyboard(), None);
+    }
+
+    #[test]
+    fn nkro_report_to_boot_signals_rollover_past_six_keys() {
+        let mut nkro = NkroKeyboardReport {
+            modifier: 0x01,
+            ..NkroKeyboardReport::default()
+        };
+        for key_code in 0x04..0x0A {
+            nkro.press(key_code);
+        }
+        assert_eq!(nkro.to_boot().keycodes, [0x04, 0x05, 0x06, 0x07, 0x08, 0x09]);
+
+        nkro.press(0x0A);
+        assert_eq!(
+            nkro.to_boot(),
+            KeyboardReport {
+                modifier: 0x01,
+                reserved: 0,
+                keycodes: [KeyCode::ErrorRollover as u8; 6],
+            }
+        );
+    }
 }
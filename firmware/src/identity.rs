@@ -0,0 +1,176 @@
+//! Identity presented by the `kontroller` to the host, such as its name and `PnP ID`,
+//! resolved from the [`IdentitySettings`] of the [`Konfiguration`].
+//!
+//! [`Konfiguration`]: crate::proto::kontroller::v1::Konfiguration
+
+use crate::{
+    hid,
+    proto::kontroller::v1::{
+        identity::{Preset, VendorIdSource},
+        Identity as IdentitySettings,
+    },
+};
+
+/// The identity presented by the `kontroller` to the host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// The device name, advertised and shown by the host.
+    pub device_name: String,
+    /// The manufacturer name.
+    pub manufacturer: String,
+    /// The organization that assigned the `vendor_id`.
+    pub vendor_id_source: VendorIdSource,
+    /// The vendor id of the `PnP ID`.
+    pub vendor_id: u16,
+    /// The product id of the `PnP ID`.
+    pub product_id: u16,
+    /// The product version of the `PnP ID`.
+    pub product_version: u16,
+    /// The BLE appearance, advertised to the host.
+    pub appearance: u16,
+}
+
+impl Identity {
+    /// Returns the identity of the [`Preset`].
+    #[must_use]
+    pub fn preset(preset: Preset) -> Self {
+        match preset {
+            Preset::Unspecified | Preset::Kontroller => Self {
+                device_name: "kontroller".to_owned(),
+                manufacturer: "openmoto".to_owned(),
+                vendor_id_source: VendorIdSource::UsbIf,
+                vendor_id: hid::PID_CODES_VENDOR_ID,
+                // Placeholder until the `kontroller` gets its own product id.
+                product_id: hid::PID_CODES_TEST_PRODUCT_ID,
+                product_version: 0x0100,
+                appearance: hid::BLE_APPEARANCE_KEYBOARD,
+            },
+            // The identity presented by the firmware before it was configurable.
+            Preset::DmdCtl8k => Self {
+                device_name: "DMD CTL 8K".to_owned(),
+                manufacturer: "openmoto".to_owned(),
+                vendor_id_source: VendorIdSource::UsbIf,
+                vendor_id: hid::APPLE_INC_VENDOR_ID,
+                product_id: hid::APPLE_BLUETOOTH_HID_KEYBOARD_PRODUCT_ID,
+                product_version: 0x0210,
+                appearance: hid::BLE_APPEARANCE_KEYBOARD,
+            },
+            Preset::AppleKeyboard => Self {
+                device_name: "Magic Keyboard".to_owned(),
+                manufacturer: "Apple Inc.".to_owned(),
+                vendor_id_source: VendorIdSource::UsbIf,
+                vendor_id: hid::APPLE_INC_VENDOR_ID,
+                product_id: hid::APPLE_BLUETOOTH_HID_KEYBOARD_PRODUCT_ID,
+                product_version: 0x0210,
+                appearance: hid::BLE_APPEARANCE_KEYBOARD,
+            },
+        }
+    }
+
+    /// Resolves the identity from the [`IdentitySettings`], using the values of
+    /// the [`Preset`] for the fields left unset.
+    ///
+    /// Values that do not fit in 16 bits are ignored, and the preset ones used instead.
+    #[must_use]
+    pub fn resolve(settings: Option<&IdentitySettings>) -> Self {
+        let Some(settings) = settings else {
+            return Self::preset(Preset::Kontroller);
+        };
+
+        let preset = Self::preset(settings.preset());
+        let or_preset = |value: u32, preset: u16| {
+            u16::try_from(value)
+                .ok()
+                .filter(|value| *value != 0)
+                .unwrap_or(preset)
+        };
+        let or_preset_name = |value: &str, preset: String| {
+            if value.is_empty() {
+                preset
+            } else {
+                value.to_owned()
+            }
+        };
+
+        Self {
+            device_name: or_preset_name(&settings.device_name, preset.device_name),
+            manufacturer: or_preset_name(&settings.manufacturer, preset.manufacturer),
+            vendor_id_source: match settings.vendor_id_source() {
+                VendorIdSource::Unspecified => preset.vendor_id_source,
+                source => source,
+            },
+            vendor_id: or_preset(settings.vendor_id, preset.vendor_id),
+            product_id: or_preset(settings.product_id, preset.product_id),
+            product_version: or_preset(settings.product_version, preset.product_version),
+            appearance: or_preset(settings.appearance, preset.appearance),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(preset: Preset) -> IdentitySettings {
+        IdentitySettings {
+            preset: preset.into(),
+            ..IdentitySettings::default()
+        }
+    }
+
+    #[test]
+    fn unset_settings_use_the_kontroller_preset() {
+        let kontroller = Identity::preset(Preset::Kontroller);
+
+        assert_eq!(Identity::resolve(None), kontroller);
+        assert_eq!(Identity::resolve(Some(&settings(Preset::Unspecified))), kontroller);
+        assert_eq!(kontroller.vendor_id, hid::PID_CODES_VENDOR_ID);
+    }
+
+    #[test]
+    fn zero_values_are_unset() {
+        let resolved = Identity::resolve(Some(&IdentitySettings {
+            vendor_id: 0,
+            product_id: 0,
+            product_version: 0,
+            appearance: 0,
+            ..settings(Preset::AppleKeyboard)
+        }));
+
+        assert_eq!(resolved, Identity::preset(Preset::AppleKeyboard));
+    }
+
+    #[test]
+    fn set_values_override_the_preset() {
+        let resolved = Identity::resolve(Some(&IdentitySettings {
+            device_name: "remote".to_owned(),
+            vendor_id_source: VendorIdSource::UsbIf.into(),
+            vendor_id: 0x1234,
+            product_id: 0x5678,
+            product_version: 0x0300,
+            ..settings(Preset::DmdCtl8k)
+        }));
+
+        assert_eq!(
+            resolved,
+            Identity {
+                device_name: "remote".to_owned(),
+                vendor_id: 0x1234,
+                product_id: 0x5678,
+                product_version: 0x0300,
+                ..Identity::preset(Preset::DmdCtl8k)
+            }
+        );
+    }
+
+    #[test]
+    fn values_beyond_16_bits_are_ignored() {
+        let resolved = Identity::resolve(Some(&IdentitySettings {
+            product_id: 0x1_0000,
+            appearance: u32::MAX,
+            ..settings(Preset::Kontroller)
+        }));
+
+        assert_eq!(resolved, Identity::preset(Preset::Kontroller));
+    }
+}
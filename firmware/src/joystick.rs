@@ -0,0 +1,228 @@
+//! Module containing the logical abstraction of an analog thumb [`Joystick`].
+
+use crate::analog;
+
+/// Full deflection of a [`Joystick`] axis, in thousandths.
+pub const FULL_DEFLECTION: i32 = 1000;
+
+/// Default [`Calibration`] of a [`Joystick`] axis, for a 12-bit ADC.
+pub const DEFAULT_CALIBRATION: Calibration = Calibration {
+    min: 0,
+    center: 2048,
+    max: 4095,
+    invert: false,
+};
+
+/// Calibration of a [`Joystick`] axis, expressed in raw ADC readings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Calibration {
+    /// The reading at the minimum deflection of the axis.
+    pub min: u16,
+    /// The reading with the axis at rest.
+    pub center: u16,
+    /// The reading at the maximum deflection of the axis.
+    pub max: u16,
+    /// Inverts the direction of the axis.
+    pub invert: bool,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        DEFAULT_CALIBRATION
+    }
+}
+
+impl Calibration {
+    /// Returns the deflection of the raw ADC reading, in thousandths,
+    /// from `-FULL_DEFLECTION` to `FULL_DEFLECTION`.
+    #[must_use]
+    pub fn deflection(&self, reading: u16) -> i32 {
+        let (reading, min, center, max) = (
+            i32::from(reading),
+            i32::from(self.min),
+            i32::from(self.center),
+            i32::from(self.max),
+        );
+
+        let deflection = if reading >= center {
+            (reading - center) * FULL_DEFLECTION / (max - center).max(1)
+        } else {
+            (reading - center) * FULL_DEFLECTION / (center - min).max(1)
+        }
+        .clamp(-FULL_DEFLECTION, FULL_DEFLECTION);
+
+        if self.invert {
+            -deflection
+        } else {
+            deflection
+        }
+    }
+}
+
+/// Configuration of the [`Joystick`] response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Config {
+    /// Calibration of the horizontal axis, positive to the right.
+    pub x: Calibration,
+    /// Calibration of the vertical axis, positive downwards.
+    pub y: Calibration,
+    /// Deflection around the center that is ignored, in thousandths.
+    pub deadzone: i32,
+    /// Response curve, from linear (0) to fully cubic (100).
+    pub expo: i32,
+}
+
+impl Config {
+    /// Applies the deadzone and response curve to the deflection of an axis.
+    fn respond(&self, deflection: i32) -> i32 {
+        let deadzone = self.deadzone.clamp(0, FULL_DEFLECTION - 1);
+        if deflection.abs() <= deadzone {
+            return 0;
+        }
+
+        // Rescale the deflection outside the deadzone, so that
+        // there is no jump when leaving it.
+        let deflection = deflection.signum() * (deflection.abs() - deadzone) * FULL_DEFLECTION
+            / (FULL_DEFLECTION - deadzone);
+
+        let expo = self.expo.clamp(0, 100);
+        let cubic = deflection * deflection / FULL_DEFLECTION * deflection / FULL_DEFLECTION;
+
+        ((100 - expo) * deflection + expo * cubic) / 100
+    }
+}
+
+/// Logical representation of an analog thumb joystick, whose axes
+/// are connected to two ADC [`analog::Channel`]s.
+pub struct Joystick {
+    x: Box<dyn analog::Channel>,
+    y: Box<dyn analog::Channel>,
+    config: Config,
+}
+
+impl Joystick {
+    /// Builds a [`Joystick`] instance sampling the given horizontal and vertical
+    /// [`analog::Channel`]s, using the specified [`Config`].
+    pub fn new(
+        x: impl analog::Channel + 'static,
+        y: impl analog::Channel + 'static,
+        config: Config,
+    ) -> Self {
+        Self {
+            x: Box::new(x),
+            y: Box::new(y),
+            config,
+        }
+    }
+
+    /// Replaces the [`Config`] used by the [`Joystick`].
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    /// Samples the [`analog::Channel`]s, and returns the horizontal and vertical
+    /// deflection of the [`Joystick`], in thousandths, after applying the deadzone
+    /// and response curve.
+    pub fn read(&mut self) -> (i32, i32) {
+        let x = self.config.x.deflection(self.x.read());
+        let y = self.config.y.deflection(self.y.read());
+
+        (self.config.respond(x), self.config.respond(y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analog::tests::FakeChannel;
+
+    #[test]
+    fn calibration_maps_the_readings_to_the_deflection() {
+        let calibration = DEFAULT_CALIBRATION;
+
+        assert_eq!(calibration.deflection(2048), 0);
+        assert_eq!(calibration.deflection(4095), FULL_DEFLECTION);
+        assert_eq!(calibration.deflection(0), -FULL_DEFLECTION);
+        assert_eq!(calibration.deflection(3072), 500);
+        assert_eq!(calibration.deflection(1024), -500);
+    }
+
+    #[test]
+    fn calibration_handles_off_center_and_out_of_range_readings() {
+        let calibration = Calibration {
+            min: 400,
+            center: 1000,
+            max: 3600,
+            invert: false,
+        };
+
+        assert_eq!(calibration.deflection(700), -500);
+        assert_eq!(calibration.deflection(2300), 500);
+        assert_eq!(calibration.deflection(0), -FULL_DEFLECTION);
+        assert_eq!(calibration.deflection(4095), FULL_DEFLECTION);
+
+        let inverted = Calibration {
+            invert: true,
+            ..calibration
+        };
+        assert_eq!(inverted.deflection(2300), -500);
+    }
+
+    #[test]
+    fn deadzone_ignores_small_deflections_without_a_jump_outside() {
+        let config = Config {
+            deadzone: 100,
+            ..Config::default()
+        };
+
+        assert_eq!(config.respond(100), 0);
+        assert_eq!(config.respond(-100), 0);
+        assert_eq!(config.respond(109), 10);
+        assert_eq!(config.respond(550), 500);
+        assert_eq!(config.respond(-FULL_DEFLECTION), -FULL_DEFLECTION);
+    }
+
+    #[test]
+    fn expo_softens_the_response_around_the_center() {
+        let config = Config {
+            expo: 100,
+            ..Config::default()
+        };
+
+        assert_eq!(config.respond(500), 125);
+        assert_eq!(config.respond(-500), -125);
+        assert_eq!(config.respond(FULL_DEFLECTION), FULL_DEFLECTION);
+
+        let half = Config {
+            expo: 50,
+            ..Config::default()
+        };
+        assert_eq!(half.respond(500), 312);
+    }
+
+    #[test]
+    fn read_applies_the_calibration_of_each_axis() {
+        let (x, y) = (FakeChannel::new(2048), FakeChannel::new(2048));
+        let mut joystick = Joystick::new(
+            x.clone(),
+            y.clone(),
+            Config {
+                y: Calibration {
+                    invert: true,
+                    ..DEFAULT_CALIBRATION
+                },
+                deadzone: 50,
+                ..Config::default()
+            },
+        );
+
+        assert_eq!(joystick.read(), (0, 0));
+
+        x.set(4095);
+        y.set(0);
+        assert_eq!(joystick.read(), (FULL_DEFLECTION, FULL_DEFLECTION));
+
+        x.set(2100);
+        assert_eq!(joystick.read().0, 0);
+    }
+}
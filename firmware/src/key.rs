@@ -1,6 +1,7 @@
 //! Module containing logical abstraction for a physical, debounced [`Key`].
 
 use embassy_time::{Duration, Instant};
+#[cfg(target_os = "espidf")]
 use esp_idf_svc::{
     hal::gpio::{AnyIOPin, Input, PinDriver, Pull},
     sys::EspError,
@@ -17,10 +18,129 @@ pub const DEFAULT_RELEASE_TIMEOUT: Duration = Duration::from_millis(1);
 /// Default hold timeout used by the [`Key`] to detect when it is being long-pressed.
 pub const DEFAULT_HOLD_TIMEOUT: Duration = Duration::from_millis(500);
 
-/// Default hold repeat timeout, used to trigger an additional [`Event::Down`] when the [`Key`]
-/// is still pressed down.
+/// Default hold repeat timeout, used to trigger an additional [`Event::Repeat`] when the [`Key`]
+/// is still pressed down and [`Repeat::Fixed`] auto-repeat has been enabled.
 pub const DEFAULT_HOLD_REPEAT_TIMEOUT: Duration = Duration::from_millis(100);
 
+/// Policy used by the [`Key`] to trigger consecutive [`Event::Repeat`]
+/// while in [`State::Held`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Repeat {
+    /// The [`Key`] does not auto-repeat, leaving key repeat to the host.
+    #[default]
+    None,
+    /// The [`Key`] repeats at a fixed interval.
+    Fixed(Duration),
+    /// The [`Key`] repeats at an increasing rate, starting from `min_rate`
+    /// and linearly reaching `max_rate` after having repeated for `ramp`.
+    Accelerating {
+        /// The initial repeat rate, in repeats per second.
+        min_rate: u32,
+        /// The final repeat rate, in repeats per second.
+        max_rate: u32,
+        /// The time it takes to go from `min_rate` to `max_rate`.
+        ramp: Duration,
+    },
+}
+
+impl Repeat {
+    /// Returns the interval to wait before the next [`Event::Repeat`],
+    /// given how long the [`Key`] has been repeating for.
+    ///
+    /// Returns `None` if auto-repeat is disabled.
+    #[must_use]
+    pub fn interval(&self, repeating_for: Duration) -> Option<Duration> {
+        match *self {
+            Self::None => None,
+            Self::Fixed(interval) => Some(interval),
+            Self::Accelerating {
+                min_rate,
+                max_rate,
+                ramp,
+            } => {
+                let (min_rate, max_rate) = (u64::from(min_rate), u64::from(max_rate));
+                let ramp = ramp.as_micros();
+
+                let rate = if ramp == 0 {
+                    max_rate
+                } else {
+                    let progress = repeating_for.as_micros().min(ramp);
+                    let step = u128::from(min_rate.abs_diff(max_rate)) * u128::from(progress)
+                        / u128::from(ramp);
+                    // The step is never larger than the difference between the two rates.
+                    let step = u64::try_from(step).unwrap_or(u64::MAX);
+
+                    if max_rate >= min_rate {
+                        min_rate + step
+                    } else {
+                        min_rate - step
+                    }
+                };
+
+                Some(Duration::from_micros(1_000_000 / rate.max(1)))
+            }
+        }
+    }
+}
+
+/// Filter rejecting the chatter of a [`Pin`] signal caused by vibrations,
+/// applied to the raw samples before the [`Key`] state machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VibrationFilter {
+    /// Time the signal must be continuously low before a press is accepted.
+    pub min_press: Duration,
+    /// Pulses shorter than this, in either direction, are rejected.
+    pub min_pulse: Duration,
+    /// Minimum time between two changes of the filtered signal,
+    /// limiting its toggle rate.
+    pub min_toggle_interval: Duration,
+}
+
+impl VibrationFilter {
+    /// Returns the time the signal must be stable on the new level
+    /// before the filtered signal follows it.
+    fn stable_for(&self, low: bool) -> Duration {
+        if low {
+            self.min_press.max(self.min_pulse)
+        } else {
+            self.min_pulse
+        }
+    }
+}
+
+/// State of the [`VibrationFilter`] applied to the samples of a [`Key`].
+#[derive(Debug, Clone, Copy, Default)]
+struct Filtered {
+    /// The filtered signal level.
+    low: bool,
+    /// When the raw signal started differing from the filtered one.
+    since: Option<Instant>,
+    /// When the filtered signal last changed.
+    toggled: Option<Instant>,
+}
+
+impl Filtered {
+    fn update(&mut self, filter: &VibrationFilter, low: bool, now: Instant) -> bool {
+        if low == self.low {
+            self.since = None;
+            return self.low;
+        }
+
+        let since = *self.since.get_or_insert(now);
+        let rate_limited = self
+            .toggled
+            .is_some_and(|toggled| now - toggled < filter.min_toggle_interval);
+
+        if now - since >= filter.stable_for(low) && !rate_limited {
+            self.low = low;
+            self.since = None;
+            self.toggled = Some(now);
+        }
+
+        self.low
+    }
+}
+
 /// Configuration for the [`Key`] state machine transition.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Config {
@@ -32,9 +152,11 @@ pub struct Config {
     pub release: Duration,
     /// Hold timeout is used to detect long-presses on the [`Key`].
     pub hold: Duration,
-    /// Repeat timeout used to trigger consecutive [`Event::Down`] when the [`Key`]
+    /// Repeat policy used to trigger consecutive [`Event::Repeat`] when the [`Key`]
     /// is in [`State::Held`].
-    pub hold_repeat: Duration,
+    pub repeat: Repeat,
+    /// Optional filter applied to the [`Pin`] samples to reject vibrations.
+    pub filter: Option<VibrationFilter>,
 }
 
 impl Default for Config {
@@ -43,7 +165,8 @@ impl Default for Config {
             debounce: DEFAULT_DEBOUNCE_TIMEOUT,
             release: DEFAULT_RELEASE_TIMEOUT,
             hold: DEFAULT_HOLD_TIMEOUT,
-            hold_repeat: DEFAULT_HOLD_REPEAT_TIMEOUT,
+            repeat: Repeat::None,
+            filter: None,
         }
     }
 }
@@ -54,7 +177,7 @@ enum State {
     Released,
     Down(Instant),
     Pressed(Instant),
-    Held(Instant),
+    Held { since: Instant, last: Instant },
     Up(Instant),
 }
 
@@ -63,23 +186,91 @@ enum State {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Event {
     /// The [`Key`] has been depressed.
-    Up,
-    /// The [`Key`] has been pressed, or it is still being pressed.
-    Down,
+    Up {
+        /// When the release has been detected.
+        at: Instant,
+        /// How long the [`Key`] has been pressed for.
+        pressed_for: Duration,
+    },
+    /// The [`Key`] has been pressed.
+    Down {
+        /// When the press has been detected.
+        at: Instant,
+    },
+    /// The [`Key`] is still being pressed, and auto-repeat is enabled.
+    Repeat {
+        /// When the repeat has been triggered.
+        at: Instant,
+        /// How long the [`Key`] has been pressed for.
+        pressed_for: Duration,
+    },
+}
+
+impl Event {
+    /// Returns the point in time the [`Event`] has been detected at.
+    #[must_use]
+    pub fn at(&self) -> Instant {
+        match *self {
+            Self::Up { at, .. } | Self::Down { at } | Self::Repeat { at, .. } => at,
+        }
+    }
+}
+
+/// A digital input signal that can be sampled by a [`Key`].
+///
+/// Keys are wired using pull-up resistors, so a pressed [`Key`] reads as low.
+pub trait Pin {
+    /// Returns `true` if the signal is currently low, i.e. the [`Key`] is pressed.
+    fn is_low(&self) -> bool;
+
+    /// Returns `true` if the signal is currently high, i.e. the [`Key`] is depressed.
+    fn is_high(&self) -> bool {
+        !self.is_low()
+    }
+}
+
+impl<P: Pin + ?Sized> Pin for Box<P> {
+    fn is_low(&self) -> bool {
+        P::is_low(self)
+    }
+}
+
+#[cfg(target_os = "espidf")]
+impl<'d> Pin for PinDriver<'d, AnyIOPin, Input> {
+    fn is_low(&self) -> bool {
+        PinDriver::is_low(self)
+    }
+}
+
+/// A source of [`Pin`] signals that must be sampled all at once, such as a key matrix,
+/// before updating the [`Key`]s reading them.
+pub trait Scan {
+    /// Samples all the signals of the source at the specified point in time.
+    fn scan(&mut self, now: Instant);
+}
+
+impl<S: Scan + ?Sized> Scan for Box<S> {
+    fn scan(&mut self, now: Instant) {
+        S::scan(self, now);
+    }
 }
 
 /// Logical representation of a physical key, or button, that is connected
 /// to a microcontroller pin using pull-up resistors (or no resistors at all).
 ///
-/// Use [`Key::try_from`] to build a new [`Key`] instance using the default
-/// [`Config`] value.
-pub struct Key<'d> {
-    pin: PinDriver<'d, AnyIOPin, Input>,
+/// Use [`Key::new`] to build a new [`Key`] instance from any [`Pin`],
+/// or [`Key::try_from`] to build one from a GPIO pin using the default [`Config`] value.
+pub struct Key<P> {
+    pin: P,
     state: State,
     config: Config,
+    filtered: Filtered,
+    /// When the last [`Event::Down`] has been detected, until released.
+    pressed_at: Option<Instant>,
 }
 
-impl<'d> Key<'d> {
+#[cfg(target_os = "espidf")]
+impl<'d> Key<PinDriver<'d, AnyIOPin, Input>> {
     /// Builds a [`Key`] instance from a given GPIO pin.
     ///
     /// # Errors
@@ -89,11 +280,30 @@ impl<'d> Key<'d> {
         let mut pin_driver = PinDriver::input(pin.into())?;
         pin_driver.set_pull(Pull::Up)?;
 
-        Ok(Self {
-            pin: pin_driver,
-            config: Config::default(),
+        Ok(Self::new(pin_driver, Config::default()))
+    }
+}
+
+impl<P: Pin> Key<P> {
+    /// Builds a [`Key`] instance sampling the given [`Pin`], using the specified [`Config`].
+    pub fn new(pin: P, config: Config) -> Self {
+        Self {
+            pin,
+            config,
             state: State::Released,
-        })
+            filtered: Filtered::default(),
+            pressed_at: None,
+        }
+    }
+
+    /// Replaces the [`Config`] used by the [`Key`] state machine.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    /// Returns `true` if the [`Pin`] currently reads low, bypassing debouncing and filtering.
+    pub fn is_low(&self) -> bool {
+        self.pin.is_low()
     }
 
     /// Updates the internal state of the [`Key`] based on the current timestamp.
@@ -104,57 +314,76 @@ impl<'d> Key<'d> {
     /// Returns an optional [`Event`] if the state machine transition
     /// has detected one.
     pub fn update(&mut self, now: Instant) -> Option<Event> {
+        let low = self.sample(now);
+
         match self.state {
             State::Released => {
-                if self.pin.is_low() {
+                if low {
                     self.state = State::Down(now);
                 }
                 None
             }
             State::Down(last) => {
-                if self.pin.is_low() && self.debounced(now, last) {
+                if low && self.debounced(now, last) {
                     self.state = State::Pressed(now);
-                    return Some(Event::Down);
+                    self.pressed_at = Some(now);
+                    return Some(Event::Down { at: now });
                 }
 
-                if self.pin.is_high() {
-                    self.state = State::Up(now);
+                // A bounce shorter than the debounce timeout, the Key was never pressed.
+                if !low {
+                    self.state = State::Released;
                 }
 
                 None
             }
             State::Pressed(last) => {
-                if self.pin.is_low() && self.held(now, last) {
-                    self.state = State::Held(now);
-                    return Some(Event::Down);
+                if low && self.held(now, last) {
+                    self.state = State::Held {
+                        since: now,
+                        last: now,
+                    };
+                    return (self.config.repeat != Repeat::None).then_some(Event::Repeat {
+                        at: now,
+                        pressed_for: self.pressed_for(now),
+                    });
                 }
 
-                if self.pin.is_high() {
+                if !low {
                     self.state = State::Up(now);
                 }
 
                 None
             }
-            State::Held(last) => {
-                if self.pin.is_low() && self.still_held(now, last) {
-                    self.state = State::Held(now);
-                    return Some(Event::Down);
+            State::Held { since, last } => {
+                if low && self.still_held(now, since, last) {
+                    self.state = State::Held { since, last: now };
+                    return Some(Event::Repeat {
+                        at: now,
+                        pressed_for: self.pressed_for(now),
+                    });
                 }
 
-                if self.pin.is_high() {
+                if !low {
                     self.state = State::Up(now);
                 }
 
                 None
             }
             State::Up(last) => {
-                if self.pin.is_high() && self.released(now, last) {
+                if !low && self.released(now, last) {
                     self.state = State::Released;
-                    return Some(Event::Up);
+                    let pressed_for = self.pressed_for(now);
+                    self.pressed_at = None;
+                    return Some(Event::Up {
+                        at: now,
+                        pressed_for,
+                    });
                 }
 
-                if self.pin.is_low() && !self.released(now, last) {
-                    self.state = State::Down(now);
+                // A bounce shorter than the release timeout, the Key is still pressed.
+                if low && !self.released(now, last) {
+                    self.state = State::Pressed(now);
                 }
 
                 None
@@ -162,6 +391,20 @@ impl<'d> Key<'d> {
         }
     }
 
+    /// Samples the [`Pin`], applying the [`VibrationFilter`] if configured.
+    fn sample(&mut self, now: Instant) -> bool {
+        let low = self.pin.is_low();
+        match &self.config.filter {
+            Some(filter) => self.filtered.update(filter, low, now),
+            None => low,
+        }
+    }
+
+    /// Returns how long the [`Key`] has been pressed for, since the last [`Event::Down`].
+    fn pressed_for(&self, now: Instant) -> Duration {
+        self.pressed_at.map_or(Duration::MIN, |at| now - at)
+    }
+
     fn debounced(&self, now: Instant, last: Instant) -> bool {
         now - last >= self.config.debounce
     }
@@ -174,7 +417,315 @@ impl<'d> Key<'d> {
         now - last >= self.config.hold
     }
 
-    fn still_held(&self, now: Instant, last: Instant) -> bool {
-        now - last >= self.config.hold_repeat
+    fn still_held(&self, now: Instant, since: Instant, last: Instant) -> bool {
+        self.config
+            .repeat
+            .interval(last - since)
+            .is_some_and(|interval| now - last >= interval)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+
+    /// A [`Pin`] whose level is set by the test, shared with the [`Key`] sampling it.
+    #[derive(Debug, Clone, Default)]
+    pub(crate) struct FakePin(Rc<Cell<bool>>);
+
+    impl FakePin {
+        pub(crate) fn set_low(&self, low: bool) {
+            self.0.set(low);
+        }
+    }
+
+    impl Pin for FakePin {
+        fn is_low(&self) -> bool {
+            self.0.get()
+        }
+    }
+
+    /// Updates the [`Key`] every `step_micros` until `until_micros`, with the [`Pin`]
+    /// following `level`, and returns the detected [`Event`]s.
+    fn run(
+        config: Config,
+        until_micros: u64,
+        step_micros: u64,
+        level: impl Fn(u64) -> bool,
+    ) -> Vec<Event> {
+        let pin = FakePin::default();
+        let mut key = Key::new(pin.clone(), config);
+
+        (0..until_micros)
+            .step_by(usize::try_from(step_micros).unwrap())
+            .filter_map(|micros| {
+                pin.set_low(level(micros));
+                key.update(Instant::from_micros(micros))
+            })
+            .collect()
+    }
+
+    fn repeats(events: &[Event]) -> Vec<Instant> {
+        events
+            .iter()
+            .filter(|event| matches!(event, Event::Repeat { .. }))
+            .map(Event::at)
+            .collect()
+    }
+
+    const ACCELERATING: Repeat = Repeat::Accelerating {
+        min_rate: 10,
+        max_rate: 50,
+        ramp: Duration::from_secs(1),
+    };
+
+    #[test]
+    fn accelerating_repeat_starts_after_the_hold_timeout() {
+        let config = Config {
+            repeat: ACCELERATING,
+            ..Config::default()
+        };
+        let events = run(config, 650_000, 1000, |_| true);
+
+        assert_eq!(
+            events,
+            [
+                Event::Down {
+                    at: Instant::from_millis(1)
+                },
+                Event::Repeat {
+                    at: Instant::from_millis(501),
+                    pressed_for: Duration::from_millis(500),
+                },
+                Event::Repeat {
+                    at: Instant::from_millis(601),
+                    pressed_for: Duration::from_millis(600),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn accelerating_repeat_interval_ramps_between_the_rates() {
+        let interval = |millis| ACCELERATING.interval(Duration::from_millis(millis));
+
+        assert_eq!(interval(0), Some(Duration::from_millis(100)));
+        assert_eq!(interval(250), Some(Duration::from_millis(50)));
+        assert_eq!(interval(500), Some(Duration::from_micros(33_333)));
+        assert_eq!(interval(750), Some(Duration::from_micros(25_000)));
+        assert_eq!(interval(1000), Some(Duration::from_millis(20)));
+
+        let decelerating = Repeat::Accelerating {
+            min_rate: 50,
+            max_rate: 10,
+            ramp: Duration::from_secs(1),
+        };
+        assert_eq!(
+            decelerating.interval(Duration::from_millis(500)),
+            Some(Duration::from_micros(33_333))
+        );
+    }
+
+    #[test]
+    fn accelerating_repeat_interval_is_floored_at_the_max_rate() {
+        assert_eq!(
+            ACCELERATING.interval(Duration::from_secs(60)),
+            Some(Duration::from_millis(20))
+        );
+
+        let instant = Repeat::Accelerating {
+            min_rate: 10,
+            max_rate: 50,
+            ramp: Duration::MIN,
+        };
+        assert_eq!(instant.interval(Duration::MIN), Some(Duration::from_millis(20)));
+
+        let stopped = Repeat::Accelerating {
+            min_rate: 0,
+            max_rate: 0,
+            ramp: Duration::from_secs(1),
+        };
+        assert_eq!(stopped.interval(Duration::MIN), Some(Duration::from_secs(1)));
+
+        let config = Config {
+            repeat: ACCELERATING,
+            ..Config::default()
+        };
+        let repeats = repeats(&run(config, 3_000_000, 1000, |_| true));
+        let intervals: Vec<Duration> = repeats.windows(2).map(|pair| pair[1] - pair[0]).collect();
+
+        assert!(intervals.windows(2).all(|pair| pair[1] <= pair[0]), "{intervals:?}");
+        assert!(intervals.iter().all(|interval| *interval >= Duration::from_millis(20)));
+        assert_eq!(intervals.last(), Some(&Duration::from_millis(20)));
+    }
+
+    const FILTER: VibrationFilter = VibrationFilter {
+        min_press: Duration::from_millis(5),
+        min_pulse: Duration::from_millis(2),
+        min_toggle_interval: Duration::from_millis(20),
+    };
+
+    fn filtered() -> Config {
+        Config {
+            filter: Some(FILTER),
+            ..Config::default()
+        }
+    }
+
+    /// Returns a pseudo-random level, changing at most every 100us.
+    fn noise(micros: u64) -> bool {
+        (micros / 100).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 63 == 1
+    }
+
+    #[test]
+    fn filter_lets_a_real_press_through() {
+        let events = run(filtered(), 100_000, 100, |micros| {
+            (10_000..60_000).contains(&micros)
+        });
+
+        assert_eq!(
+            events,
+            [
+                Event::Down {
+                    at: Instant::from_micros(15_500)
+                },
+                Event::Up {
+                    at: Instant::from_micros(63_000),
+                    pressed_for: Duration::from_micros(47_500),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn filter_rejects_a_vibration_burst() {
+        let events = run(filtered(), 100_000, 100, |micros| {
+            (10_000..60_000).contains(&micros) && noise(micros)
+        });
+
+        assert_eq!(events, []);
+    }
+
+    #[test]
+    fn filter_lets_a_press_starting_with_chatter_through_once() {
+        let events = run(filtered(), 100_000, 100, |micros| match micros {
+            10_000..=14_999 => noise(micros),
+            15_000..=59_999 => true,
+            _ => false,
+        });
+
+        assert!(
+            matches!(events[..], [Event::Down { .. }, Event::Up { .. }]),
+            "{events:?}"
+        );
+    }
+
+    #[test]
+    fn filter_limits_the_toggle_rate() {
+        // 6ms presses every 10ms, each long enough to pass the filter on its own.
+        let events = run(filtered(), 100_000, 100, |micros| micros % 10_000 < 6_000);
+        let downs: Vec<Instant> = events
+            .iter()
+            .filter(|event| matches!(event, Event::Down { .. }))
+            .map(Event::at)
+            .collect();
+
+        assert!(!downs.is_empty());
+        assert!(
+            downs
+                .windows(2)
+                .all(|pair| pair[1] - pair[0] >= FILTER.min_toggle_interval * 2),
+            "{downs:?}"
+        );
+    }
+
+    #[test]
+    fn filter_rejects_a_vibration_the_debounce_lets_through() {
+        // 1ms pulses every 2.5ms, long enough to pass the debounce on their own.
+        let vibration = |micros| (10_000..60_000).contains(&micros) && micros % 2_500 < 1_000;
+
+        assert_eq!(run(filtered(), 100_000, 100, vibration), []);
+        assert_eq!(run(Config::default(), 100_000, 100, vibration).len(), 40);
+    }
+
+    #[test]
+    fn bounce_shorter_than_the_debounce_is_ignored() {
+        let events = run(Config::default(), 20_000, 100, |micros| {
+            (10_000..10_300).contains(&micros)
+        });
+
+        assert_eq!(events, []);
+    }
+
+    #[test]
+    fn press_and_release_are_debounced() {
+        let events = run(Config::default(), 100_000, 100, |micros| {
+            (10_000..60_000).contains(&micros)
+        });
+
+        assert_eq!(
+            events,
+            [
+                Event::Down {
+                    at: Instant::from_micros(10_500)
+                },
+                Event::Up {
+                    at: Instant::from_micros(61_000),
+                    pressed_for: Duration::from_micros(50_500),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn bounce_on_release_does_not_press_again() {
+        let events = run(Config::default(), 100_000, 100, |micros| {
+            (10_000..60_000).contains(&micros) || (60_300..60_500).contains(&micros)
+        });
+
+        assert_eq!(
+            events,
+            [
+                Event::Down {
+                    at: Instant::from_micros(10_500)
+                },
+                Event::Up {
+                    at: Instant::from_micros(61_500),
+                    pressed_for: Duration::from_micros(51_000),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fixed_repeat_follows_the_hold_timeout() {
+        let config = Config {
+            repeat: Repeat::Fixed(DEFAULT_HOLD_REPEAT_TIMEOUT),
+            ..Config::default()
+        };
+        let events = run(config, 800_000, 1000, |micros| micros < 700_000);
+
+        assert_eq!(
+            events,
+            [
+                Event::Down {
+                    at: Instant::from_millis(1)
+                },
+                Event::Repeat {
+                    at: Instant::from_millis(501),
+                    pressed_for: Duration::from_millis(500),
+                },
+                Event::Repeat {
+                    at: Instant::from_millis(601),
+                    pressed_for: Duration::from_millis(600),
+                },
+                Event::Up {
+                    at: Instant::from_millis(701),
+                    pressed_for: Duration::from_millis(700),
+                },
+            ]
+        );
     }
 }
@@ -1,61 +1,385 @@
 //! Abstractions to build a controller layout.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 use embassy_time::{Duration, Instant, Timer};
-use esp_idf_svc::{hal::gpio::AnyIOPin, sys::EspError};
 use futures::{channel::mpsc::Sender, SinkExt};
 
 use crate::{
-    hid,
+    chord, encoder, gesture, hid, joystick,
     key::{self, Key as HwKey},
     proto::kontroller::{
-        hid::v1::KeyCode,
-        v1::{keymap::Entry, Button, Keymap, Konfiguration},
+        hid::v1::{GamepadButton, KeyCode, KeyboardReportMode, ReportType},
+        v1::{
+            joystick_settings::Mode,
+            keymap::{entry::Action, Entry},
+            repeat_policy::Policy,
+            identity, AxisCalibration, Button, ButtonSettings, Identity, Keymap, Konfiguration,
+            SystemAction,
+        },
     },
+    status::Status,
+    touch, trace,
 };
 
+/// Maximum number of key codes that fit in a single [`hid::KeyboardReport`].
+const MAX_KEYCODES: usize = 6;
+
+/// Default time available to confirm a dangerous [`SystemAction`], by triggering it again.
+pub const DEFAULT_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default time after which a one-shot key, waiting for the next key press, is cancelled.
+pub const DEFAULT_ONE_SHOT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Default time after which a [`Button`] held down is considered stuck, and quarantined,
+/// once the stuck button detection is configured.
+pub const DEFAULT_STUCK_BUTTON_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Default speed of the pointer driven by the joystick at full deflection, in units per second.
+pub const DEFAULT_POINTER_SPEED: u32 = 600;
+
+/// Default speed of the scroll driven by the joystick at full deflection, in units per second.
+pub const DEFAULT_SCROLL_SPEED: u32 = 20;
+
+/// Default deflection past which the joystick presses a directional pad [`Button`],
+/// in thousandths of the full deflection.
+pub const DEFAULT_DPAD_THRESHOLD: i32 = 500;
+
+/// Upper bound of the joystick speed, in units per second.
+const MAX_JOYSTICK_SPEED: u32 = 100_000;
+
+/// Time between two mouse reports driven by the joystick.
+const JOYSTICK_REPORT_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Time allowed to press all the [`Button`]s of a gesture made of more than one,
+/// before the ones already pressed are sent to the host.
+const COMBO_WINDOW: Duration = Duration::from_millis(100);
+
+/// Builds a [`Keymap`] out of a list of ([`Button`], [`KeyCode`]) associations.
 pub fn make_keymap(it: impl IntoIterator<Item = (Button, KeyCode)>) -> Keymap {
     Keymap {
         entries: it
             .into_iter()
             .map(|(button, key_code)| Entry {
                 button: button.into(),
-                key_code: key_code.into(),
+                action: Some(Action::KeyCode(key_code.into())),
             })
             .collect(),
     }
 }
 
+/// Returns the [`Konfiguration`] used by the `kontroller` when none has been provided.
+#[must_use]
+pub fn default_konfiguration() -> Konfiguration {
+    Konfiguration {
+        buttons_poll_interval_micros: 500,
+        keymap: Some(make_keymap([
+            (Button::Enter, KeyCode::Enter),
+            (Button::Up, KeyCode::Up),
+            (Button::Right, KeyCode::Right),
+            (Button::Left, KeyCode::Left),
+            (Button::Down, KeyCode::Down),
+            (Button::Fn1, KeyCode::F7),
+            (Button::Fn2, KeyCode::F6),
+            (Button::Fn3, KeyCode::F5),
+        ])),
+        button_settings: Vec::new(),
+        lock: None,
+        system_actions: None,
+        profiles: Vec::new(),
+        one_shot_timeout_millis: 0,
+        stuck_buttons: None,
+        chord_layout: None,
+        encoder: None,
+        joystick: None,
+        ladder: None,
+        keyboard_report_mode: KeyboardReportMode::SixKey.into(),
+        identity: Some(Identity {
+            preset: identity::Preset::Kontroller.into(),
+            ..Identity::default()
+        }),
+    }
+}
+
+/// Returns the [`ReportType`]s the `kontroller` sends with the [`Konfiguration`],
+/// so that only the collections actually used are advertised to the host.
+///
+/// The keyboard is always included when nothing else is used, as a HID device
+/// must declare at least one report.
+#[must_use]
+pub fn report_types(config: &Konfiguration) -> BTreeSet<ReportType> {
+    let mut report_types = BTreeSet::new();
+
+    let actions = config
+        .keymap
+        .iter()
+        .chain(&config.profiles)
+        .flat_map(|keymap| &keymap.entries)
+        .filter_map(|entry| entry.action.as_ref());
+
+    for action in actions {
+        report_types.insert(match action {
+            Action::KeyCode(_) | Action::OneShot(_) | Action::Latch(_) => ReportType::Keyboard,
+            Action::MouseWheel(_) => ReportType::Mouse,
+            Action::GamepadButton(_) => ReportType::Gamepad,
+            Action::Touch(_) | Action::MultiTouch(_) => ReportType::Digitizer,
+            Action::SystemAction(_) => continue,
+        });
+    }
+
+    if config.chord_layout.is_some() {
+        report_types.insert(ReportType::Keyboard);
+    }
+
+    if config.joystick.as_ref().is_some_and(|settings| {
+        matches!(settings.mode(), Mode::Pointer | Mode::Scroll)
+    }) {
+        report_types.insert(ReportType::Mouse);
+    }
+
+    if report_types.is_empty() {
+        report_types.insert(ReportType::Keyboard);
+    }
+
+    report_types
+}
+
+/// Returns `true` if the [`SystemAction`] must be confirmed before being performed,
+/// by triggering it again.
+#[must_use]
+pub fn requires_confirmation(action: SystemAction) -> bool {
+    matches!(action, SystemAction::ClearBonds | SystemAction::FactoryReset)
+}
+
+/// Output produced by the [`Kontroller`] when polling the hardware buttons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    /// A [`hid::Report`] to send to the host.
+    Report(hid::Report),
+    /// A [`Status`] update to show to the user.
+    Status(Status),
+    /// A [`SystemAction`] to be performed by the firmware.
+    Action(SystemAction),
+}
+
 /// Represents the layout of the Controller.
-pub struct Kontroller<'d> {
-    keys: HashMap<Button, HwKey<'d>>,
+pub struct Kontroller<P> {
+    keys: BTreeMap<Button, HwKey<P>>,
+    encoder: Option<encoder::Encoder<P>>,
+    joystick: Option<joystick::Joystick>,
+    /// Pointer or scroll movement accumulated from the joystick, in thousandths of a unit.
+    motion: (i64, i64),
+    /// When the joystick movement has been last reported.
+    last_motion: Option<Instant>,
+    /// Directional pad Buttons pressed by the joystick, and when they were pressed.
+    dpad: BTreeMap<Button, Instant>,
+    /// Touch, or multi-touch gesture, being performed on the host screen, if any.
+    stroke: Option<touch::Stroke>,
+    /// Sources scanned before updating the keys, e.g. key matrices.
+    scanners: Vec<Box<dyn key::Scan>>,
     config: Konfiguration,
+    /// Buttons physically held down.
+    down: BTreeSet<Button>,
+    /// When each of the Buttons physically held down has been pressed.
+    down_since: BTreeMap<Button, Instant>,
+    /// Stuck buttons, ignored until they are released.
+    quarantined: BTreeSet<Button>,
+    /// Time after which a Button held down is quarantined, `None` if never.
+    stuck_timeout: Option<Duration>,
+    /// Whether the Buttons held down at boot have been checked.
+    booted: bool,
+    /// Buttons held down to ignore until they are released.
+    ignored: BTreeSet<Button>,
+    /// Buttons logically pressed, as seen by the host.
+    pressed: BTreeSet<Button>,
+    /// Buttons with a latch action, kept pressed until pressed again.
+    latched: BTreeSet<Button>,
+    /// One-shot key codes waiting for the next key press, and when they were armed.
+    one_shots: Vec<(KeyCode, Instant)>,
+    /// One-shot key codes applied to a pressed button, until it is released.
+    applied: Option<(Button, Vec<KeyCode>)>,
+    one_shot_timeout: Duration,
+    last_sent: HashMap<ReportType, hid::Report>,
+    lock_gesture: Option<gesture::Detector>,
+    /// Buttons that can start a gesture, held back from the host until the gesture
+    /// is decided, and when they were pressed.
+    held_back: BTreeMap<Button, Instant>,
+    auto_lock: Option<Duration>,
+    locked: bool,
+    last_activity: Option<Instant>,
+    bindings: Vec<(gesture::Detector, SystemAction)>,
+    confirmation_timeout: Duration,
+    /// System action waiting to be confirmed, and when it was triggered.
+    pending: Option<(SystemAction, Instant)>,
+    /// Index of the active keymap profile.
+    profile: usize,
+    /// Decoder of the chord layout, if any.
+    chorder: Option<chord::Chorder>,
+    /// Whether the chord text entry mode is enabled.
+    chording: bool,
+    /// Recorder of the raw samples and events, if enabled.
+    recorder: Option<trace::Recorder>,
+    /// Dump of the recorder being logged, a few lines per poll.
+    dump: Option<trace::Dump>,
 }
 
-impl<'d> Kontroller<'d> {
-    /// TODO
+impl<P: key::Pin> Kontroller<P> {
+    /// Creates a new [`Kontroller`] out of the physical [`HwKey`]s
+    /// associated to each [`Button`].
     ///
-    /// # Errors
-    pub fn new(
-        keys: impl IntoIterator<Item = (Button, impl Into<AnyIOPin>)>,
-        config: Konfiguration,
-    ) -> Result<Self, EspError> {
-        Ok(Self {
+    /// The [`HwKey`]s are configured according to the [`ButtonSettings`]
+    /// specified in the [`Konfiguration`].
+    pub fn new(keys: impl IntoIterator<Item = (Button, HwKey<P>)>, config: Konfiguration) -> Self {
+        let keys = keys
+            .into_iter()
+            .map(|(button, mut key)| {
+                if let Some(settings) = config
+                    .button_settings
+                    .iter()
+                    .find(|settings| settings.button() == button)
+                {
+                    key.set_config(key_config(settings));
+                }
+
+                (button, key)
+            })
+            .collect();
+
+        let lock = config.lock.as_ref();
+        let system_actions = config.system_actions.as_ref();
+
+        Self {
+            keys,
+            encoder: None,
+            joystick: None,
+            motion: (0, 0),
+            last_motion: None,
+            dpad: BTreeMap::new(),
+            stroke: None,
+            scanners: Vec::new(),
+            down: BTreeSet::new(),
+            down_since: BTreeMap::new(),
+            quarantined: BTreeSet::new(),
+            stuck_timeout: config.stuck_buttons.as_ref().map(|settings| {
+                match settings.max_hold_seconds {
+                    0 => DEFAULT_STUCK_BUTTON_TIMEOUT,
+                    seconds => Duration::from_secs(seconds.into()),
+                }
+            }),
+            booted: false,
+            ignored: BTreeSet::new(),
+            pressed: BTreeSet::new(),
+            latched: BTreeSet::new(),
+            one_shots: Vec::new(),
+            applied: None,
+            one_shot_timeout: match config.one_shot_timeout_millis {
+                0 => DEFAULT_ONE_SHOT_TIMEOUT,
+                millis => Duration::from_millis(millis.into()),
+            },
+            last_sent: HashMap::new(),
+            lock_gesture: lock
+                .and_then(|lock| lock.toggle.as_ref())
+                .and_then(gesture::Detector::new),
+            held_back: BTreeMap::new(),
+            auto_lock: lock
+                .map(|lock| lock.auto_lock_after_minutes)
+                .filter(|minutes| *minutes > 0)
+                .map(|minutes| Duration::from_secs(u64::from(minutes) * 60)),
+            locked: false,
+            last_activity: None,
+            bindings: system_actions
+                .map(|settings| settings.bindings.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|binding| {
+                    let detector = binding.gesture.as_ref().and_then(gesture::Detector::new)?;
+                    Some((detector, binding.action()))
+                })
+                .collect(),
+            confirmation_timeout: system_actions
+                .map(|settings| settings.confirmation_timeout_millis)
+                .filter(|millis| *millis > 0)
+                .map_or(DEFAULT_CONFIRMATION_TIMEOUT, |millis| {
+                    Duration::from_millis(millis.into())
+                }),
+            pending: None,
+            profile: 0,
+            chorder: config.chord_layout.as_ref().map(chord::Chorder::new),
+            chording: false,
+            recorder: None,
+            dump: None,
             config,
-            keys: keys
-                .into_iter()
-                .map(|(key_type, pin)| Ok((key_type, HwKey::try_from(pin)?)))
-                .collect::<Result<HashMap<Button, HwKey<'d>>, EspError>>()?,
-        })
+        }
+    }
+
+    /// Sets the rotary [`encoder::Encoder`], configured according to the
+    /// encoder settings specified in the [`Konfiguration`].
+    ///
+    /// Encoder steps tap the [`Button::EncoderCw`] and [`Button::EncoderCcw`]
+    /// virtual buttons, bound to actions in the keymap.
+    pub fn set_encoder(&mut self, mut encoder: encoder::Encoder<P>) {
+        if let Some(settings) = &self.config.encoder {
+            encoder.set_config(encoder::Config {
+                pulses_per_detent: match settings.pulses_per_detent {
+                    0 => encoder::DEFAULT_PULSES_PER_DETENT,
+                    pulses => u8::try_from(pulses).unwrap_or(u8::MAX),
+                },
+                reverse: settings.reverse,
+            });
+        }
+
+        self.encoder = Some(encoder);
+    }
+
+    /// Sets the analog [`joystick::Joystick`], configured according to the
+    /// joystick settings specified in the [`Konfiguration`].
+    ///
+    /// Depending on the mode, the joystick moves the mouse pointer, scrolls
+    /// the mouse wheel, or presses the directional pad [`Button`]s.
+    pub fn set_joystick(&mut self, mut joystick: joystick::Joystick) {
+        if let Some(settings) = &self.config.joystick {
+            joystick.set_config(joystick::Config {
+                x: settings.x.as_ref().map_or_else(Default::default, axis_calibration),
+                y: settings.y.as_ref().map_or_else(Default::default, axis_calibration),
+                deadzone: i32::try_from(settings.deadzone_permille).unwrap_or(i32::MAX),
+                expo: i32::try_from(settings.expo_percent).unwrap_or(i32::MAX),
+            });
+        }
+
+        self.joystick = Some(joystick);
+    }
+
+    /// Enables the recording of the raw [`Button`] samples and [`key::Event`]s
+    /// into the [`trace::Recorder`], dumped by the [`SystemAction::DumpTrace`] action.
+    pub fn set_recorder(&mut self, recorder: trace::Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Returns the [`trace::Recorder`], if recording is enabled.
+    #[must_use]
+    pub fn recorder(&self) -> Option<&trace::Recorder> {
+        self.recorder.as_ref()
+    }
+
+    /// Adds a [`key::Scan`] source, such as a [`crate::matrix::Matrix`], scanned
+    /// before updating the [`HwKey`]s on every poll.
+    pub fn add_scanner(&mut self, scanner: impl key::Scan + 'static) {
+        self.scanners.push(Box::new(scanner));
     }
 
+    /// Polls the hardware buttons state every `buttons_poll_interval_micros`,
+    /// as specified in the [`Konfiguration`], and sends the resulting
+    /// [`hid::Report`]s, [`Status`] updates and [`SystemAction`]s through the provided channels.
+    ///
     /// # Errors
     ///
+    /// The method fails when any of the channels has been closed.
     pub async fn start<Clk>(
         &mut self,
         clock: Clk,
-        mut tx: Sender<hid::Report>,
+        mut reports: Sender<hid::Report>,
+        mut statuses: Sender<Status>,
+        mut actions: Sender<SystemAction>,
     ) -> anyhow::Result<()>
     where
         Clk: Fn() -> Instant,
@@ -66,49 +390,1817 @@ impl<'d> Kontroller<'d> {
             ))
             .await;
 
-            let pressed_keys = self.report_pressed_keys(clock());
-            if pressed_keys.is_empty() {
-                continue;
+            for output in self.poll(clock()) {
+                match output {
+                    Output::Report(report) => reports.send(report).await?,
+                    Output::Status(status) => statuses.send(status).await?,
+                    Output::Action(action) => actions.send(action).await?,
+                }
+            }
+        }
+    }
+
+    /// Updates the state of all the hardware buttons at the specified
+    /// point in time, and returns the [`Output`]s produced in the process.
+    ///
+    /// Reports are only returned when their content changes from the last one
+    /// sent with the same [`ReportType`], so that no duplicate reports are sent.
+    pub fn poll(&mut self, now: Instant) -> Vec<Output> {
+        for scanner in &mut self.scanners {
+            scanner.scan(now);
+        }
+
+        self.log_dump();
+
+        let mut outputs = self.quarantine_stuck_at_boot();
+        let mut events = self.report_pressed_keys(now);
+
+        events.retain(|(button, evt)| {
+            if !self.quarantined.contains(button) {
+                return true;
+            }
+
+            if matches!(evt, key::Event::Up { .. }) {
+                log::info!("{button:?} recovered, no longer quarantined");
+                self.quarantined.remove(button);
+                outputs.push(Output::Status(Status::ButtonRecovered(*button)));
+            }
+
+            false
+        });
+
+        for (button, evt) in &events {
+            match evt {
+                key::Event::Down { at } => {
+                    self.down.insert(*button);
+                    self.down_since.insert(*button, *at);
+                }
+                key::Event::Up { .. } => {
+                    self.down.remove(button);
+                    self.down_since.remove(button);
+                }
+                key::Event::Repeat { .. } => {}
             }
+        }
+
+        outputs.extend(self.quarantine_stuck(now).into_iter().map(Output::Status));
+
+        if !events.is_empty() || self.last_activity.is_none() {
+            self.last_activity = Some(now);
+        }
+
+        outputs.extend(self.update_lock(now).map(Output::Status));
+        outputs.extend(self.expire_pending(now).map(Output::Status));
+        self.expire_one_shots(now);
 
-            let mut report = hid::Report::default();
+        for action in self.performed_bindings(now) {
+            outputs.extend(self.trigger(action, now));
+        }
 
-            for (i, evt) in pressed_keys.iter().enumerate() {
-                report.keycodes[i] = match evt {
-                    (_, key::Event::Up) | (Button::Unspecified, _) => KeyCode::Unspecified as u8,
-                    (button, key::Event::Down) => self
-                        .config
-                        .keymap
-                        .as_ref()
-                        .and_then(|keymap| {
-                            keymap
-                                .entries
-                                .iter()
-                                .find(|entry| entry.button() == *button)
-                        })
-                        .map_or(KeyCode::Unspecified, Entry::key_code)
-                        as u8,
+        let mut tapped = BTreeSet::new();
+        for (button, evt) in self.hold_back(events, now) {
+            // Held back Buttons released before their gesture is decided are pressed and
+            // released in the same poll: the press is reported first, for the host to see it.
+            match evt {
+                key::Event::Down { .. } => {
+                    tapped.insert(button);
+                }
+                key::Event::Up { .. } if tapped.contains(&button) => {
+                    outputs.extend(self.key_reports_if_changed());
                 }
+                _ => {}
             }
 
-            tx.send(report).await?;
+            outputs.extend(self.handle(button, evt, now));
         }
+
+        if let Some(step) = self.encoder.as_mut().and_then(|encoder| encoder.update(now)) {
+            self.last_activity = Some(now);
+            outputs.extend(self.step(step));
+        }
+
+        outputs.extend(self.update_joystick(now));
+        outputs.extend(self.update_touch(now));
+
+        outputs.extend(self.key_reports_if_changed());
+        outputs
     }
 
-    /// TODO
+    /// Holds back the [`key::Event`]s of the [`Button`]s that can start a lock or system
+    /// action gesture, and returns the events to handle, in order.
     ///
-    /// # Errors
-    ///
-    /// # Panics
-    pub fn report_pressed_keys(&mut self, now: Instant) -> Vec<(Button, key::Event)> {
-        self.keys
-            .iter_mut()
-            .map(|(kt, key)| (kt, key.update(now)))
-            .filter(|(_, evt)| evt.is_some())
-            .map(|(kt, evt)| {
-                log::info!("{evt:?} {kt:?}");
-                (*kt, evt.unwrap())
+    /// A held back [`Button`] is handled once the gesture is decided: when it is released,
+    /// or its gesture window elapsed, before the gesture is performed. Once performed, the
+    /// [`Button`] is ignored until released instead, so that nothing reaches the host.
+    fn hold_back(
+        &mut self,
+        events: Vec<(Button, key::Event)>,
+        now: Instant,
+    ) -> Vec<(Button, key::Event)> {
+        let mut ready = Vec::new();
+
+        for (button, evt) in events {
+            match evt {
+                key::Event::Down { at } if self.gesture_window(button).is_some() => {
+                    self.held_back.insert(button, at);
+                }
+                key::Event::Up { .. } => {
+                    ready.extend(self.release_held_back(button));
+                    ready.push((button, evt));
+                }
+                // Repeats of a held back Button are dropped, as it is not pressed yet.
+                key::Event::Repeat { .. } if self.held_back.contains_key(&button) => {}
+                evt => ready.push((button, evt)),
+            }
+        }
+
+        let decided: Vec<Button> = self
+            .held_back
+            .iter()
+            .filter(|(button, at)| {
+                self.ignored.contains(button)
+                    || now - **at >= self.gesture_window(**button).unwrap_or_default()
+            })
+            .map(|(button, _)| *button)
+            .collect();
+
+        for button in decided {
+            ready.extend(self.release_held_back(button));
+        }
+
+        ready
+    }
+
+    /// Returns the held back [`key::Event::Down`] of the [`Button`], if any.
+    fn release_held_back(&mut self, button: Button) -> Option<(Button, key::Event)> {
+        let at = self.held_back.remove(&button)?;
+        Some((button, key::Event::Down { at }))
+    }
+
+    /// Returns the time the [`Button`] is held back for, waiting for the longest
+    /// gesture it is part of to be performed, or `None` if it is not part of any.
+    fn gesture_window(&self, button: Button) -> Option<Duration> {
+        self.lock_gesture
+            .iter()
+            .chain(self.bindings.iter().map(|(detector, _)| detector))
+            .filter(|detector| detector.buttons().contains(&button))
+            .map(|detector| match detector.buttons().len() {
+                1 => detector.hold(),
+                _ => detector.hold() + COMBO_WINDOW,
+            })
+            .max()
+    }
+
+    /// Handles the [`key::Event`] of the [`Button`], returning the [`Output`]s produced.
+    fn handle(&mut self, button: Button, evt: key::Event, now: Instant) -> Vec<Output> {
+        if self.locked || self.ignored.contains(&button) {
+            if matches!(evt, key::Event::Up { .. }) {
+                self.ignored.remove(&button);
+            }
+            return Vec::new();
+        }
+
+        if self.chording && !self.toggles_chord_mode(button) {
+            return self.chord(button, evt);
+        }
+
+        let mut outputs = Vec::new();
+
+        match evt {
+            key::Event::Down { .. } => match self.action(button) {
+                Some(Action::SystemAction(action)) => {
+                    let action = SystemAction::try_from(action).unwrap_or_default();
+                    outputs.extend(self.trigger(action, now));
+                }
+                Some(Action::OneShot(key_code)) => {
+                    let key_code = KeyCode::try_from(key_code).unwrap_or_default();
+                    outputs.extend(self.cancel_pending().map(Output::Status));
+                    self.toggle_one_shot(key_code, now);
+                }
+                Some(Action::Latch(_)) => {
+                    outputs.extend(self.cancel_pending().map(Output::Status));
+                    if !self.latched.remove(&button) {
+                        self.latched.insert(button);
+                    }
+                }
+                Some(Action::MouseWheel(amount)) => {
+                    outputs.extend(self.cancel_pending().map(Output::Status));
+                    outputs.extend(self.scroll(amount));
+                }
+                Some(Action::Touch(touch)) => {
+                    outputs.extend(self.cancel_pending().map(Output::Status));
+                    outputs.extend(self.start_stroke(touch::Stroke::new(&touch, now), now));
+                }
+                Some(Action::MultiTouch(multi_touch)) => {
+                    outputs.extend(self.cancel_pending().map(Output::Status));
+                    if let Some(stroke) = touch::Stroke::multi_touch(&multi_touch, now) {
+                        outputs.extend(self.start_stroke(stroke, now));
+                    }
+                }
+                _ => {
+                    outputs.extend(self.cancel_pending().map(Output::Status));
+                    self.pressed.insert(button);
+                    self.apply_one_shots(button);
+                }
+            },
+            key::Event::Up { .. } => {
+                self.pressed.remove(&button);
+                self.release_one_shots(button);
+            }
+            key::Event::Repeat { .. } => {
+                if let Some(Action::MouseWheel(amount)) = self.action(button) {
+                    outputs.extend(self.scroll(amount));
+                }
+
+                // Hosts only register a new key press after a release,
+                // so the key is released before being pressed again.
+                if self.pressed.remove(&button) {
+                    outputs.extend(self.key_reports_if_changed());
+                    self.pressed.insert(button);
+                }
+            }
+        }
+
+        outputs
+    }
+
+    /// Taps the virtual [`Button`] bound to the [`encoder::Step`] direction,
+    /// multiple times when rotating above the acceleration threshold.
+    fn step(&mut self, step: encoder::Step) -> Vec<Output> {
+        let button = match step.direction {
+            encoder::Direction::Clockwise => Button::EncoderCw,
+            encoder::Direction::CounterClockwise => Button::EncoderCcw,
+        };
+
+        let taps = self
+            .config
+            .encoder
+            .as_ref()
+            .filter(|settings| {
+                settings.acceleration_threshold_hz > 0
+                    && step.velocity >= settings.acceleration_threshold_hz
             })
+            .map_or(1, |settings| settings.acceleration_multiplier.max(1));
+
+        // Steps are instantaneous taps of the virtual Button.
+        let release = key::Event::Up {
+            at: step.at,
+            pressed_for: Duration::from_ticks(0),
+        };
+
+        let mut outputs = Vec::new();
+        for _ in 0..taps {
+            outputs.extend(self.handle(button, key::Event::Down { at: step.at }, step.at));
+            outputs.extend(self.key_reports_if_changed());
+            outputs.extend(self.handle(button, release, step.at));
+            outputs.extend(self.key_reports_if_changed());
+        }
+
+        outputs
+    }
+
+    /// Samples the [`joystick::Joystick`], and drives the pointer, scroll or
+    /// directional pad [`Button`]s according to its mode.
+    fn update_joystick(&mut self, now: Instant) -> Vec<Output> {
+        let Some(joystick) = self.joystick.as_mut() else {
+            return Vec::new();
+        };
+
+        let (x, y) = joystick.read();
+        if (x, y) != (0, 0) {
+            self.last_activity = Some(now);
+        }
+
+        let Some(settings) = &self.config.joystick else {
+            return Vec::new();
+        };
+
+        match settings.mode() {
+            Mode::Unspecified => Vec::new(),
+            mode @ (Mode::Pointer | Mode::Scroll) => {
+                let speed = match (settings.max_speed, mode) {
+                    (0, Mode::Scroll) => DEFAULT_SCROLL_SPEED,
+                    (0, _) => DEFAULT_POINTER_SPEED,
+                    (speed, _) => speed.min(MAX_JOYSTICK_SPEED),
+                };
+                self.move_pointer(mode, (x, y), speed, now)
+            }
+            Mode::Dpad => {
+                let threshold = match settings.dpad_threshold_permille {
+                    0 => DEFAULT_DPAD_THRESHOLD,
+                    threshold => i32::try_from(threshold).unwrap_or(i32::MAX),
+                };
+                self.press_dpad((x, y), threshold, now)
+            }
+        }
+    }
+
+    /// Accumulates the joystick deflection into relative pointer or scroll movements,
+    /// reported at most every [`JOYSTICK_REPORT_INTERVAL`].
+    fn move_pointer(
+        &mut self,
+        mode: Mode,
+        (x, y): (i32, i32),
+        speed: u32,
+        now: Instant,
+    ) -> Vec<Output> {
+        if self
+            .last_motion
+            .is_some_and(|last| now - last < JOYSTICK_REPORT_INTERVAL)
+        {
+            return Vec::new();
+        }
+
+        // Bound the elapsed time, so that a late poll does not cause a jump.
+        let elapsed = self
+            .last_motion
+            .map_or(JOYSTICK_REPORT_INTERVAL, |last| now - last)
+            .min(JOYSTICK_REPORT_INTERVAL * 10);
+        self.last_motion = Some(now);
+
+        if self.locked {
+            self.motion = (0, 0);
+            return Vec::new();
+        }
+
+        let scale = i64::from(speed) * i64::try_from(elapsed.as_millis()).unwrap_or_default();
+        self.motion.0 += i64::from(x) * scale / 1000;
+        self.motion.1 += i64::from(y) * scale / 1000;
+
+        let take = |motion: &mut i64| {
+            let units = (*motion / i64::from(joystick::FULL_DEFLECTION))
+                .clamp(i8::MIN.into(), i8::MAX.into());
+            *motion -= units * i64::from(joystick::FULL_DEFLECTION);
+            i8::try_from(units).unwrap_or_default()
+        };
+
+        let (dx, dy) = (take(&mut self.motion.0), take(&mut self.motion.1));
+        let report = match mode {
+            Mode::Scroll => hid::MouseReport {
+                wheel: dy.saturating_neg(),
+                pan: dx,
+                ..hid::MouseReport::default()
+            },
+            _ => hid::MouseReport {
+                x: dx,
+                y: dy,
+                ..hid::MouseReport::default()
+            },
+        };
+
+        self.report_if_changed(report.into())
+            .into_iter()
+            .collect()
+    }
+
+    /// Presses the directional pad [`Button`]s whose direction is deflected past
+    /// the threshold, and releases them below three quarters of it, so that
+    /// they do not chatter around the threshold.
+    fn press_dpad(&mut self, (x, y): (i32, i32), threshold: i32, now: Instant) -> Vec<Output> {
+        let release = threshold * 3 / 4;
+        let mut outputs = Vec::new();
+
+        for (button, deflection) in [
+            (Button::Right, x),
+            (Button::Left, -x),
+            (Button::Down, y),
+            (Button::Up, -y),
+        ] {
+            match self.dpad.get(&button).copied() {
+                None if deflection >= threshold => {
+                    self.dpad.insert(button, now);
+                    outputs.extend(self.handle(button, key::Event::Down { at: now }, now));
+                }
+                Some(since) if deflection < release => {
+                    self.dpad.remove(&button);
+                    let evt = key::Event::Up {
+                        at: now,
+                        pressed_for: now - since,
+                    };
+                    outputs.extend(self.handle(button, evt, now));
+                }
+                _ => {}
+            }
+        }
+
+        outputs
+    }
+
+    /// Scrolls the mouse wheel by the specified amount, immediately followed
+    /// by a report with no movement, since mouse movements are relative.
+    fn scroll(&mut self, amount: i32) -> Vec<Output> {
+        let report = hid::MouseReport {
+            wheel: i8::try_from(amount.clamp(i8::MIN.into(), i8::MAX.into())).unwrap_or_default(),
+            ..hid::MouseReport::default()
+        };
+
+        self.report_if_changed(report.into())
+            .into_iter()
+            .chain(self.report_if_changed(hid::MouseReport::default().into()))
+            .collect()
+    }
+
+    /// Starts performing the [`touch::Stroke`] on the host screen, lifting the
+    /// fingers first if a previous one is still in progress, so that the host
+    /// sees two distinct touches.
+    fn start_stroke(&mut self, stroke: touch::Stroke, now: Instant) -> Vec<Output> {
+        let lift = self.stroke.take().map(|stroke| stroke.lift(now));
+
+        self.stroke = Some(stroke);
+
+        lift.and_then(|report| self.report_if_changed(report.into()))
+            .into_iter()
+            .chain(self.update_touch(now))
+            .collect()
+    }
+
+    /// Reports the position of the fingers performing the [`touch::Stroke`],
+    /// until they leave the host screen.
+    fn update_touch(&mut self, now: Instant) -> Option<Output> {
+        let report = self.stroke?.report(now);
+        if !report.is_touching() {
+            self.stroke = None;
+        }
+
+        self.report_if_changed(report.into())
+    }
+
+    /// Quarantines the [`Button`]s already held down when first polled,
+    /// as they are likely stuck rather than pressed by the user.
+    fn quarantine_stuck_at_boot(&mut self) -> Vec<Output> {
+        if std::mem::replace(&mut self.booted, true) {
+            return Vec::new();
+        }
+
+        let stuck: Vec<Button> = self
+            .keys
+            .iter()
+            .filter(|(_, key)| key.is_low())
+            .map(|(button, _)| *button)
+            .collect();
+
+        stuck
+            .into_iter()
+            .map(|button| Output::Status(self.quarantine(button)))
             .collect()
     }
+
+    /// Quarantines the [`Button`]s held down for longer than the stuck button timeout,
+    /// if configured.
+    fn quarantine_stuck(&mut self, now: Instant) -> Vec<Status> {
+        let Some(timeout) = self.stuck_timeout else {
+            return Vec::new();
+        };
+
+        let stuck: Vec<Button> = self
+            .down_since
+            .iter()
+            .filter(|(_, since)| now - **since >= timeout)
+            .map(|(button, _)| *button)
+            .collect();
+
+        stuck
+            .into_iter()
+            .map(|button| self.quarantine(button))
+            .collect()
+    }
+
+    /// Releases the stuck [`Button`] and ignores it until it is released.
+    fn quarantine(&mut self, button: Button) -> Status {
+        log::warn!("{button:?} is stuck, quarantined until released");
+
+        self.quarantined.insert(button);
+        self.held_back.remove(&button);
+        self.down.remove(&button);
+        self.down_since.remove(&button);
+        self.ignored.remove(&button);
+        self.pressed.remove(&button);
+        self.latched.remove(&button);
+        self.release_one_shots(button);
+
+        Status::StuckButton(button)
+    }
+
+    /// Returns `true` if the input lock mode is enabled.
+    #[must_use]
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Toggles the input lock mode when the lock gesture has been performed,
+    /// or enables it after the configured inactivity time.
+    fn update_lock(&mut self, now: Instant) -> Option<Status> {
+        let toggled = self
+            .lock_gesture
+            .as_mut()
+            .is_some_and(|gesture| gesture.update(&self.down, now));
+
+        let inactive = !self.locked
+            && self
+                .auto_lock
+                .zip(self.last_activity)
+                .is_some_and(|(auto_lock, last_activity)| now - last_activity >= auto_lock);
+
+        if !toggled && !inactive {
+            return None;
+        }
+
+        self.locked = !self.locked;
+        self.last_activity = Some(now);
+
+        // Buttons held down while toggling the lock, e.g. the lock gesture itself,
+        // must not be sent to the host: they are ignored until released.
+        self.ignored.extend(self.down.iter().copied());
+        self.release_all();
+
+        Some(if self.locked {
+            Status::Locked
+        } else {
+            Status::Unlocked
+        })
+    }
+
+    /// Updates the state of all the hardware buttons at the specified point in time,
+    /// and returns the [`key::Event`]s detected for each [`Button`], with their timing.
+    pub fn report_pressed_keys(&mut self, now: Instant) -> Vec<(Button, key::Event)> {
+        if let Some(recorder) = &mut self.recorder {
+            for (kt, key) in &self.keys {
+                recorder.sample(*kt, key.is_low(), now);
+            }
+        }
+
+        let events: Vec<_> = self
+            .keys
+            .iter_mut()
+            .filter_map(|(kt, key)| key.update(now).map(|evt| (*kt, evt)))
+            .inspect(|(kt, evt)| log::info!("{evt:?} {kt:?}"))
+            .collect();
+
+        if let Some(recorder) = &mut self.recorder {
+            for (kt, evt) in &events {
+                recorder.event(*kt, *evt);
+            }
+        }
+
+        events
+    }
+
+    /// Returns the index of the active keymap profile.
+    #[must_use]
+    pub fn profile(&self) -> usize {
+        self.profile
+    }
+
+    /// Returns the [`SystemAction`]s bound to the gestures performed at this point in time.
+    ///
+    /// The [`Button`]s of a performed gesture are released, and ignored until they are released.
+    fn performed_bindings(&mut self, now: Instant) -> Vec<SystemAction> {
+        let mut performed = Vec::new();
+
+        for (detector, action) in &mut self.bindings {
+            // Detectors are always updated, so that gestures started while locked
+            // are not performed once unlocked.
+            if detector.update(&self.down, now) && !self.locked {
+                self.ignored.extend(detector.buttons());
+                self.pressed.retain(|button| !detector.buttons().contains(button));
+                if let Some(chorder) = &mut self.chorder {
+                    chorder.reset();
+                }
+                performed.push(*action);
+            }
+        }
+
+        performed
+    }
+
+    /// Triggers the specified [`SystemAction`].
+    ///
+    /// Actions that require confirmation are only performed when triggered
+    /// twice within the confirmation timeout. Triggering any other action
+    /// cancels the one waiting for confirmation.
+    fn trigger(&mut self, action: SystemAction, now: Instant) -> Vec<Output> {
+        if action == SystemAction::Unspecified {
+            return Vec::new();
+        }
+
+        let mut outputs = Vec::new();
+
+        match self.pending.take() {
+            Some((pending, since))
+                if pending == action && now - since <= self.confirmation_timeout => {}
+            pending => {
+                self.pending = pending;
+                outputs.extend(self.cancel_pending().map(Output::Status));
+
+                if requires_confirmation(action) {
+                    self.pending = Some((action, now));
+                    outputs.push(Output::Status(Status::ConfirmationRequired(action)));
+                    return outputs;
+                }
+            }
+        }
+
+        match action {
+            SystemAction::CycleProfile => {
+                self.profile = (self.profile + 1) % (self.config.profiles.len() + 1);
+
+                // Keys pressed with the previous profile would be remapped:
+                // they are ignored until released instead.
+                self.ignored.extend(self.pressed.iter().copied());
+                self.release_all();
+
+                outputs.push(Output::Status(Status::Profile(self.profile)));
+            }
+            SystemAction::ToggleChordMode => outputs.extend(self.toggle_chord_mode()),
+            SystemAction::DumpTrace => self.dump_trace(),
+            action => outputs.push(Output::Action(action)),
+        }
+
+        outputs
+    }
+
+    /// Starts logging the dump of the [`trace::Recorder`], so that it can be
+    /// collected from the serial console.
+    fn dump_trace(&mut self) {
+        let Some(recorder) = &self.recorder else {
+            log::warn!("trace recording is not enabled");
+            return;
+        };
+
+        self.dump = Some(trace::Dump::from(recorder));
+    }
+
+    /// Logs the next [`trace::DUMP_LINES_PER_POLL`] lines of the dump in progress, if any.
+    fn log_dump(&mut self) {
+        let Some(dump) = &mut self.dump else {
+            return;
+        };
+
+        for _ in 0..trace::DUMP_LINES_PER_POLL {
+            let Some(line) = dump.next_line() else {
+                break;
+            };
+            log::info!("{line}");
+        }
+
+        if dump.is_done() {
+            self.dump = None;
+        }
+    }
+
+    /// Enters or leaves the chord text entry mode, if a chord layout is configured.
+    fn toggle_chord_mode(&mut self) -> Option<Output> {
+        if self.chorder.is_none() {
+            log::warn!("no chord layout configured, chord mode not available");
+            return None;
+        }
+
+        // Buttons held down while toggling the mode would be handled differently
+        // once released: they are ignored until released instead.
+        self.ignored.extend(self.down.iter().copied());
+        self.release_all();
+        self.chording = !self.chording;
+
+        Some(Output::Status(if self.chording {
+            Status::ChordModeEntered
+        } else {
+            Status::ChordModeExited
+        }))
+    }
+
+    /// Returns `true` if the [`Button`] is mapped to the [`SystemAction::ToggleChordMode`]
+    /// action in the active keymap, so that the chord mode can be left.
+    fn toggles_chord_mode(&self, button: Button) -> bool {
+        self.action(button)
+            == Some(Action::SystemAction(SystemAction::ToggleChordMode.into()))
+    }
+
+    /// Feeds the [`key::Event`] to the chord decoder, typing the chord
+    /// character once all of its [`Button`]s are released.
+    fn chord(&mut self, button: Button, evt: key::Event) -> Vec<Output> {
+        let Some(chorder) = &mut self.chorder else {
+            return Vec::new();
+        };
+
+        let outcome = match evt {
+            key::Event::Down { .. } => {
+                chorder.press(button);
+                return Vec::new();
+            }
+            key::Event::Up { .. } => chorder.release(button),
+            key::Event::Repeat { .. } => chord::Outcome::Pending,
+        };
+
+        match outcome {
+            chord::Outcome::Pending => Vec::new(),
+            chord::Outcome::Unknown => vec![Output::Status(Status::UnknownChord)],
+            chord::Outcome::Typed(character) => {
+                let report = self.keyboard_report(character.modifier, &[character.key_code]);
+                let release = self.keyboard_report(0, &[]);
+
+                let mut outputs: Vec<Output> = self
+                    .report_if_changed(report)
+                    .into_iter()
+                    .chain(self.report_if_changed(release))
+                    .collect();
+
+                outputs.push(Output::Status(Status::Character(character.key_code)));
+                outputs
+            }
+        }
+    }
+
+    /// Arms the one-shot [`KeyCode`] for the next key press, or cancels it if already armed.
+    fn toggle_one_shot(&mut self, key_code: KeyCode, now: Instant) {
+        if key_code == KeyCode::Unspecified {
+            return;
+        }
+
+        let armed = self.one_shots.len();
+        self.one_shots.retain(|(armed, _)| *armed != key_code);
+        if self.one_shots.len() == armed {
+            self.one_shots.push((key_code, now));
+        }
+    }
+
+    /// Applies the armed one-shot [`KeyCode`]s to the pressed [`Button`], until it is released.
+    fn apply_one_shots(&mut self, button: Button) {
+        if self.one_shots.is_empty() {
+            return;
+        }
+
+        let key_codes = self.one_shots.drain(..).map(|(key_code, _)| key_code);
+        match &mut self.applied {
+            // Keys pressed while the first one is still held share its one-shots.
+            Some((_, applied)) => applied.extend(key_codes),
+            applied => *applied = Some((button, key_codes.collect())),
+        }
+    }
+
+    /// Releases the one-shot [`KeyCode`]s applied to the [`Button`], if any.
+    fn release_one_shots(&mut self, button: Button) {
+        if self
+            .applied
+            .as_ref()
+            .is_some_and(|(applied, _)| *applied == button)
+        {
+            self.applied = None;
+        }
+    }
+
+    fn expire_one_shots(&mut self, now: Instant) {
+        let timeout = self.one_shot_timeout;
+        self.one_shots.retain(|(_, since)| now - *since < timeout);
+    }
+
+    /// Releases all the logically pressed keys, including latched and one-shot ones.
+    fn release_all(&mut self) {
+        self.pressed.clear();
+        self.latched.clear();
+        self.one_shots.clear();
+        self.applied = None;
+        if let Some(chorder) = &mut self.chorder {
+            chorder.reset();
+        }
+    }
+
+    fn expire_pending(&mut self, now: Instant) -> Option<Status> {
+        match self.pending {
+            Some((_, since)) if now - since > self.confirmation_timeout => self.cancel_pending(),
+            _ => None,
+        }
+    }
+
+    fn cancel_pending(&mut self) -> Option<Status> {
+        self.pending
+            .take()
+            .map(|(action, _)| Status::ConfirmationCancelled(action))
+    }
+
+    fn keymap(&self) -> Option<&Keymap> {
+        match self.profile {
+            0 => self.config.keymap.as_ref(),
+            profile => self.config.profiles.get(profile - 1),
+        }
+    }
+
+    fn action(&self, button: Button) -> Option<Action> {
+        self.keymap()?
+            .entries
+            .iter()
+            .find(|entry| entry.button() == button)?
+            .action
+            .clone()
+    }
+
+    fn key_code(&self, button: Button) -> KeyCode {
+        match self.action(button) {
+            Some(Action::KeyCode(key_code) | Action::Latch(key_code)) => {
+                KeyCode::try_from(key_code).unwrap_or_default()
+            }
+            _ => KeyCode::Unspecified,
+        }
+    }
+
+    /// Returns the keyboard and gamepad [`Output`]s for the pressed [`Button`]s,
+    /// for the reports that changed since the last ones sent.
+    fn key_reports_if_changed(&mut self) -> Vec<Output> {
+        self.keyboard_report_if_changed()
+            .into_iter()
+            .chain(self.gamepad_report_if_changed())
+            .collect()
+    }
+
+    fn gamepad_report_if_changed(&mut self) -> Option<Output> {
+        let pressed: Vec<GamepadButton> = self
+            .pressed
+            .iter()
+            .filter_map(|button| match self.action(*button) {
+                Some(Action::GamepadButton(gamepad_button)) => {
+                    GamepadButton::try_from(gamepad_button).ok()
+                }
+                _ => None,
+            })
+            .collect();
+
+        self.report_if_changed(hid::GamepadReport::from_pressed(pressed).into())
+    }
+
+    fn keyboard_report_if_changed(&mut self) -> Option<Output> {
+        let mut modifier = 0;
+        let mut key_codes = Vec::new();
+
+        let applied = self
+            .applied
+            .iter()
+            .flat_map(|(_, key_codes)| key_codes.iter().copied());
+
+        for key_code in self
+            .pressed
+            .iter()
+            .chain(&self.latched)
+            .map(|button| self.key_code(*button))
+            .chain(applied)
+            .filter(|key_code| *key_code != KeyCode::Unspecified)
+        {
+            if let Some(bit) = hid::modifier_bit(key_code) {
+                modifier |= bit;
+            } else if !key_codes.contains(&key_code) {
+                key_codes.push(key_code);
+            }
+        }
+
+        let report = self.keyboard_report(modifier, &key_codes);
+        self.report_if_changed(report)
+    }
+
+    /// Builds the keyboard [`hid::Report`] with the layout selected by the [`KeyboardReportMode`].
+    ///
+    /// Key codes not covered by the [`hid::NkroKeyboardReport`] bitmap are not reported.
+    fn keyboard_report(&self, modifier: u8, key_codes: &[KeyCode]) -> hid::Report {
+        if self.config.keyboard_report_mode() == KeyboardReportMode::NKey {
+            let mut report = hid::NkroKeyboardReport {
+                modifier,
+                ..hid::NkroKeyboardReport::default()
+            };
+            for key_code in key_codes {
+                report.press(*key_code as u8);
+            }
+
+            return report.into();
+        }
+
+        let mut report = hid::KeyboardReport {
+            modifier,
+            ..hid::KeyboardReport::default()
+        };
+
+        if key_codes.len() > MAX_KEYCODES {
+            // Too many keys pressed at once: signal a phantom state to the host.
+            report.keycodes = [KeyCode::ErrorRollover as u8; MAX_KEYCODES];
+        } else {
+            for (slot, key_code) in report.keycodes.iter_mut().zip(key_codes) {
+                *slot = *key_code as u8;
+            }
+        }
+
+        report.into()
+    }
+
+    /// Returns the [`Output`] for the [`hid::Report`], unless it is the same as the last one sent.
+    ///
+    /// Mouse reports with movement are always sent, since mouse movements are relative,
+    /// while empty reports are only sent to release what a previous report pressed.
+    fn report_if_changed(&mut self, report: hid::Report) -> Option<Output> {
+        let report_type = report.report_type();
+        let moving = matches!(report, hid::Report::Mouse(mouse) if mouse.is_moving());
+        let unchanged = self
+            .last_sent
+            .get(&report_type)
+            .map_or(report.is_empty(), |last| *last == report);
+        if unchanged && !moving {
+            return None;
+        }
+
+        self.last_sent.insert(report_type, report);
+        Some(Output::Report(report))
+    }
+}
+
+fn axis_calibration(calibration: &AxisCalibration) -> joystick::Calibration {
+    joystick::Calibration {
+        min: u16::try_from(calibration.min).unwrap_or(u16::MAX),
+        center: u16::try_from(calibration.center).unwrap_or(u16::MAX),
+        max: u16::try_from(calibration.max).unwrap_or(u16::MAX),
+        invert: calibration.invert,
+    }
+}
+
+fn key_config(settings: &ButtonSettings) -> key::Config {
+    let default = key::Config::default();
+
+    let filter = settings
+        .vibration_filter
+        .as_ref()
+        .map(|filter| key::VibrationFilter {
+            min_press: Duration::from_millis(filter.min_press_millis.into()),
+            min_pulse: Duration::from_millis(filter.min_pulse_millis.into()),
+            min_toggle_interval: match filter.max_toggle_rate_hz {
+                0 => Duration::MIN,
+                rate => Duration::from_micros(1_000_000 / u64::from(rate)),
+            },
+        });
+
+    let Some(repeat) = &settings.repeat else {
+        return key::Config { filter, ..default };
+    };
+
+    key::Config {
+        hold: match repeat.delay_millis {
+            0 => default.hold,
+            delay => Duration::from_millis(delay.into()),
+        },
+        repeat: match &repeat.policy {
+            None => key::Repeat::None,
+            Some(Policy::Fixed(fixed)) => key::Repeat::Fixed(match fixed.interval_millis {
+                0 => key::DEFAULT_HOLD_REPEAT_TIMEOUT,
+                interval => Duration::from_millis(interval.into()),
+            }),
+            Some(Policy::Accelerating(accelerating)) => key::Repeat::Accelerating {
+                min_rate: accelerating.min_rate_hz,
+                max_rate: accelerating.max_rate_hz,
+                ramp: Duration::from_millis(accelerating.ramp_millis.into()),
+            },
+        },
+        filter,
+        ..default
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analog::tests::FakeChannel,
+        key::tests::FakePin,
+        proto::kontroller::v1::{
+            multi_touch, repeat_policy::Fixed, Chord, ChordLayout, Gesture, JoystickSettings,
+            LockSettings, MultiTouch, RepeatPolicy, StuckButtonSettings, SystemActionBinding,
+            SystemActionSettings,
+        },
+    };
+
+    /// A [`Kontroller`] wired to [`FakePin`]s, polled every millisecond by a fixed clock.
+    struct Harness {
+        kontroller: Kontroller<FakePin>,
+        pins: BTreeMap<Button, FakePin>,
+        now: Instant,
+    }
+
+    impl Harness {
+        fn new(config: Konfiguration) -> Self {
+            let pins: BTreeMap<Button, FakePin> = [
+                Button::Enter,
+                Button::Up,
+                Button::Right,
+                Button::Left,
+                Button::Down,
+                Button::Fn1,
+                Button::Fn2,
+                Button::Fn3,
+            ]
+            .into_iter()
+            .map(|button| (button, FakePin::default()))
+            .collect();
+
+            let keys = pins
+                .iter()
+                .map(|(button, pin)| (*button, HwKey::new(pin.clone(), key::Config::default())));
+
+            let mut harness = Self {
+                kontroller: Kontroller::new(keys, config),
+                pins,
+                now: Instant::from_ticks(0),
+            };
+
+            // Buttons held down at boot are quarantined: boot with all of them released.
+            assert_eq!(harness.run(1), []);
+            harness
+        }
+
+        fn press(&mut self, button: Button) {
+            self.pins[&button].set_low(true);
+        }
+
+        fn release(&mut self, button: Button) {
+            self.pins[&button].set_low(false);
+        }
+
+        /// Polls the [`Kontroller`] for the specified time, returning the [`Output`]s.
+        fn run(&mut self, millis: u64) -> Vec<Output> {
+            let end = self.now + Duration::from_millis(millis);
+            let mut outputs = Vec::new();
+
+            while self.now < end {
+                outputs.extend(self.kontroller.poll(self.now));
+                self.now += Duration::from_millis(1);
+            }
+
+            outputs
+        }
+
+        /// Presses the [`Button`] for 10ms, then releases it for 10ms.
+        fn tap(&mut self, button: Button) -> Vec<Output> {
+            self.press(button);
+            let mut outputs = self.run(10);
+            self.release(button);
+            outputs.extend(self.run(10));
+            outputs
+        }
+    }
+
+    fn keyboard(key_codes: &[KeyCode]) -> Output {
+        shifted(false, key_codes)
+    }
+
+    fn shifted(shift: bool, key_codes: &[KeyCode]) -> Output {
+        let mut report = hid::KeyboardReport {
+            modifier: if shift {
+                hid::modifier_bit(KeyCode::Lshift).unwrap()
+            } else {
+                0
+            },
+            ..hid::KeyboardReport::default()
+        };
+        for (slot, key_code) in report.keycodes.iter_mut().zip(key_codes) {
+            *slot = *key_code as u8;
+        }
+
+        Output::Report(report.into())
+    }
+
+    fn reports(outputs: &[Output]) -> Vec<Output> {
+        outputs
+            .iter()
+            .filter(|output| matches!(output, Output::Report(_)))
+            .copied()
+            .collect()
+    }
+
+    fn gesture(buttons: &[Button], hold_millis: u32) -> Gesture {
+        Gesture {
+            buttons: buttons.iter().map(|button| (*button).into()).collect(),
+            hold_millis,
+        }
+    }
+
+    /// Locks with a long press of Enter, and enters pairing by holding Fn1 and Fn2.
+    fn with_gestures() -> Konfiguration {
+        Konfiguration {
+            lock: Some(LockSettings {
+                toggle: Some(gesture(&[Button::Enter], 1000)),
+                auto_lock_after_minutes: 0,
+            }),
+            system_actions: Some(SystemActionSettings {
+                bindings: vec![SystemActionBinding {
+                    gesture: Some(gesture(&[Button::Fn1, Button::Fn2], 2000)),
+                    action: SystemAction::EnterPairing.into(),
+                }],
+                confirmation_timeout_millis: 0,
+            }),
+            ..default_konfiguration()
+        }
+    }
+
+    #[test]
+    fn lock_gesture_does_not_leak_its_button() {
+        let mut harness = Harness::new(with_gestures());
+
+        harness.press(Button::Enter);
+        let outputs = harness.run(1500);
+        assert_eq!(reports(&outputs), []);
+        assert!(outputs.contains(&Output::Status(Status::Locked)));
+
+        harness.release(Button::Enter);
+        assert_eq!(reports(&harness.run(100)), []);
+        assert!(harness.kontroller.is_locked());
+    }
+
+    #[test]
+    fn gesture_button_released_early_is_sent_on_release() {
+        let mut harness = Harness::new(with_gestures());
+
+        harness.press(Button::Enter);
+        assert_eq!(reports(&harness.run(200)), []);
+
+        harness.release(Button::Enter);
+        assert_eq!(
+            reports(&harness.run(10)),
+            [keyboard(&[KeyCode::Enter]), keyboard(&[])]
+        );
+        assert!(!harness.kontroller.is_locked());
+    }
+
+    #[test]
+    fn gesture_button_is_sent_once_the_gesture_window_elapsed() {
+        let mut harness = Harness::new(with_gestures());
+
+        harness.press(Button::Fn1);
+        assert_eq!(reports(&harness.run(2000)), []);
+        assert_eq!(reports(&harness.run(200)), [keyboard(&[KeyCode::F7])]);
+
+        harness.release(Button::Fn1);
+        assert_eq!(reports(&harness.run(10)), [keyboard(&[])]);
+    }
+
+    #[test]
+    fn other_buttons_are_not_held_back() {
+        let mut harness = Harness::new(with_gestures());
+
+        harness.press(Button::Enter);
+        harness.press(Button::Up);
+        assert_eq!(reports(&harness.run(10)), [keyboard(&[KeyCode::Up])]);
+    }
+
+    #[test]
+    fn system_action_combo_does_not_leak_its_buttons() {
+        let mut harness = Harness::new(with_gestures());
+
+        harness.press(Button::Fn1);
+        harness.run(50);
+        harness.press(Button::Fn2);
+        let outputs = harness.run(2500);
+        assert_eq!(reports(&outputs), []);
+        assert!(outputs.contains(&Output::Action(SystemAction::EnterPairing)));
+
+        harness.release(Button::Fn1);
+        harness.release(Button::Fn2);
+        assert_eq!(reports(&harness.run(100)), []);
+    }
+
+    /// Shifts the next key press with Fn1, and latches F6 with Fn2.
+    fn with_one_shot_and_latch() -> Konfiguration {
+        let mut config = Konfiguration {
+            one_shot_timeout_millis: 500,
+            ..default_konfiguration()
+        };
+        let entries = &mut config.keymap.as_mut().unwrap().entries;
+        entries.retain(|entry| ![Button::Fn1, Button::Fn2].contains(&entry.button()));
+        entries.extend([
+            Entry {
+                button: Button::Fn1.into(),
+                action: Some(Action::OneShot(KeyCode::Lshift.into())),
+            },
+            Entry {
+                button: Button::Fn2.into(),
+                action: Some(Action::Latch(KeyCode::F6.into())),
+            },
+        ]);
+
+        config
+    }
+
+    #[test]
+    fn one_shot_applies_to_the_next_key_press_only() {
+        let mut harness = Harness::new(with_one_shot_and_latch());
+
+        assert_eq!(reports(&harness.tap(Button::Fn1)), []);
+        assert_eq!(
+            reports(&harness.tap(Button::Up)),
+            [shifted(true, &[KeyCode::Up]), keyboard(&[])]
+        );
+        assert_eq!(
+            reports(&harness.tap(Button::Up)),
+            [keyboard(&[KeyCode::Up]), keyboard(&[])]
+        );
+    }
+
+    #[test]
+    fn one_shot_is_shared_by_the_keys_pressed_together() {
+        let mut harness = Harness::new(with_one_shot_and_latch());
+
+        harness.tap(Button::Fn1);
+        harness.press(Button::Up);
+        harness.run(10);
+        harness.press(Button::Down);
+        assert_eq!(
+            reports(&harness.run(10)),
+            [shifted(true, &[KeyCode::Up, KeyCode::Down])]
+        );
+
+        harness.release(Button::Up);
+        harness.release(Button::Down);
+        assert_eq!(reports(&harness.run(10)), [keyboard(&[])]);
+    }
+
+    #[test]
+    fn one_shot_expires_after_the_timeout() {
+        let mut harness = Harness::new(with_one_shot_and_latch());
+
+        harness.tap(Button::Fn1);
+        harness.run(500);
+        assert_eq!(
+            reports(&harness.tap(Button::Up)),
+            [keyboard(&[KeyCode::Up]), keyboard(&[])]
+        );
+    }
+
+    #[test]
+    fn one_shot_is_cancelled_by_a_second_tap() {
+        let mut harness = Harness::new(with_one_shot_and_latch());
+
+        harness.tap(Button::Fn1);
+        harness.tap(Button::Fn1);
+        assert_eq!(
+            reports(&harness.tap(Button::Up)),
+            [keyboard(&[KeyCode::Up]), keyboard(&[])]
+        );
+    }
+
+    #[test]
+    fn latch_keeps_the_key_pressed_until_tapped_again() {
+        let mut harness = Harness::new(with_one_shot_and_latch());
+
+        assert_eq!(reports(&harness.tap(Button::Fn2)), [keyboard(&[KeyCode::F6])]);
+        assert_eq!(
+            reports(&harness.tap(Button::Up)),
+            [
+                keyboard(&[KeyCode::Up, KeyCode::F6]),
+                keyboard(&[KeyCode::F6])
+            ]
+        );
+        assert_eq!(reports(&harness.tap(Button::Fn2)), [keyboard(&[])]);
+    }
+
+    /// Returns a [`Harness`] with a centered joystick in the mode, and its axes.
+    fn with_joystick(mode: Mode) -> (Harness, FakeChannel, FakeChannel) {
+        let mut harness = Harness::new(Konfiguration {
+            joystick: Some(JoystickSettings {
+                mode: mode.into(),
+                ..JoystickSettings::default()
+            }),
+            ..default_konfiguration()
+        });
+
+        let center = joystick::DEFAULT_CALIBRATION.center;
+        let (x, y) = (FakeChannel::new(center), FakeChannel::new(center));
+        harness.kontroller.set_joystick(joystick::Joystick::new(
+            x.clone(),
+            y.clone(),
+            joystick::Config::default(),
+        ));
+
+        (harness, x, y)
+    }
+
+    fn mouse(outputs: &[Output]) -> Vec<hid::MouseReport> {
+        outputs
+            .iter()
+            .filter_map(|output| match output {
+                Output::Report(hid::Report::Mouse(mouse)) => Some(*mouse),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn joystick_pointer_mode_moves_the_pointer() {
+        let (mut harness, x, _) = with_joystick(Mode::Pointer);
+        assert_eq!(harness.run(50), []);
+
+        x.set(4095);
+        let moves = mouse(&harness.run(50));
+        assert_eq!(
+            moves,
+            [hid::MouseReport {
+                x: 6,
+                ..hid::MouseReport::default()
+            }; 5]
+        );
+    }
+
+    #[test]
+    fn joystick_scroll_mode_scrolls_the_wheel() {
+        let (mut harness, x, y) = with_joystick(Mode::Scroll);
+
+        // Pushing up scrolls up, and right pans right, at the scroll speed.
+        y.set(0);
+        x.set(4095);
+        let scrolls = mouse(&harness.run(500));
+        assert!(scrolls.iter().all(|report| report.x == 0 && report.y == 0));
+        assert_eq!(scrolls.iter().map(|report| i32::from(report.wheel)).sum::<i32>(), 10);
+        assert_eq!(scrolls.iter().map(|report| i32::from(report.pan)).sum::<i32>(), 10);
+    }
+
+    #[test]
+    fn joystick_dpad_mode_presses_the_directional_buttons() {
+        let (mut harness, x, _) = with_joystick(Mode::Dpad);
+
+        x.set(4095);
+        assert_eq!(reports(&harness.run(10)), [keyboard(&[KeyCode::Right])]);
+
+        // Released below three quarters of the threshold only.
+        x.set(2048 + 900);
+        assert_eq!(reports(&harness.run(10)), []);
+        x.set(2048 + 700);
+        assert_eq!(reports(&harness.run(10)), [keyboard(&[])]);
+
+        x.set(0);
+        assert_eq!(reports(&harness.run(10)), [keyboard(&[KeyCode::Left])]);
+    }
+
+    #[test]
+    fn trace_dump_is_logged_over_multiple_polls() {
+        let mut harness = Harness::new(default_konfiguration());
+        harness.kontroller.set_recorder(trace::Recorder::new(64));
+        for _ in 0..5 {
+            harness.tap(Button::Up);
+        }
+
+        // A header, then two samples and two events per tap.
+        let lines = harness.kontroller.recorder().map(|recorder| recorder.dump().lines().count());
+        assert_eq!(lines, Some(21));
+
+        harness.kontroller.dump_trace();
+        for _ in 0..2 {
+            harness.run(1);
+            assert!(harness.kontroller.dump.is_some());
+        }
+        harness.run(1);
+        assert!(harness.kontroller.dump.is_none());
+    }
+
+    /// Returns the [`Konfiguration`] repeating Up with the [`RepeatPolicy`], if any.
+    fn with_up_repeat(policy: Option<Policy>) -> Konfiguration {
+        Konfiguration {
+            button_settings: vec![ButtonSettings {
+                button: Button::Up.into(),
+                repeat: Some(RepeatPolicy {
+                    delay_millis: 300,
+                    policy,
+                }),
+                vibration_filter: None,
+            }],
+            ..default_konfiguration()
+        }
+    }
+
+    #[test]
+    fn held_key_is_reported_once_per_state_change() {
+        let mut harness = Harness::new(with_up_repeat(None));
+
+        harness.press(Button::Up);
+        assert_eq!(reports(&harness.run(2000)), [keyboard(&[KeyCode::Up])]);
+
+        harness.release(Button::Up);
+        assert_eq!(reports(&harness.run(2000)), [keyboard(&[])]);
+    }
+
+    #[test]
+    fn repeats_are_sent_despite_the_duplicate_suppression() {
+        let mut harness = Harness::new(with_up_repeat(Some(Policy::Fixed(Fixed {
+            interval_millis: 100,
+        }))));
+
+        harness.press(Button::Up);
+        assert_eq!(reports(&harness.run(250)), [keyboard(&[KeyCode::Up])]);
+
+        // Each repeat releases the key, then presses it again.
+        let repeat = [keyboard(&[]), keyboard(&[KeyCode::Up])];
+        assert_eq!(reports(&harness.run(100)), repeat);
+        assert_eq!(reports(&harness.run(100)), repeat);
+        assert_eq!(reports(&harness.run(100)), repeat);
+
+        harness.release(Button::Up);
+        assert_eq!(reports(&harness.run(100)), [keyboard(&[])]);
+    }
+
+    /// Returns the default [`Konfiguration`], with the [`Button`]s bound to the [`Action`]s.
+    fn with_actions(actions: impl IntoIterator<Item = (Button, Action)>) -> Konfiguration {
+        let mut config = default_konfiguration();
+        let entries = &mut config.keymap.as_mut().unwrap().entries;
+        for (button, action) in actions {
+            entries.retain(|entry| entry.button() != button);
+            entries.push(Entry {
+                button: button.into(),
+                action: Some(action),
+            });
+        }
+
+        config
+    }
+
+    /// Clears the bonds with Fn1, confirmed within 1s, and cycles the profiles with Fn2,
+    /// the second one mapping Up to A.
+    fn with_system_actions() -> Konfiguration {
+        Konfiguration {
+            system_actions: Some(SystemActionSettings {
+                bindings: Vec::new(),
+                confirmation_timeout_millis: 1000,
+            }),
+            profiles: vec![Keymap {
+                entries: vec![
+                    Entry {
+                        button: Button::Up.into(),
+                        action: Some(Action::KeyCode(KeyCode::A.into())),
+                    },
+                    Entry {
+                        button: Button::Fn2.into(),
+                        action: Some(Action::SystemAction(SystemAction::CycleProfile.into())),
+                    },
+                ],
+            }],
+            ..with_actions([
+                (
+                    Button::Fn1,
+                    Action::SystemAction(SystemAction::ClearBonds.into()),
+                ),
+                (
+                    Button::Fn2,
+                    Action::SystemAction(SystemAction::CycleProfile.into()),
+                ),
+            ])
+        }
+    }
+
+    #[test]
+    fn dangerous_action_is_performed_once_confirmed() {
+        let mut harness = Harness::new(with_system_actions());
+
+        assert_eq!(
+            harness.tap(Button::Fn1),
+            [Output::Status(Status::ConfirmationRequired(
+                SystemAction::ClearBonds
+            ))]
+        );
+        harness.run(500);
+        assert_eq!(
+            harness.tap(Button::Fn1),
+            [Output::Action(SystemAction::ClearBonds)]
+        );
+    }
+
+    #[test]
+    fn dangerous_action_confirmation_times_out() {
+        let mut harness = Harness::new(with_system_actions());
+
+        harness.tap(Button::Fn1);
+        assert_eq!(
+            harness.run(1000),
+            [Output::Status(Status::ConfirmationCancelled(
+                SystemAction::ClearBonds
+            ))]
+        );
+        assert_eq!(
+            harness.tap(Button::Fn1),
+            [Output::Status(Status::ConfirmationRequired(
+                SystemAction::ClearBonds
+            ))]
+        );
+    }
+
+    #[test]
+    fn dangerous_action_confirmation_is_cancelled_by_another_key() {
+        let mut harness = Harness::new(with_system_actions());
+
+        harness.tap(Button::Fn1);
+        let outputs = harness.tap(Button::Up);
+        assert_eq!(
+            outputs[0],
+            Output::Status(Status::ConfirmationCancelled(SystemAction::ClearBonds))
+        );
+        assert_eq!(
+            reports(&outputs),
+            [keyboard(&[KeyCode::Up]), keyboard(&[])]
+        );
+        assert_eq!(
+            harness.tap(Button::Fn1),
+            [Output::Status(Status::ConfirmationRequired(
+                SystemAction::ClearBonds
+            ))]
+        );
+    }
+
+    #[test]
+    fn cycle_profile_switches_the_keymap() {
+        let mut harness = Harness::new(with_system_actions());
+
+        assert_eq!(harness.tap(Button::Fn2), [Output::Status(Status::Profile(1))]);
+        assert_eq!(
+            reports(&harness.tap(Button::Up)),
+            [keyboard(&[KeyCode::A]), keyboard(&[])]
+        );
+
+        assert_eq!(harness.tap(Button::Fn2), [Output::Status(Status::Profile(0))]);
+        assert_eq!(
+            reports(&harness.tap(Button::Up)),
+            [keyboard(&[KeyCode::Up]), keyboard(&[])]
+        );
+    }
+
+    #[test]
+    fn long_holds_are_not_stuck_without_stuck_button_settings() {
+        let mut harness = Harness::new(default_konfiguration());
+
+        harness.press(Button::Up);
+        let outputs = harness.run(DEFAULT_STUCK_BUTTON_TIMEOUT.as_millis() + 1000);
+        assert_eq!(outputs, [keyboard(&[KeyCode::Up])]);
+    }
+
+    #[test]
+    fn button_held_past_the_stuck_timeout_is_quarantined_until_released() {
+        let mut harness = Harness::new(Konfiguration {
+            stuck_buttons: Some(StuckButtonSettings {
+                max_hold_seconds: 1,
+            }),
+            ..default_konfiguration()
+        });
+
+        harness.press(Button::Up);
+        assert_eq!(harness.run(1000), [keyboard(&[KeyCode::Up])]);
+        assert_eq!(
+            harness.run(10),
+            [
+                Output::Status(Status::StuckButton(Button::Up)),
+                keyboard(&[])
+            ]
+        );
+
+        // Nothing is reported while the Button stays down.
+        assert_eq!(harness.run(5000), []);
+
+        harness.release(Button::Up);
+        assert_eq!(
+            harness.run(10),
+            [Output::Status(Status::ButtonRecovered(Button::Up))]
+        );
+        assert_eq!(
+            harness.tap(Button::Up),
+            [keyboard(&[KeyCode::Up]), keyboard(&[])]
+        );
+    }
+
+    /// Toggles the chord mode with Fn2, where Up and Down type `a`, and Enter types `B`.
+    fn with_chord_layout() -> Konfiguration {
+        Konfiguration {
+            chord_layout: Some(ChordLayout {
+                chords: vec![
+                    Chord {
+                        buttons: vec![Button::Up.into(), Button::Down.into()],
+                        key_code: KeyCode::A.into(),
+                        modifiers: Vec::new(),
+                    },
+                    Chord {
+                        buttons: vec![Button::Enter.into()],
+                        key_code: KeyCode::B.into(),
+                        modifiers: vec![KeyCode::Lshift.into()],
+                    },
+                ],
+            }),
+            ..with_actions([(
+                Button::Fn2,
+                Action::SystemAction(SystemAction::ToggleChordMode.into()),
+            )])
+        }
+    }
+
+    #[test]
+    fn chord_mode_types_characters_once_the_chord_is_released() {
+        let mut harness = Harness::new(with_chord_layout());
+        assert_eq!(
+            harness.tap(Button::Fn2),
+            [Output::Status(Status::ChordModeEntered)]
+        );
+
+        harness.press(Button::Up);
+        harness.run(10);
+        harness.press(Button::Down);
+        harness.run(10);
+        harness.release(Button::Up);
+        assert_eq!(harness.run(10), []);
+
+        harness.release(Button::Down);
+        assert_eq!(
+            harness.run(10),
+            [
+                keyboard(&[KeyCode::A]),
+                keyboard(&[]),
+                Output::Status(Status::Character(KeyCode::A)),
+            ]
+        );
+
+        assert_eq!(
+            harness.tap(Button::Enter),
+            [
+                shifted(true, &[KeyCode::B]),
+                keyboard(&[]),
+                Output::Status(Status::Character(KeyCode::B)),
+            ]
+        );
+    }
+
+    #[test]
+    fn chord_mode_reports_unknown_chords() {
+        let mut harness = Harness::new(with_chord_layout());
+        harness.tap(Button::Fn2);
+
+        assert_eq!(
+            harness.tap(Button::Up),
+            [Output::Status(Status::UnknownChord)]
+        );
+    }
+
+    #[test]
+    fn chord_mode_is_left_with_the_toggle() {
+        let mut harness = Harness::new(with_chord_layout());
+        harness.tap(Button::Fn2);
+
+        assert_eq!(
+            harness.tap(Button::Fn2),
+            [Output::Status(Status::ChordModeExited)]
+        );
+        assert_eq!(
+            harness.tap(Button::Up),
+            [keyboard(&[KeyCode::Up]), keyboard(&[])]
+        );
+    }
+
+    #[test]
+    fn chord_mode_is_not_available_without_a_layout() {
+        let mut harness = Harness::new(with_actions([(
+            Button::Fn2,
+            Action::SystemAction(SystemAction::ToggleChordMode.into()),
+        )]));
+
+        assert_eq!(harness.tap(Button::Fn2), []);
+        assert_eq!(
+            harness.tap(Button::Up),
+            [keyboard(&[KeyCode::Up]), keyboard(&[])]
+        );
+    }
+
+    const ALL_BUTTONS: [(Button, KeyCode); 8] = [
+        (Button::Enter, KeyCode::Enter),
+        (Button::Up, KeyCode::Up),
+        (Button::Right, KeyCode::Right),
+        (Button::Left, KeyCode::Left),
+        (Button::Down, KeyCode::Down),
+        (Button::Fn1, KeyCode::F7),
+        (Button::Fn2, KeyCode::F6),
+        (Button::Fn3, KeyCode::F5),
+    ];
+
+    #[test]
+    fn nkey_mode_reports_all_the_buttons_pressed() {
+        let mut harness = Harness::new(Konfiguration {
+            keyboard_report_mode: KeyboardReportMode::NKey.into(),
+            ..default_konfiguration()
+        });
+
+        for (button, _) in ALL_BUTTONS {
+            harness.press(button);
+        }
+
+        let mut expected = hid::NkroKeyboardReport::default();
+        for (_, key_code) in ALL_BUTTONS {
+            assert!(expected.press(key_code as u8));
+        }
+        assert_eq!(reports(&harness.run(10)), [Output::Report(expected.into())]);
+        assert!(!expected.is_pressed(KeyCode::ErrorRollover as u8));
+
+        for (button, _) in ALL_BUTTONS {
+            harness.release(button);
+        }
+        assert_eq!(
+            reports(&harness.run(10)),
+            [Output::Report(hid::NkroKeyboardReport::default().into())]
+        );
+    }
+
+    #[test]
+    fn six_key_mode_reports_a_rollover_error_past_six_keys() {
+        let mut harness = Harness::new(default_konfiguration());
+
+        for (button, _) in ALL_BUTTONS {
+            harness.press(button);
+        }
+        assert_eq!(
+            reports(&harness.run(10)),
+            [keyboard(&[KeyCode::ErrorRollover; MAX_KEYCODES])]
+        );
+
+        harness.release(Button::Fn2);
+        harness.release(Button::Fn3);
+        assert_eq!(
+            reports(&harness.run(10)),
+            // Key codes are ordered as their Buttons.
+            [keyboard(&[
+                KeyCode::Up,
+                KeyCode::Down,
+                KeyCode::Left,
+                KeyCode::Right,
+                KeyCode::Enter,
+                KeyCode::F7,
+            ])]
+        );
+    }
+
+    #[test]
+    fn gamepad_buttons_are_sent_as_gamepad_reports() {
+        let config = with_actions([
+            (Button::Up, Action::GamepadButton(GamepadButton::HatUp.into())),
+            (Button::Right, Action::GamepadButton(GamepadButton::HatRight.into())),
+            (Button::Enter, Action::GamepadButton(GamepadButton::B1.into())),
+            (Button::Fn3, Action::GamepadButton(GamepadButton::B10.into())),
+        ]);
+        assert_eq!(
+            report_types(&config),
+            BTreeSet::from([ReportType::Keyboard, ReportType::Gamepad])
+        );
+
+        let mut harness = Harness::new(config);
+        let gamepad = |hat, buttons| Output::Report(hid::GamepadReport { hat, buttons }.into());
+
+        harness.press(Button::Up);
+        harness.press(Button::Right);
+        assert_eq!(harness.run(10), [gamepad(1, 0)]);
+
+        harness.press(Button::Enter);
+        harness.press(Button::Fn3);
+        assert_eq!(harness.run(10), [gamepad(1, 0b10_0000_0001)]);
+
+        harness.release(Button::Up);
+        assert_eq!(harness.run(10), [gamepad(2, 0b10_0000_0001)]);
+
+        for button in [Button::Right, Button::Enter, Button::Fn3] {
+            harness.release(button);
+        }
+        assert_eq!(
+            harness.run(10),
+            [gamepad(hid::GamepadReport::HAT_CENTERED, 0)]
+        );
+
+        // Other buttons are still sent as keyboard reports.
+        assert_eq!(
+            harness.tap(Button::Down),
+            [keyboard(&[KeyCode::Down]), keyboard(&[])]
+        );
+    }
+
+    #[test]
+    fn multi_touch_sends_both_fingers_until_lifted() {
+        let mut harness = Harness::new(with_actions([(
+            Button::Fn1,
+            Action::MultiTouch(MultiTouch {
+                kind: multi_touch::Kind::PinchOut.into(),
+                duration_millis: 100,
+                ..MultiTouch::default()
+            }),
+        )]));
+
+        harness.press(Button::Fn1);
+        let digitizer: Vec<hid::DigitizerReport> = harness
+            .run(200)
+            .into_iter()
+            .map(|output| match output {
+                Output::Report(hid::Report::Digitizer(digitizer)) => digitizer,
+                output => panic!("expected a digitizer report, got {output:?}"),
+            })
+            .collect();
+
+        // The fingers move every 10ms, and are lifted once the gesture is over.
+        assert_eq!(digitizer.len(), 11);
+        assert!(digitizer.iter().all(|report| report.count() == 2));
+        assert!(digitizer[..10].iter().all(hid::DigitizerReport::is_touching));
+
+        let positions = |report: &hid::DigitizerReport| {
+            report.contacts.map(|contact| contact.map(|contact| contact.x))
+        };
+        assert_eq!(positions(&digitizer[0]), [Some(450), Some(550)]);
+        assert_eq!(positions(&digitizer[10]), [Some(300), Some(700)]);
+        assert!(!digitizer[10].is_touching());
+
+        harness.release(Button::Fn1);
+        assert_eq!(harness.run(10), []);
+    }
 }
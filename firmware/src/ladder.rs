@@ -0,0 +1,264 @@
+//! Resistor ladder decoding, to connect multiple buttons to a single ADC line.
+//!
+//! Each button switches a different resistor of a voltage divider, so that every
+//! combination of pressed buttons reads as a distinct voltage, matched against
+//! calibrated windows. While a button is pressed or released, the voltage sweeps
+//! through the windows of other combinations: readings are only trusted once stable.
+
+use std::{cell::RefCell, rc::Rc};
+
+use embassy_time::Instant;
+
+use crate::{
+    analog,
+    key::{self, Pin},
+    proto::kontroller::v1::{Button, LadderSettings},
+};
+
+/// Default number of consecutive readings within a [`Window`] needed to enter it.
+pub const DEFAULT_SETTLE_SAMPLES: u32 = 3;
+
+/// A window of raw ADC readings of the [`Ladder`], in which some [`Button`]s are pressed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Window {
+    /// The [`Button`]s pressed when the reading is within the window.
+    pub buttons: Vec<Button>,
+    /// The lowest reading of the window.
+    pub min: u16,
+    /// The highest reading of the window.
+    pub max: u16,
+    /// The margin by which the reading can leave the window, once entered,
+    /// before the [`Button`]s are released.
+    pub hysteresis: u16,
+}
+
+impl Window {
+    /// Returns `true` if the reading is within the window, widened by the margin.
+    fn contains(&self, reading: u16, margin: u16) -> bool {
+        (self.min.saturating_sub(margin)..=self.max.saturating_add(margin)).contains(&reading)
+    }
+}
+
+/// A resistor ladder, connecting multiple buttons to a single ADC [`analog::Channel`]:
+/// each combination of pressed buttons produces a different voltage, matched against
+/// a calibration table of [`Window`]s.
+///
+/// Each [`Button`] is read through a [`LadderPin`], pressed while the last settled
+/// reading is in one of its [`Window`]s. Readings outside all of them, such as the
+/// idle one, release all the [`Button`]s.
+pub struct Ladder {
+    channel: Box<dyn analog::Channel>,
+    windows: Vec<Window>,
+    settle_samples: u32,
+    /// Index of the window the reading is in, if any.
+    active: Option<usize>,
+    /// Window the reading is settling in, and for how many samples.
+    settling: Option<(Option<usize>, u32)>,
+    pressed: Rc<RefCell<Vec<Button>>>,
+}
+
+impl Ladder {
+    /// Builds a [`Ladder`] sampling the given [`analog::Channel`], using the
+    /// calibration table in the [`LadderSettings`].
+    pub fn new(channel: impl analog::Channel + 'static, settings: &LadderSettings) -> Self {
+        let clamp = |value: u32| u16::try_from(value).unwrap_or(u16::MAX);
+
+        Self {
+            channel: Box::new(channel),
+            windows: settings
+                .windows
+                .iter()
+                .map(|window| Window {
+                    buttons: window.buttons().collect(),
+                    min: clamp(window.min),
+                    max: clamp(window.max),
+                    hysteresis: clamp(window.hysteresis),
+                })
+                .collect(),
+            settle_samples: match settings.settle_samples {
+                0 => DEFAULT_SETTLE_SAMPLES,
+                samples => samples,
+            },
+            active: None,
+            settling: None,
+            pressed: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// Returns the [`LadderPin`] of the [`Button`], if part of any [`Window`].
+    #[must_use]
+    pub fn pin(&self, button: Button) -> Option<LadderPin> {
+        self.windows
+            .iter()
+            .any(|window| window.buttons.contains(&button))
+            .then(|| LadderPin {
+                pressed: Rc::clone(&self.pressed),
+                button,
+            })
+    }
+
+    /// Returns the [`Window`] matching the reading, preferring the active one
+    /// as long as the reading is within its hysteresis.
+    fn detect(&self, reading: u16) -> Option<usize> {
+        self.active
+            .filter(|index| {
+                let window = &self.windows[*index];
+                window.contains(reading, window.hysteresis)
+            })
+            .or_else(|| {
+                self.windows
+                    .iter()
+                    .position(|window| window.contains(reading, 0))
+            })
+    }
+}
+
+impl key::Scan for Ladder {
+    fn scan(&mut self, _now: Instant) {
+        let reading = self.channel.read();
+        let detected = self.detect(reading);
+
+        if detected == self.active {
+            self.settling = None;
+            return;
+        }
+
+        let samples = match self.settling {
+            Some((window, samples)) if window == detected => samples + 1,
+            _ => 1,
+        };
+
+        if samples < self.settle_samples {
+            self.settling = Some((detected, samples));
+            return;
+        }
+
+        self.active = detected;
+        self.settling = None;
+        *self.pressed.borrow_mut() = detected
+            .map(|index| self.windows[index].buttons.clone())
+            .unwrap_or_default();
+    }
+}
+
+/// A [`Button`] of a [`Ladder`], reading as low when pressed in the last scan.
+#[derive(Debug, Clone)]
+pub struct LadderPin {
+    pressed: Rc<RefCell<Vec<Button>>>,
+    button: Button,
+}
+
+impl Pin for LadderPin {
+    fn is_low(&self) -> bool {
+        self.pressed.borrow().contains(&self.button)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analog::tests::FakeChannel, key::Scan, proto::kontroller::v1::LadderWindow,
+    };
+
+    /// Idle reading of a ladder pulled up to the ADC reference, with no button pressed.
+    const IDLE: u16 = 4095;
+
+    fn window(buttons: &[Button], min: u32, max: u32, hysteresis: u32) -> LadderWindow {
+        LadderWindow {
+            buttons: buttons.iter().map(|button| (*button).into()).collect(),
+            min,
+            max,
+            hysteresis,
+        }
+    }
+
+    /// Up, Down and both together, with adjacent windows for Enter and Fn1.
+    fn ladder(settle_samples: u32) -> (FakeChannel, Ladder) {
+        let channel = FakeChannel::new(IDLE);
+        let ladder = Ladder::new(
+            channel.clone(),
+            &LadderSettings {
+                windows: vec![
+                    window(&[Button::Up, Button::Down], 600, 900, 0),
+                    window(&[Button::Up], 1000, 1400, 0),
+                    window(&[Button::Down], 2000, 2400, 0),
+                    window(&[Button::Enter], 3000, 3199, 50),
+                    window(&[Button::Fn1], 3200, 3400, 50),
+                ],
+                settle_samples,
+            },
+        );
+
+        (channel, ladder)
+    }
+
+    /// Scans the [`Ladder`] with the reading, returning the [`Button`]s read as pressed.
+    fn scan(channel: &FakeChannel, ladder: &mut Ladder, reading: u16) -> Vec<Button> {
+        channel.set(reading);
+        ladder.scan(Instant::from_ticks(0));
+
+        [Button::Up, Button::Down, Button::Enter, Button::Fn1]
+            .into_iter()
+            .filter(|button| ladder.pin(*button).is_some_and(|pin| pin.is_low()))
+            .collect()
+    }
+
+    #[test]
+    fn readings_press_the_buttons_of_their_window() {
+        let (channel, mut ladder) = ladder(1);
+
+        assert_eq!(scan(&channel, &mut ladder, 1200), [Button::Up]);
+        assert_eq!(scan(&channel, &mut ladder, 2000), [Button::Down]);
+        assert_eq!(scan(&channel, &mut ladder, 750), [Button::Up, Button::Down]);
+        assert_eq!(scan(&channel, &mut ladder, 1600), []);
+    }
+
+    #[test]
+    fn idle_reading_presses_nothing() {
+        let (channel, mut ladder) = ladder(1);
+
+        assert_eq!(scan(&channel, &mut ladder, IDLE), []);
+        assert_eq!(scan(&channel, &mut ladder, 1200), [Button::Up]);
+        assert_eq!(scan(&channel, &mut ladder, IDLE), []);
+        assert!(ladder.pin(Button::Left).is_none());
+    }
+
+    #[test]
+    fn window_bounds_are_inclusive_between_adjacent_buttons() {
+        let (channel, mut ladder) = ladder(1);
+
+        assert_eq!(scan(&channel, &mut ladder, 3000), [Button::Enter]);
+        assert_eq!(scan(&channel, &mut ladder, IDLE), []);
+        assert_eq!(scan(&channel, &mut ladder, 3200), [Button::Fn1]);
+        assert_eq!(scan(&channel, &mut ladder, IDLE), []);
+        assert_eq!(scan(&channel, &mut ladder, 3199), [Button::Enter]);
+    }
+
+    #[test]
+    fn hysteresis_keeps_the_active_window_across_the_boundary() {
+        let (channel, mut ladder) = ladder(1);
+
+        assert_eq!(scan(&channel, &mut ladder, 3150), [Button::Enter]);
+        assert_eq!(scan(&channel, &mut ladder, 3249), [Button::Enter]);
+        assert_eq!(scan(&channel, &mut ladder, 3250), [Button::Fn1]);
+        assert_eq!(scan(&channel, &mut ladder, 3150), [Button::Fn1]);
+        assert_eq!(scan(&channel, &mut ladder, 3149), [Button::Enter]);
+    }
+
+    #[test]
+    fn readings_are_only_trusted_once_settled() {
+        let (channel, mut ladder) = ladder(DEFAULT_SETTLE_SAMPLES);
+
+        // Pressing Down sweeps through the Up window on the way.
+        assert_eq!(scan(&channel, &mut ladder, 1200), []);
+        assert_eq!(scan(&channel, &mut ladder, 1800), []);
+        assert_eq!(scan(&channel, &mut ladder, 2200), []);
+        assert_eq!(scan(&channel, &mut ladder, 2200), []);
+        assert_eq!(scan(&channel, &mut ladder, 2200), [Button::Down]);
+
+        assert_eq!(scan(&channel, &mut ladder, IDLE), [Button::Down]);
+        assert_eq!(scan(&channel, &mut ladder, IDLE), [Button::Down]);
+        assert_eq!(scan(&channel, &mut ladder, IDLE), []);
+    }
+}
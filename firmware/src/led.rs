@@ -1,10 +1,12 @@
-use std::ops::Deref;
+use std::{collections::BTreeSet, ops::Deref};
 
 use embassy_time::{Duration, Timer};
 use esp_idf_svc::{
     hal::gpio::{AnyIOPin, InputOutput, PinDriver},
     sys::EspError,
 };
+use firmware::{hid::HostLeds, proto::kontroller::v1::SystemAction, status::Status};
+use futures::{channel::mpsc::Receiver, future::Either, StreamExt};
 
 pub struct Led<'d> {
     pin: PinDriver<'d, AnyIOPin, InputOutput>,
@@ -74,12 +76,10 @@ impl<'d> Blinker<'d> {
         Self { led, config }
     }
 
-    #[allow(dead_code)]
     pub async fn short_blink(&mut self) -> anyhow::Result<()> {
         self.blink(self.config.short_blink_duration).await
     }
 
-    #[allow(dead_code)]
     pub async fn long_blink(&mut self) -> anyhow::Result<()> {
         self.blink(self.config.long_blink_duration).await
     }
@@ -93,4 +93,134 @@ impl<'d> Blinker<'d> {
 
         Ok(())
     }
+
+    /// Blinks the LED the specified amount of times.
+    pub async fn blink_times(&mut self, times: usize, d: Duration) -> anyhow::Result<()> {
+        for _ in 0..times {
+            self.blink(d).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Shows the [`Status`] updates received from the channel on the LED:
+    ///
+    /// * the LED blinks quickly while advertising,
+    /// * the LED blinks continuously while a system action waits for confirmation,
+    /// * the LED blinks slowly while any button is stuck,
+    /// * the LED blinks once for every report sent to the host,
+    /// * the LED stays on while the input lock mode, or the host Caps Lock, is enabled,
+    /// * the LED blinks a pattern specific to each system action when performed,
+    /// * the LED blinks once per profile index, or once every 25% of battery level,
+    /// * the LED blinks once per character typed in chord mode, three times for unknown chords.
+    pub async fn show(&mut self, mut rx: Receiver<Status>) -> anyhow::Result<()> {
+        let mut advertising = false;
+        let mut confirming = false;
+        let mut locked = false;
+        let mut caps_lock = false;
+        let mut stuck = BTreeSet::new();
+
+        loop {
+            let pattern = if confirming {
+                Some(Duration::MIN)
+            } else if !stuck.is_empty() {
+                Some(self.config.long_blink_duration)
+            } else if advertising {
+                // TODO(ar3s3ru): do not hardcode.
+                Some(Duration::from_millis(100))
+            } else {
+                None
+            };
+
+            let status = if let Some(pause) = pattern {
+                let blink_pattern = Box::pin(self.blink_with_pause(pause));
+
+                match futures::future::select(rx.next(), blink_pattern).await {
+                    Either::Left((status, _)) => status,
+                    Either::Right((result, _)) => {
+                        result?;
+                        continue;
+                    }
+                }
+            } else {
+                rx.next().await
+            };
+
+            let Some(status) = status else {
+                return Ok(());
+            };
+
+            match status {
+                Status::Advertising => advertising = true,
+                Status::Connected => advertising = false,
+                Status::Activity if !locked => self.short_blink().await?,
+                Status::Activity => {}
+                Status::Locked => {
+                    locked = true;
+                    self.long_blink().await?;
+                }
+                Status::Unlocked => locked = false,
+                Status::ConfirmationRequired(_) => confirming = true,
+                Status::ConfirmationCancelled(_) => confirming = false,
+                Status::Action(action) => {
+                    confirming = false;
+                    self.show_action(action).await?;
+                }
+                Status::Profile(profile) => {
+                    self.blink_times(profile + 1, self.config.short_blink_duration)
+                        .await?;
+                }
+                Status::Battery(level) => {
+                    self.blink_times(
+                        usize::from(level.div_ceil(25)),
+                        self.config.short_blink_duration,
+                    )
+                    .await?;
+                }
+                Status::StuckButton(button) => {
+                    stuck.insert(button);
+                }
+                Status::ButtonRecovered(button) => {
+                    stuck.remove(&button);
+                }
+                Status::ChordModeEntered | Status::ChordModeExited => self.long_blink().await?,
+                Status::Character(_) => self.short_blink().await?,
+                Status::UnknownChord => {
+                    self.blink_times(3, self.config.short_blink_duration)
+                        .await?;
+                }
+                Status::HostLeds(leds) => caps_lock = leds.is_on(HostLeds::CAPS_LOCK),
+            }
+
+            if locked || caps_lock {
+                self.led.on().await?;
+            } else {
+                self.led.off().await?;
+            }
+        }
+    }
+
+    async fn show_action(&mut self, action: SystemAction) -> anyhow::Result<()> {
+        match action {
+            SystemAction::EnterPairing | SystemAction::Reboot | SystemAction::FactoryReset => {
+                self.long_blink().await
+            }
+            SystemAction::ClearBonds => {
+                self.blink_times(3, self.config.short_blink_duration).await
+            }
+            // Feedback is shown through the Profile and Battery status updates.
+            SystemAction::CycleProfile
+            | SystemAction::BatteryCheck
+            | SystemAction::ToggleChordMode
+            | SystemAction::DumpTrace
+            | SystemAction::Unspecified => Ok(()),
+        }
+    }
+
+    async fn blink_with_pause(&mut self, pause: Duration) -> anyhow::Result<()> {
+        self.short_blink().await?;
+        Timer::after(pause).await;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,24 @@
+//! Hardware-independent building blocks of the openmoto `kontroller` firmware.
+//!
+//! Everything in here can be compiled for the host too, so that the `kontroller`
+//! logic can be exercised without the actual hardware (e.g. by the `simulator`).
+
+#![allow(clippy::multiple_crate_versions)]
+
+pub mod analog;
+pub mod chord;
+pub mod encoder;
+pub mod expander;
+pub mod gesture;
+pub mod hid;
+pub mod identity;
+pub mod joystick;
+pub mod key;
+pub mod kontroller;
+pub mod ladder;
+pub mod matrix;
+#[allow(clippy::pedantic, missing_docs)]
+pub mod proto;
+pub mod status;
+pub mod touch;
+pub mod trace;
@@ -2,20 +2,38 @@
 
 #![allow(clippy::multiple_crate_versions)]
 
+use std::rc::Rc;
+
 use embassy_time::Instant;
-use esp_idf_svc::hal::{gpio::IOPin, peripherals::Peripherals, task};
+use esp_idf_svc::{
+    hal::{
+        adc::oneshot::AdcDriver,
+        gpio::{AnyIOPin, IOPin, PinDriver, Pull},
+        peripherals::Peripherals,
+        task,
+    },
+    nvs::EspDefaultNvsPartition,
+    sys::EspError,
+};
+use firmware::{
+    hid,
+    identity::Identity,
+    joystick::{self, Joystick},
+    key::{self, Key, Pin},
+    kontroller,
+    ladder::Ladder,
+    proto::kontroller::v1::{Button, SystemAction},
+    status::Status,
+    trace,
+};
 
+mod adc;
 mod ble;
-mod hid;
-pub mod key;
-mod kontroller;
 mod led;
-#[allow(clippy::pedantic)]
-mod proto;
+mod system;
 
 use futures::channel::mpsc::channel;
 use led::Led;
-use proto::kontroller::{hid::v1::KeyCode, v1::Button};
 
 fn main() -> anyhow::Result<()> {
     // It is necessary to call this function once. Otherwise some patches to the runtime
@@ -28,49 +46,97 @@ fn main() -> anyhow::Result<()> {
     log::debug!("Initializing peripherals...");
 
     let peripherals = Peripherals::take()?;
+    let nvs = EspDefaultNvsPartition::take()?;
 
     let mut led_blinker = led::Blinker::from(Led::new(peripherals.pins.gpio7)?);
 
+    let konfiguration = kontroller::default_konfiguration();
+    let keyboard_report_mode = konfiguration.keyboard_report_mode();
+    let report_types = kontroller::report_types(&konfiguration);
+    let identity = Identity::resolve(konfiguration.identity.as_ref());
+    let has_joystick = konfiguration.joystick.is_some();
+
+    let mut pins = [
+        (Button::Enter, button_pin(peripherals.pins.gpio8.downgrade())?),
+        (Button::Up, button_pin(peripherals.pins.gpio9.downgrade())?),
+        (Button::Right, button_pin(peripherals.pins.gpio10.downgrade())?),
+        (Button::Left, button_pin(peripherals.pins.gpio11.downgrade())?),
+        (Button::Down, button_pin(peripherals.pins.gpio12.downgrade())?),
+        (Button::Fn1, button_pin(peripherals.pins.gpio4.downgrade())?),
+        (Button::Fn2, button_pin(peripherals.pins.gpio5.downgrade())?),
+        (Button::Fn3, button_pin(peripherals.pins.gpio6.downgrade())?),
+    ];
+
+    let adc = Rc::new(AdcDriver::new(peripherals.adc1)?);
+
+    // The resistor ladder line is wired to the GPIO3 pin of the ADC1 unit:
+    // the buttons it decodes are read from it, instead of their own pins.
+    let ladder = match &konfiguration.ladder {
+        Some(settings) => {
+            let channel = adc::Channel::new(adc.clone(), peripherals.pins.gpio3)?;
+            let ladder = Ladder::new(channel, settings);
+            for (button, pin) in &mut pins {
+                if let Some(ladder_pin) = ladder.pin(*button) {
+                    *pin = Box::new(ladder_pin);
+                }
+            }
+            Some(ladder)
+        }
+        None => None,
+    };
+
     let mut kontroller = kontroller::Kontroller::new(
-        [
-            (Button::Enter, peripherals.pins.gpio8.downgrade()),
-            (Button::Up, peripherals.pins.gpio9.downgrade()),
-            (Button::Right, peripherals.pins.gpio10.downgrade()),
-            (Button::Left, peripherals.pins.gpio11.downgrade()),
-            (Button::Down, peripherals.pins.gpio12.downgrade()),
-            (Button::Fn1, peripherals.pins.gpio4.downgrade()),
-            (Button::Fn2, peripherals.pins.gpio5.downgrade()),
-            (Button::Fn3, peripherals.pins.gpio6.downgrade()),
-        ],
-        proto::kontroller::v1::Konfiguration {
-            buttons_poll_interval_micros: 500,
-            keymap: Some(kontroller::make_keymap([
-                (Button::Enter, KeyCode::Enter),
-                (Button::Up, KeyCode::Up),
-                (Button::Right, KeyCode::Right),
-                (Button::Left, KeyCode::Left),
-                (Button::Down, KeyCode::Down),
-                (Button::Fn1, KeyCode::F7),
-                (Button::Fn2, KeyCode::F6),
-                (Button::Fn3, KeyCode::F5),
-            ])),
+        pins.map(|(button, pin)| (button, Key::new(pin, key::Config::default()))),
+        konfiguration,
+    );
+
+    if let Some(ladder) = ladder {
+        kontroller.add_scanner(ladder);
+    }
+
+    // The joystick axes are wired to the GPIO1 and GPIO2 pins of the ADC1 unit.
+    if has_joystick {
+        kontroller.set_joystick(Joystick::new(
+            adc::Channel::new(adc.clone(), peripherals.pins.gpio1)?,
+            adc::Channel::new(adc.clone(), peripherals.pins.gpio2)?,
+            joystick::Config::default(),
+        ));
+    }
+
+    // Keep a trace of the latest button activity, dumped to the log on demand.
+    kontroller.set_recorder(trace::Recorder::new(trace::DEFAULT_CAPACITY));
+
+    let mut ble_server = ble::Server::initialize(
+        &ble::Config {
+            identity,
+            keyboard_report_mode,
+            report_types,
         },
+        nvs.clone(),
     )?;
 
-    let mut ble_server = ble::Server::initialize(&ble::Config {
-        device_name: "DMD CTL 8K",
-    })?;
-
     let (report_tx, report_rx) = channel::<hid::Report>(1);
+    let (status_tx, status_rx) = channel::<Status>(4);
+    let (action_tx, action_rx) = channel::<SystemAction>(1);
 
     log::debug!("Peripherals fully initialized");
 
     task::block_on(async {
         futures::try_join!(
-            kontroller.start(Instant::now, report_tx),
-            ble_server.start(report_rx, &mut led_blinker),
+            kontroller.start(Instant::now, report_tx, status_tx.clone(), action_tx),
+            ble_server.start(report_rx, status_tx.clone()),
+            system::run(action_rx, status_tx, nvs),
+            led_blinker.show(status_rx),
         )
     })?;
 
     Ok(())
 }
+
+/// Returns the input pin of a button shorting it to ground, with its pull-up resistor enabled.
+fn button_pin(pin: AnyIOPin) -> Result<Box<dyn Pin>, EspError> {
+    let mut driver = PinDriver::input(pin)?;
+    driver.set_pull(Pull::Up)?;
+
+    Ok(Box::new(driver))
+}
@@ -0,0 +1,403 @@
+//! Row/column key matrix scanning, to connect more buttons than the available GPIO pins.
+
+use std::{cell::RefCell, rc::Rc};
+
+use embassy_time::{Duration, Instant};
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::hal::{
+    delay::Ets,
+    gpio::{AnyIOPin, Output, PinDriver},
+};
+
+use crate::key::{self, Pin};
+
+/// A digital output signal that drives a line of the [`Matrix`].
+///
+/// Sensed lines are wired using pull-up resistors, so a line is selected
+/// by driving it low.
+pub trait Line {
+    /// Drives the line low, selecting it.
+    fn set_low(&mut self);
+
+    /// Drives the line high, deselecting it.
+    fn set_high(&mut self);
+}
+
+impl<L: Line + ?Sized> Line for Box<L> {
+    fn set_low(&mut self) {
+        L::set_low(self);
+    }
+
+    fn set_high(&mut self) {
+        L::set_high(self);
+    }
+}
+
+#[cfg(target_os = "espidf")]
+impl<'d> Line for PinDriver<'d, AnyIOPin, Output> {
+    fn set_low(&mut self) {
+        // Setting the level of a pin configured as output cannot fail.
+        let _ = PinDriver::set_low(self);
+    }
+
+    fn set_high(&mut self) {
+        // Setting the level of a pin configured as output cannot fail.
+        let _ = PinDriver::set_high(self);
+    }
+}
+
+/// A busy-wait delay, short enough to be spent within a single [`Matrix`] scan.
+pub trait Delay {
+    /// Waits for the specified time before returning.
+    fn delay(&mut self, duration: Duration);
+}
+
+#[cfg(target_os = "espidf")]
+impl Delay for Ets {
+    fn delay(&mut self, duration: Duration) {
+        Ets::delay_us(u32::try_from(duration.as_micros()).unwrap_or(u32::MAX));
+    }
+}
+
+/// Orientation of the diodes of the [`Matrix`], which determines
+/// which lines are driven and which ones are sensed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Diodes {
+    /// Diodes point from columns to rows: rows are driven, columns are sensed.
+    #[default]
+    ColumnToRow,
+    /// Diodes point from rows to columns: columns are driven, rows are sensed.
+    RowToColumn,
+    /// The matrix has no diodes: rows are driven, columns are sensed,
+    /// and presses that may be caused by ghosting are rejected.
+    None,
+}
+
+/// A key matrix, scanned one driven line at a time.
+///
+/// Each position is read through a [`Cell`], holding its level from the last scan.
+pub struct Matrix<D, S> {
+    drive: Vec<D>,
+    sense: Vec<S>,
+    diodes: Diodes,
+    columns: usize,
+    pressed: Rc<RefCell<Vec<bool>>>,
+    /// Time waited after selecting each driven line, and how.
+    settle: Option<(Duration, Box<dyn Delay>)>,
+}
+
+impl<D: Line, S: Pin> Matrix<D, S> {
+    /// Builds a [`Matrix`] out of the driven and sensed lines.
+    ///
+    /// Depending on the [`Diodes`] orientation, the driven lines are either
+    /// the rows or the columns of the [`Matrix`], and the sensed lines the other ones.
+    #[must_use]
+    pub fn new(drive: Vec<D>, sense: Vec<S>, diodes: Diodes) -> Self {
+        let (rows, columns) = match diodes {
+            Diodes::ColumnToRow | Diodes::None => (drive.len(), sense.len()),
+            Diodes::RowToColumn => (sense.len(), drive.len()),
+        };
+
+        let mut matrix = Self {
+            drive,
+            sense,
+            diodes,
+            columns,
+            pressed: Rc::new(RefCell::new(vec![false; rows * columns])),
+            settle: None,
+        };
+
+        for line in &mut matrix.drive {
+            line.set_high();
+        }
+
+        matrix
+    }
+
+    /// Returns the [`Cell`] at the specified row and column, if part of the [`Matrix`].
+    #[must_use]
+    pub fn cell(&self, row: usize, column: usize) -> Option<Cell> {
+        let index = row * self.columns + column;
+
+        (column < self.columns && index < self.pressed.borrow().len()).then(|| Cell {
+            pressed: Rc::clone(&self.pressed),
+            index,
+        })
+    }
+
+    /// Waits for the settle time after selecting each driven line, before sensing
+    /// the other ones, for matrices whose long wires are slow to change level.
+    #[must_use]
+    pub fn with_settle(mut self, settle: Duration, delay: impl Delay + 'static) -> Self {
+        self.settle = Some((settle, Box::new(delay)));
+        self
+    }
+
+    fn index(&self, driven: usize, sensed: usize) -> usize {
+        match self.diodes {
+            Diodes::ColumnToRow | Diodes::None => driven * self.columns + sensed,
+            Diodes::RowToColumn => sensed * self.columns + driven,
+        }
+    }
+}
+
+impl<D: Line, S: Pin> key::Scan for Matrix<D, S> {
+    fn scan(&mut self, _now: Instant) {
+        let mut scanned = vec![false; self.pressed.borrow().len()];
+
+        for driven in 0..self.drive.len() {
+            self.drive[driven].set_low();
+            if let Some((settle, delay)) = &mut self.settle {
+                delay.delay(*settle);
+            }
+            for (sensed, line) in self.sense.iter().enumerate() {
+                scanned[self.index(driven, sensed)] = line.is_low();
+            }
+            self.drive[driven].set_high();
+        }
+
+        let mut pressed = self.pressed.borrow_mut();
+
+        if self.diodes == Diodes::None {
+            let ghosts: Vec<usize> = (0..scanned.len())
+                .filter(|index| scanned[*index] && !pressed[*index])
+                .filter(|index| is_ghost(&scanned, self.columns, *index))
+                .collect();
+
+            // Without diodes, a new press closing a rectangle with three other
+            // pressed positions is indistinguishable from a ghost: it is rejected.
+            for index in ghosts {
+                log::debug!("matrix press at {index} rejected as a possible ghost");
+                scanned[index] = false;
+            }
+        }
+
+        *pressed = scanned;
+    }
+}
+
+/// Returns `true` if the pressed position at `index` forms a rectangle
+/// with three other pressed positions.
+fn is_ghost(pressed: &[bool], columns: usize, index: usize) -> bool {
+    let rows = pressed.len() / columns;
+    let (row, column) = (index / columns, index % columns);
+    let at = |row: usize, column: usize| pressed[row * columns + column];
+
+    (0..rows).filter(|other| *other != row).any(|other_row| {
+        at(other_row, column)
+            && (0..columns)
+                .filter(|other| *other != column)
+                .any(|other_column| at(row, other_column) && at(other_row, other_column))
+    })
+}
+
+/// A position of a [`Matrix`], reading as low when pressed in the last scan.
+#[derive(Debug, Clone)]
+pub struct Cell {
+    pressed: Rc<RefCell<Vec<bool>>>,
+    index: usize,
+}
+
+impl Pin for Cell {
+    fn is_low(&self) -> bool {
+        self.pressed.borrow()[self.index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::key::Scan;
+
+    const ROWS: usize = 3;
+    const COLUMNS: usize = 4;
+
+    /// The electrical model of a [`Matrix`], with the positions pressed by the test.
+    #[derive(Debug, Default)]
+    struct Wiring {
+        diodes: Diodes,
+        pressed: BTreeSet<(usize, usize)>,
+        /// Driven lines currently low.
+        selected: BTreeSet<usize>,
+        /// Whether the lines are slow, only reading the selected level after a delay.
+        slow: bool,
+        settled: bool,
+        delays: Vec<Duration>,
+    }
+
+    impl Wiring {
+        /// Returns `true` if the sensed line is pulled low by a selected driven line.
+        fn is_low(&self, sensed: usize) -> bool {
+            if self.slow && !self.settled {
+                return false;
+            }
+
+            // Positions as (driven, sensed) switches.
+            let switches: Vec<(usize, usize)> = self
+                .pressed
+                .iter()
+                .map(|(row, column)| match self.diodes {
+                    Diodes::ColumnToRow | Diodes::None => (*row, *column),
+                    Diodes::RowToColumn => (*column, *row),
+                })
+                .collect();
+
+            if self.diodes != Diodes::None {
+                return switches
+                    .iter()
+                    .any(|(driven, to)| *to == sensed && self.selected.contains(driven));
+            }
+
+            // Without diodes, current flows both ways through any pressed switch.
+            let mut driven = self.selected.clone();
+            let mut reached = BTreeSet::new();
+            loop {
+                let more: BTreeSet<usize> = switches
+                    .iter()
+                    .filter(|(from, _)| driven.contains(from))
+                    .map(|(_, to)| *to)
+                    .collect();
+                let back: BTreeSet<usize> = switches
+                    .iter()
+                    .filter(|(_, to)| more.contains(to))
+                    .map(|(from, _)| *from)
+                    .collect();
+
+                if more == reached && back.is_subset(&driven) {
+                    return reached.contains(&sensed);
+                }
+
+                reached = more;
+                driven.extend(back);
+            }
+        }
+    }
+
+    type Shared = Rc<RefCell<Wiring>>;
+
+    struct FakeLine(Shared, usize);
+
+    impl Line for FakeLine {
+        fn set_low(&mut self) {
+            let mut wiring = self.0.borrow_mut();
+            wiring.selected.insert(self.1);
+            wiring.settled = false;
+        }
+
+        fn set_high(&mut self) {
+            self.0.borrow_mut().selected.remove(&self.1);
+        }
+    }
+
+    struct FakeSense(Shared, usize);
+
+    impl Pin for FakeSense {
+        fn is_low(&self) -> bool {
+            self.0.borrow().is_low(self.1)
+        }
+    }
+
+    struct FakeDelay(Shared);
+
+    impl Delay for FakeDelay {
+        fn delay(&mut self, duration: Duration) {
+            let mut wiring = self.0.borrow_mut();
+            wiring.settled = true;
+            wiring.delays.push(duration);
+        }
+    }
+
+    fn matrix(diodes: Diodes) -> (Shared, Matrix<FakeLine, FakeSense>) {
+        let wiring = Rc::new(RefCell::new(Wiring {
+            diodes,
+            ..Wiring::default()
+        }));
+
+        let (drive, sense) = match diodes {
+            Diodes::ColumnToRow | Diodes::None => (ROWS, COLUMNS),
+            Diodes::RowToColumn => (COLUMNS, ROWS),
+        };
+        let matrix = Matrix::new(
+            (0..drive).map(|line| FakeLine(wiring.clone(), line)).collect(),
+            (0..sense).map(|line| FakeSense(wiring.clone(), line)).collect(),
+            diodes,
+        );
+
+        (wiring, matrix)
+    }
+
+    /// Scans the [`Matrix`] with the positions pressed, returning the ones read as pressed.
+    fn scan(
+        wiring: &Shared,
+        matrix: &mut Matrix<FakeLine, FakeSense>,
+        pressed: &[(usize, usize)],
+    ) -> Vec<(usize, usize)> {
+        wiring.borrow_mut().pressed = pressed.iter().copied().collect();
+        matrix.scan(Instant::from_ticks(0));
+
+        (0..ROWS)
+            .flat_map(|row| (0..COLUMNS).map(move |column| (row, column)))
+            .filter(|(row, column)| matrix.cell(*row, *column).unwrap().is_low())
+            .collect()
+    }
+
+    #[test]
+    fn scan_reads_the_pressed_position_with_both_diode_orientations() {
+        for diodes in [Diodes::ColumnToRow, Diodes::RowToColumn] {
+            let (wiring, mut matrix) = matrix(diodes);
+
+            assert_eq!(scan(&wiring, &mut matrix, &[(1, 2)]), [(1, 2)], "{diodes:?}");
+            assert_eq!(scan(&wiring, &mut matrix, &[]), [], "{diodes:?}");
+        }
+    }
+
+    #[test]
+    fn scan_reads_multiple_positions_pressed_together() {
+        let rectangle = [(0, 0), (0, 3), (2, 0)];
+
+        for diodes in [Diodes::ColumnToRow, Diodes::RowToColumn] {
+            let (wiring, mut matrix) = matrix(diodes);
+
+            assert_eq!(scan(&wiring, &mut matrix, &rectangle), rectangle, "{diodes:?}");
+        }
+
+        let (wiring, mut matrix) = matrix(Diodes::None);
+        assert_eq!(
+            scan(&wiring, &mut matrix, &[(0, 0), (1, 1), (2, 3)]),
+            [(0, 0), (1, 1), (2, 3)]
+        );
+    }
+
+    #[test]
+    fn scan_without_diodes_rejects_ghosts() {
+        let (wiring, mut matrix) = matrix(Diodes::None);
+
+        assert_eq!(scan(&wiring, &mut matrix, &[(0, 0), (0, 3)]), [(0, 0), (0, 3)]);
+
+        // The third corner of a rectangle also closes the fourth one:
+        // neither can be told apart from a ghost.
+        assert_eq!(
+            scan(&wiring, &mut matrix, &[(0, 0), (0, 3), (2, 0)]),
+            [(0, 0), (0, 3)]
+        );
+
+        // Once the rectangle is opened again, the remaining presses are read.
+        assert_eq!(scan(&wiring, &mut matrix, &[(0, 3), (2, 0)]), [(0, 3), (2, 0)]);
+    }
+
+    #[test]
+    fn scan_waits_for_slow_lines_to_settle() {
+        let (wiring, mut matrix) = matrix(Diodes::ColumnToRow);
+        wiring.borrow_mut().slow = true;
+
+        assert_eq!(scan(&wiring, &mut matrix, &[(1, 2)]), []);
+
+        let settle = Duration::from_micros(10);
+        let mut matrix = matrix.with_settle(settle, FakeDelay(wiring.clone()));
+
+        assert_eq!(scan(&wiring, &mut matrix, &[(1, 2)]), [(1, 2)]);
+        assert_eq!(wiring.borrow().delays, [settle; ROWS]);
+    }
+}
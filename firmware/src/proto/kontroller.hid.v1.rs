@@ -1,4 +1,109 @@
 // @generated
+/// A control of the HID gamepad, either a hat switch direction or a numbered button.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum GamepadButton {
+    /// Default value, should not be used.
+    Unspecified = 0,
+    /// Moves the hat switch up, combined with the other hat directions pressed.
+    HatUp = 1,
+    /// Moves the hat switch down, combined with the other hat directions pressed.
+    HatDown = 2,
+    /// Moves the hat switch left, combined with the other hat directions pressed.
+    HatLeft = 3,
+    /// Moves the hat switch right, combined with the other hat directions pressed.
+    HatRight = 4,
+    /// Numbered gamepad button 1.
+    B1 = 5,
+    /// Numbered gamepad button 2.
+    B2 = 6,
+    /// Numbered gamepad button 3.
+    B3 = 7,
+    /// Numbered gamepad button 4.
+    B4 = 8,
+    /// Numbered gamepad button 5.
+    B5 = 9,
+    /// Numbered gamepad button 6.
+    B6 = 10,
+    /// Numbered gamepad button 7.
+    B7 = 11,
+    /// Numbered gamepad button 8.
+    B8 = 12,
+    /// Numbered gamepad button 9.
+    B9 = 13,
+    /// Numbered gamepad button 10.
+    B10 = 14,
+    /// Numbered gamepad button 11.
+    B11 = 15,
+    /// Numbered gamepad button 12.
+    B12 = 16,
+    /// Numbered gamepad button 13.
+    B13 = 17,
+    /// Numbered gamepad button 14.
+    B14 = 18,
+    /// Numbered gamepad button 15.
+    B15 = 19,
+    /// Numbered gamepad button 16.
+    B16 = 20,
+}
+impl GamepadButton {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            GamepadButton::Unspecified => "GAMEPAD_BUTTON_UNSPECIFIED",
+            GamepadButton::HatUp => "GAMEPAD_BUTTON_HAT_UP",
+            GamepadButton::HatDown => "GAMEPAD_BUTTON_HAT_DOWN",
+            GamepadButton::HatLeft => "GAMEPAD_BUTTON_HAT_LEFT",
+            GamepadButton::HatRight => "GAMEPAD_BUTTON_HAT_RIGHT",
+            GamepadButton::B1 => "GAMEPAD_BUTTON_B1",
+            GamepadButton::B2 => "GAMEPAD_BUTTON_B2",
+            GamepadButton::B3 => "GAMEPAD_BUTTON_B3",
+            GamepadButton::B4 => "GAMEPAD_BUTTON_B4",
+            GamepadButton::B5 => "GAMEPAD_BUTTON_B5",
+            GamepadButton::B6 => "GAMEPAD_BUTTON_B6",
+            GamepadButton::B7 => "GAMEPAD_BUTTON_B7",
+            GamepadButton::B8 => "GAMEPAD_BUTTON_B8",
+            GamepadButton::B9 => "GAMEPAD_BUTTON_B9",
+            GamepadButton::B10 => "GAMEPAD_BUTTON_B10",
+            GamepadButton::B11 => "GAMEPAD_BUTTON_B11",
+            GamepadButton::B12 => "GAMEPAD_BUTTON_B12",
+            GamepadButton::B13 => "GAMEPAD_BUTTON_B13",
+            GamepadButton::B14 => "GAMEPAD_BUTTON_B14",
+            GamepadButton::B15 => "GAMEPAD_BUTTON_B15",
+            GamepadButton::B16 => "GAMEPAD_BUTTON_B16",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "GAMEPAD_BUTTON_UNSPECIFIED" => Some(Self::Unspecified),
+            "GAMEPAD_BUTTON_HAT_UP" => Some(Self::HatUp),
+            "GAMEPAD_BUTTON_HAT_DOWN" => Some(Self::HatDown),
+            "GAMEPAD_BUTTON_HAT_LEFT" => Some(Self::HatLeft),
+            "GAMEPAD_BUTTON_HAT_RIGHT" => Some(Self::HatRight),
+            "GAMEPAD_BUTTON_B1" => Some(Self::B1),
+            "GAMEPAD_BUTTON_B2" => Some(Self::B2),
+            "GAMEPAD_BUTTON_B3" => Some(Self::B3),
+            "GAMEPAD_BUTTON_B4" => Some(Self::B4),
+            "GAMEPAD_BUTTON_B5" => Some(Self::B5),
+            "GAMEPAD_BUTTON_B6" => Some(Self::B6),
+            "GAMEPAD_BUTTON_B7" => Some(Self::B7),
+            "GAMEPAD_BUTTON_B8" => Some(Self::B8),
+            "GAMEPAD_BUTTON_B9" => Some(Self::B9),
+            "GAMEPAD_BUTTON_B10" => Some(Self::B10),
+            "GAMEPAD_BUTTON_B11" => Some(Self::B11),
+            "GAMEPAD_BUTTON_B12" => Some(Self::B12),
+            "GAMEPAD_BUTTON_B13" => Some(Self::B13),
+            "GAMEPAD_BUTTON_B14" => Some(Self::B14),
+            "GAMEPAD_BUTTON_B15" => Some(Self::B15),
+            "GAMEPAD_BUTTON_B16" => Some(Self::B16),
+            _ => None,
+        }
+    }
+}
 /// Contains all key codes that are compatible with HID.
 ///
 /// NOTE - more codes can be found here:
@@ -689,6 +794,39 @@ impl KeyCode {
         }
     }
 }
+/// Layout of the keyboard report sent to the host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum KeyboardReportMode {
+    /// Default value, uses KEYBOARD_REPORT_MODE_SIX_KEY.
+    Unspecified = 0,
+    /// Boot-style report, with up to 6 key codes pressed at once.
+    SixKey = 1,
+    /// N-key rollover report, with a bitmap of all the key codes pressed.
+    NKey = 2,
+}
+impl KeyboardReportMode {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            KeyboardReportMode::Unspecified => "KEYBOARD_REPORT_MODE_UNSPECIFIED",
+            KeyboardReportMode::SixKey => "KEYBOARD_REPORT_MODE_SIX_KEY",
+            KeyboardReportMode::NKey => "KEYBOARD_REPORT_MODE_N_KEY",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "KEYBOARD_REPORT_MODE_UNSPECIFIED" => Some(Self::Unspecified),
+            "KEYBOARD_REPORT_MODE_SIX_KEY" => Some(Self::SixKey),
+            "KEYBOARD_REPORT_MODE_N_KEY" => Some(Self::NKey),
+            _ => None,
+        }
+    }
+}
 /// Predefined report ids for composite BLE hid report.
 /// The report id of BLE should start from 0x01.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
@@ -704,6 +842,10 @@ pub enum ReportType {
     Media = 3,
     /// Report for the HID system device.
     System = 4,
+    /// Report for the HID gamepad device.
+    Gamepad = 5,
+    /// Report for the HID touch screen digitizer device.
+    Digitizer = 6,
 }
 impl ReportType {
     /// String value of the enum field names used in the ProtoBuf definition.
@@ -717,6 +859,8 @@ impl ReportType {
             ReportType::Mouse => "REPORT_TYPE_MOUSE",
             ReportType::Media => "REPORT_TYPE_MEDIA",
             ReportType::System => "REPORT_TYPE_SYSTEM",
+            ReportType::Gamepad => "REPORT_TYPE_GAMEPAD",
+            ReportType::Digitizer => "REPORT_TYPE_DIGITIZER",
         }
     }
     /// Creates an enum from field names used in the ProtoBuf definition.
@@ -727,6 +871,8 @@ impl ReportType {
             "REPORT_TYPE_MOUSE" => Some(Self::Mouse),
             "REPORT_TYPE_MEDIA" => Some(Self::Media),
             "REPORT_TYPE_SYSTEM" => Some(Self::System),
+            "REPORT_TYPE_GAMEPAD" => Some(Self::Gamepad),
+            "REPORT_TYPE_DIGITIZER" => Some(Self::Digitizer),
             _ => None,
         }
     }
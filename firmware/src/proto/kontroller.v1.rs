@@ -21,6 +21,10 @@ pub enum Button {
     Fn2 = 7,
     /// The third function button.
     Fn3 = 8,
+    /// Virtual button, tapped on each clockwise step of the rotary encoder.
+    EncoderCw = 9,
+    /// Virtual button, tapped on each counter-clockwise step of the rotary encoder.
+    EncoderCcw = 10,
 }
 impl Button {
     /// String value of the enum field names used in the ProtoBuf definition.
@@ -38,6 +42,8 @@ impl Button {
             Button::Fn1 => "BUTTON_FN1",
             Button::Fn2 => "BUTTON_FN2",
             Button::Fn3 => "BUTTON_FN3",
+            Button::EncoderCw => "BUTTON_ENCODER_CW",
+            Button::EncoderCcw => "BUTTON_ENCODER_CCW",
         }
     }
     /// Creates an enum from field names used in the ProtoBuf definition.
@@ -52,10 +58,357 @@ impl Button {
             "BUTTON_FN1" => Some(Self::Fn1),
             "BUTTON_FN2" => Some(Self::Fn2),
             "BUTTON_FN3" => Some(Self::Fn3),
+            "BUTTON_ENCODER_CW" => Some(Self::EncoderCw),
+            "BUTTON_ENCODER_CCW" => Some(Self::EncoderCcw),
             _ => None,
         }
     }
 }
+/// Policy used by the firmware to repeat a key press while its Button
+/// is being held down.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RepeatPolicy {
+    /// The time a Button must be held down before the first repeat.
+    /// Expressed in milliseconds, uses the firmware default when zero.
+    #[prost(uint32, tag = "3")]
+    pub delay_millis: u32,
+    /// The repeat policy. When unset, the firmware does not repeat
+    /// key presses and leaves key repeat to the host.
+    #[prost(oneof = "repeat_policy::Policy", tags = "1, 2")]
+    pub policy: ::core::option::Option<repeat_policy::Policy>,
+}
+/// Nested message and enum types in `RepeatPolicy`.
+pub mod repeat_policy {
+    /// Repeats the key press at a fixed interval.
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Fixed {
+        /// The interval between two repeated key presses.
+        /// Expressed in milliseconds.
+        #[prost(uint32, tag = "1")]
+        pub interval_millis: u32,
+    }
+    /// Repeats the key press at an increasing rate, starting from `min_rate_hz`
+    /// and linearly reaching `max_rate_hz` after `ramp_millis` of repeats.
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Accelerating {
+        /// The initial repeat rate, in key presses per second.
+        #[prost(uint32, tag = "1")]
+        pub min_rate_hz: u32,
+        /// The final repeat rate, in key presses per second.
+        #[prost(uint32, tag = "2")]
+        pub max_rate_hz: u32,
+        /// The time it takes to go from the initial to the final repeat rate.
+        /// Expressed in milliseconds.
+        #[prost(uint32, tag = "3")]
+        pub ramp_millis: u32,
+    }
+    /// The repeat policy. When unset, the firmware does not repeat
+    /// key presses and leaves key repeat to the host.
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Policy {
+        /// Repeats the key press at a fixed interval.
+        #[prost(message, tag = "1")]
+        Fixed(Fixed),
+        /// Repeats the key press at an increasing rate.
+        #[prost(message, tag = "2")]
+        Accelerating(Accelerating),
+    }
+}
+/// Filter rejecting the switch chatter caused by vibrations, such as engine
+/// or road vibrations, applied to the raw Button signal before debouncing.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VibrationFilter {
+    /// The time the Button must be continuously held down before the press
+    /// is accepted. Expressed in milliseconds.
+    #[prost(uint32, tag = "1")]
+    pub min_press_millis: u32,
+    /// Isolated pulses shorter than this, in either direction, are rejected.
+    /// Expressed in milliseconds.
+    #[prost(uint32, tag = "2")]
+    pub min_pulse_millis: u32,
+    /// The maximum number of times per second the filtered signal can change.
+    /// No limit is applied when zero.
+    #[prost(uint32, tag = "3")]
+    pub max_toggle_rate_hz: u32,
+}
+/// Settings of a single Kontroller button.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ButtonSettings {
+    /// The physical Button these settings apply to.
+    #[prost(enumeration = "Button", tag = "1")]
+    pub button: i32,
+    /// How the firmware should repeat the key press while the Button
+    /// is being held down.
+    #[prost(message, optional, tag = "3")]
+    pub repeat: ::core::option::Option<RepeatPolicy>,
+    /// Filter applied to the Button signal to reject vibrations.
+    /// The signal is not filtered when unset.
+    #[prost(message, optional, tag = "4")]
+    pub vibration_filter: ::core::option::Option<VibrationFilter>,
+}
+/// A combination of Buttons, pressed together, that types a character
+/// while the chord text entry mode is enabled.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Chord {
+    /// The Buttons that must be pressed together. The chord is typed
+    /// once all of them have been released.
+    #[prost(enumeration = "Button", repeated, tag = "1")]
+    pub buttons: ::prost::alloc::vec::Vec<i32>,
+    /// The key code typed by the chord.
+    #[prost(enumeration = "super::hid::v1::KeyCode", tag = "2")]
+    pub key_code: i32,
+    /// The modifier key codes typed together with the key code,
+    /// e.g. KEY_CODE_LSHIFT for capital letters.
+    #[prost(enumeration = "super::hid::v1::KeyCode", repeated, tag = "3")]
+    pub modifiers: ::prost::alloc::vec::Vec<i32>,
+}
+/// The layout of the chord text entry mode, toggled with the
+/// SYSTEM_ACTION_TOGGLE_CHORD_MODE system action.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ChordLayout {
+    /// The chords available in the layout.
+    #[prost(message, repeated, tag = "1")]
+    pub chords: ::prost::alloc::vec::Vec<Chord>,
+}
+/// Settings of the rotary encoder of the Kontroller.
+///
+/// Encoder steps are bound in the Keymap through the BUTTON_ENCODER_CW
+/// and BUTTON_ENCODER_CCW virtual Buttons, tapped once per step.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EncoderSettings {
+    /// The number of quadrature pulses between two detents.
+    /// Uses the firmware default when zero.
+    #[prost(uint32, tag = "1")]
+    pub pulses_per_detent: u32,
+    /// Swaps the clockwise and counter-clockwise directions.
+    #[prost(bool, tag = "2")]
+    pub reverse: bool,
+    /// The rotation speed, in detents per second, above which each detent
+    /// counts as multiple steps. Disabled when zero.
+    #[prost(uint32, tag = "3")]
+    pub acceleration_threshold_hz: u32,
+    /// The number of steps each detent counts as, above the acceleration threshold.
+    #[prost(uint32, tag = "4")]
+    pub acceleration_multiplier: u32,
+}
+/// A gesture performed on the Kontroller buttons, such as a combo
+/// (multiple Buttons pressed together) or a long press.
+///
+/// The Buttons of a gesture are only sent to the host once it can no longer
+/// be performed: when released, or held down past the hold time.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Gesture {
+    /// The Buttons that must be pressed together to perform the gesture.
+    #[prost(enumeration = "Button", repeated, tag = "1")]
+    pub buttons: ::prost::alloc::vec::Vec<i32>,
+    /// The time all the Buttons must be held down to perform the gesture.
+    /// Expressed in milliseconds, the gesture is performed as soon as
+    /// all Buttons are pressed when zero.
+    #[prost(uint32, tag = "2")]
+    pub hold_millis: u32,
+}
+/// The identity the Kontroller presents to the host over BLE, which some
+/// apps use to recognize the controllers they support.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Identity {
+    /// The preset providing the values of the fields left unset.
+    #[prost(enumeration = "identity::Preset", tag = "1")]
+    pub preset: i32,
+    /// The device name, advertised and shown by the host. Uses the preset one when empty.
+    #[prost(string, tag = "2")]
+    pub device_name: ::prost::alloc::string::String,
+    /// The manufacturer name. Uses the preset one when empty.
+    #[prost(string, tag = "3")]
+    pub manufacturer: ::prost::alloc::string::String,
+    /// The organization that assigned the vendor id. Uses the preset one when unspecified.
+    #[prost(enumeration = "identity::VendorIdSource", tag = "4")]
+    pub vendor_id_source: i32,
+    /// The 16-bit vendor id. Uses the preset one when zero.
+    #[prost(uint32, tag = "5")]
+    pub vendor_id: u32,
+    /// The 16-bit product id. Uses the preset one when zero.
+    #[prost(uint32, tag = "6")]
+    pub product_id: u32,
+    /// The 16-bit product version, e.g. 0x0210 for 2.1.0. Uses the preset one when zero.
+    #[prost(uint32, tag = "7")]
+    pub product_version: u32,
+    /// The 16-bit BLE appearance, e.g. 0x03C1 for a keyboard. Uses the preset one when zero.
+    #[prost(uint32, tag = "8")]
+    pub appearance: u32,
+}
+/// Nested message and enum types in `Identity`.
+pub mod identity {
+    /// A predefined identity, providing the values of the fields left unset.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Preset {
+        /// Default value, uses PRESET_KONTROLLER.
+        Unspecified = 0,
+        /// The openmoto Kontroller's own identity.
+        Kontroller = 1,
+        /// The DMD CTL 8K remote, as recognized by the DMD2 navigation app.
+        DmdCtl8k = 2,
+        /// An Apple Bluetooth keyboard.
+        AppleKeyboard = 3,
+    }
+    impl Preset {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Preset::Unspecified => "PRESET_UNSPECIFIED",
+                Preset::Kontroller => "PRESET_KONTROLLER",
+                Preset::DmdCtl8k => "PRESET_DMD_CTL_8K",
+                Preset::AppleKeyboard => "PRESET_APPLE_KEYBOARD",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "PRESET_UNSPECIFIED" => Some(Self::Unspecified),
+                "PRESET_KONTROLLER" => Some(Self::Kontroller),
+                "PRESET_DMD_CTL_8K" => Some(Self::DmdCtl8k),
+                "PRESET_APPLE_KEYBOARD" => Some(Self::AppleKeyboard),
+                _ => None,
+            }
+        }
+    }
+    /// The organization that assigned the vendor id of the PnP ID.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum VendorIdSource {
+        /// Default value, uses the source of the preset.
+        Unspecified = 0,
+        /// The vendor id is a Bluetooth SIG company identifier.
+        BluetoothSig = 1,
+        /// The vendor id is a USB Implementer's Forum vendor id.
+        UsbIf = 2,
+    }
+    impl VendorIdSource {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                VendorIdSource::Unspecified => "VENDOR_ID_SOURCE_UNSPECIFIED",
+                VendorIdSource::BluetoothSig => "VENDOR_ID_SOURCE_BLUETOOTH_SIG",
+                VendorIdSource::UsbIf => "VENDOR_ID_SOURCE_USB_IF",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "VENDOR_ID_SOURCE_UNSPECIFIED" => Some(Self::Unspecified),
+                "VENDOR_ID_SOURCE_BLUETOOTH_SIG" => Some(Self::BluetoothSig),
+                "VENDOR_ID_SOURCE_USB_IF" => Some(Self::UsbIf),
+                _ => None,
+            }
+        }
+    }
+}
+/// Calibration of a joystick axis, expressed in raw ADC readings.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AxisCalibration {
+    /// The reading at the minimum deflection of the axis.
+    #[prost(uint32, tag = "1")]
+    pub min: u32,
+    /// The reading with the axis at rest.
+    #[prost(uint32, tag = "2")]
+    pub center: u32,
+    /// The reading at the maximum deflection of the axis.
+    #[prost(uint32, tag = "3")]
+    pub max: u32,
+    /// Inverts the direction of the axis.
+    #[prost(bool, tag = "4")]
+    pub invert: bool,
+}
+/// Settings of the analog thumb joystick of the Kontroller.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct JoystickSettings {
+    /// What the joystick deflection drives.
+    #[prost(enumeration = "joystick_settings::Mode", tag = "1")]
+    pub mode: i32,
+    /// The calibration of the horizontal axis, positive to the right.
+    /// Uses the firmware defaults when unset.
+    #[prost(message, optional, tag = "2")]
+    pub x: ::core::option::Option<AxisCalibration>,
+    /// The calibration of the vertical axis, positive downwards.
+    /// Uses the firmware defaults when unset.
+    #[prost(message, optional, tag = "3")]
+    pub y: ::core::option::Option<AxisCalibration>,
+    /// The deflection around the center that is ignored.
+    /// Expressed in thousandths of the full deflection.
+    #[prost(uint32, tag = "4")]
+    pub deadzone_permille: u32,
+    /// The response curve, from linear (0) to fully cubic (100),
+    /// for finer control around the center.
+    #[prost(uint32, tag = "5")]
+    pub expo_percent: u32,
+    /// The speed at full deflection, in pointer or scroll units per second.
+    /// Uses the firmware default when zero.
+    #[prost(uint32, tag = "6")]
+    pub max_speed: u32,
+    /// The deflection past which a directional pad Button is pressed, in MODE_DPAD.
+    /// Expressed in thousandths of the full deflection, uses the firmware default when zero.
+    #[prost(uint32, tag = "7")]
+    pub dpad_threshold_permille: u32,
+}
+/// Nested message and enum types in `JoystickSettings`.
+pub mod joystick_settings {
+    /// What the joystick deflection drives.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Mode {
+        /// Default value, the joystick is disabled.
+        Unspecified = 0,
+        /// Moves the mouse pointer.
+        Pointer = 1,
+        /// Scrolls the mouse wheel vertically and horizontally.
+        Scroll = 2,
+        /// Emulates the directional pad Buttons.
+        Dpad = 3,
+    }
+    impl Mode {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Mode::Unspecified => "MODE_UNSPECIFIED",
+                Mode::Pointer => "MODE_POINTER",
+                Mode::Scroll => "MODE_SCROLL",
+                Mode::Dpad => "MODE_DPAD",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "MODE_UNSPECIFIED" => Some(Self::Unspecified),
+                "MODE_POINTER" => Some(Self::Pointer),
+                "MODE_SCROLL" => Some(Self::Scroll),
+                "MODE_DPAD" => Some(Self::Dpad),
+                _ => None,
+            }
+        }
+    }
+}
 /// A keymap for the Kontroller, i.e. the list of which HID keycode to apply
 /// to a specific physical button press.
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -67,16 +420,54 @@ pub struct Keymap {
 }
 /// Nested message and enum types in `Keymap`.
 pub mod keymap {
-    /// A keymap entry, i.e. the association between one Button and a KeyCode.
+    /// A keymap entry, i.e. the association between one Button and an action.
     #[allow(clippy::derive_partial_eq_without_eq)]
     #[derive(Clone, PartialEq, ::prost::Message)]
     pub struct Entry {
         /// The physical Button.
         #[prost(enumeration = "super::Button", tag = "1")]
         pub button: i32,
-        /// The key code to apply to the physical Button.
-        #[prost(enumeration = "super::super::hid::v1::KeyCode", tag = "2")]
-        pub key_code: i32,
+        /// The action to perform when the physical Button is pressed.
+        #[prost(oneof = "entry::Action", tags = "2, 3, 4, 5, 6, 7, 8, 9")]
+        pub action: ::core::option::Option<entry::Action>,
+    }
+    /// Nested message and enum types in `Entry`.
+    pub mod entry {
+        /// The action to perform when the physical Button is pressed.
+        #[allow(clippy::derive_partial_eq_without_eq)]
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub enum Action {
+            /// The key code to apply to the physical Button.
+            #[prost(enumeration = "super::super::super::hid::v1::KeyCode", tag = "2")]
+            KeyCode(i32),
+            /// The system action to perform when the physical Button is pressed.
+            #[prost(enumeration = "super::super::SystemAction", tag = "3")]
+            SystemAction(i32),
+            /// A one-shot key code, usually a modifier (e.g. KEY_CODE_LSHIFT), applied
+            /// to the next key press only. Pressing the Button again cancels it.
+            #[prost(enumeration = "super::super::super::hid::v1::KeyCode", tag = "4")]
+            OneShot(i32),
+            /// A key code that stays pressed after the physical Button is released,
+            /// until the Button is pressed again.
+            #[prost(enumeration = "super::super::super::hid::v1::KeyCode", tag = "5")]
+            Latch(i32),
+            /// Scrolls the mouse wheel by this many units, up when positive
+            /// and down when negative. Repeated while the Button is held down.
+            #[prost(sint32, tag = "6")]
+            MouseWheel(i32),
+            /// The gamepad button or hat switch direction to press while the
+            /// physical Button is held down.
+            #[prost(enumeration = "super::super::super::hid::v1::GamepadButton", tag = "7")]
+            GamepadButton(i32),
+            /// Touches the host screen, e.g. to tap an on-screen button
+            /// with no keyboard shortcut.
+            #[prost(message, tag = "8")]
+            Touch(super::super::Touch),
+            /// Performs a two-finger gesture on the host screen, e.g. to
+            /// zoom a map that does not zoom with a single finger.
+            #[prost(message, tag = "9")]
+            MultiTouch(super::super::MultiTouch),
+        }
     }
 }
 /// A Kontroller configuration.
@@ -91,5 +482,294 @@ pub struct Konfiguration {
     /// to a physical Button press.
     #[prost(message, optional, tag = "2")]
     pub keymap: ::core::option::Option<Keymap>,
+    /// Per-button settings, e.g. how the firmware should auto-repeat
+    /// a key press. Buttons with no settings use the default ones.
+    #[prost(message, repeated, tag = "3")]
+    pub button_settings: ::prost::alloc::vec::Vec<ButtonSettings>,
+    /// The input lock mode settings. The lock mode is not available when unset.
+    #[prost(message, optional, tag = "4")]
+    pub lock: ::core::option::Option<LockSettings>,
+    /// The system actions settings, e.g. which gestures trigger them.
+    #[prost(message, optional, tag = "5")]
+    pub system_actions: ::core::option::Option<SystemActionSettings>,
+    /// Additional keymap profiles, that can be switched to with the
+    /// SYSTEM_ACTION_CYCLE_PROFILE system action. The `keymap` is always
+    /// the first profile.
+    #[prost(message, repeated, tag = "6")]
+    pub profiles: ::prost::alloc::vec::Vec<Keymap>,
+    /// The time after which a one-shot key, waiting for the next key press,
+    /// is cancelled. Expressed in milliseconds, uses the firmware default when zero.
+    #[prost(uint32, tag = "7")]
+    pub one_shot_timeout_millis: u32,
+    /// The stuck Button detection settings. When unset, only the Buttons held
+    /// down at boot are considered stuck, however long a Button is held down.
+    #[prost(message, optional, tag = "8")]
+    pub stuck_buttons: ::core::option::Option<StuckButtonSettings>,
+    /// The layout of the chord text entry mode. The mode is not available when unset.
+    #[prost(message, optional, tag = "9")]
+    pub chord_layout: ::core::option::Option<ChordLayout>,
+    /// The rotary encoder settings. Uses the firmware defaults when unset.
+    #[prost(message, optional, tag = "10")]
+    pub encoder: ::core::option::Option<EncoderSettings>,
+    /// The analog joystick settings. The joystick is disabled when unset.
+    #[prost(message, optional, tag = "11")]
+    pub joystick: ::core::option::Option<JoystickSettings>,
+    /// The resistor ladder settings. The ladder is disabled when unset.
+    #[prost(message, optional, tag = "12")]
+    pub ladder: ::core::option::Option<LadderSettings>,
+    /// The layout of the keyboard report sent to the host, which also
+    /// selects the keyboard report descriptor.
+    #[prost(enumeration = "super::hid::v1::KeyboardReportMode", tag = "13")]
+    pub keyboard_report_mode: i32,
+    /// The identity presented to the host. Uses the PRESET_KONTROLLER identity when unset.
+    #[prost(message, optional, tag = "14")]
+    pub identity: ::core::option::Option<Identity>,
+}
+/// A window of raw ADC readings of the resistor ladder, in which
+/// some Buttons are pressed.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LadderWindow {
+    /// The Buttons pressed when the reading is within the window.
+    /// Multiple Buttons are pressed together, for ladders supporting
+    /// simultaneous presses.
+    #[prost(enumeration = "Button", repeated, tag = "1")]
+    pub buttons: ::prost::alloc::vec::Vec<i32>,
+    /// The lowest reading of the window.
+    #[prost(uint32, tag = "2")]
+    pub min: u32,
+    /// The highest reading of the window.
+    #[prost(uint32, tag = "3")]
+    pub max: u32,
+    /// The margin by which the reading can leave the window, once entered,
+    /// before the Buttons are released.
+    #[prost(uint32, tag = "4")]
+    pub hysteresis: u32,
+}
+/// Settings of the resistor ladder, connecting multiple Buttons
+/// to a single ADC line.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LadderSettings {
+    /// The calibration table of the ladder. Readings outside all the
+    /// windows mean no Button is pressed.
+    #[prost(message, repeated, tag = "1")]
+    pub windows: ::prost::alloc::vec::Vec<LadderWindow>,
+    /// The number of consecutive readings within a window needed to enter it,
+    /// so that the readings crossed while the voltage settles are ignored.
+    /// Uses the firmware default when zero.
+    #[prost(uint32, tag = "2")]
+    pub settle_samples: u32,
+}
+/// Settings of the input lock mode, which prevents accidental presses
+/// from being sent to the host.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LockSettings {
+    /// The gesture that locks and unlocks the Kontroller.
+    #[prost(message, optional, tag = "1")]
+    pub toggle: ::core::option::Option<Gesture>,
+    /// Locks the Kontroller automatically after this many minutes
+    /// without any button activity. Disabled when zero.
+    #[prost(uint32, tag = "2")]
+    pub auto_lock_after_minutes: u32,
+}
+/// Settings of the stuck Button detection.
+///
+/// A Button is stuck when it is held down for too long, or already
+/// held down at boot, e.g. because water got into the switch or a wire
+/// shorted. Stuck Buttons are quarantined: they are ignored until released.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StuckButtonSettings {
+    /// The time after which a Button held down is considered stuck.
+    /// Expressed in seconds, uses the firmware default of 60 seconds when zero.
+    #[prost(uint32, tag = "1")]
+    pub max_hold_seconds: u32,
+}
+/// The association between a Gesture and the SystemAction it triggers.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SystemActionBinding {
+    /// The gesture triggering the action.
+    #[prost(message, optional, tag = "1")]
+    pub gesture: ::core::option::Option<Gesture>,
+    /// The action to perform.
+    #[prost(enumeration = "SystemAction", tag = "2")]
+    pub action: i32,
+}
+/// Settings of the system actions of the Kontroller.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SystemActionSettings {
+    /// The gestures triggering system actions, in addition to the keymap entries.
+    #[prost(message, repeated, tag = "1")]
+    pub bindings: ::prost::alloc::vec::Vec<SystemActionBinding>,
+    /// The time available to confirm a dangerous action, by triggering it again.
+    /// Expressed in milliseconds, uses the firmware default when zero.
+    #[prost(uint32, tag = "2")]
+    pub confirmation_timeout_millis: u32,
+}
+/// A firmware-internal action, performed by the Kontroller itself
+/// instead of being sent to the host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum SystemAction {
+    /// Default value, must not be used.
+    Unspecified = 0,
+    /// Disconnects the current host and starts advertising,
+    /// so that a new host can pair with the Kontroller.
+    EnterPairing = 1,
+    /// Removes all the bonded hosts. Requires confirmation.
+    ClearBonds = 2,
+    /// Switches to the next keymap profile.
+    CycleProfile = 3,
+    /// Reboots the Kontroller.
+    Reboot = 4,
+    /// Restores the Kontroller to its factory state, removing all the bonded
+    /// hosts and erasing the state persisted by the firmware, then reboots it.
+    /// Requires confirmation.
+    FactoryReset = 5,
+    /// Shows the battery level on the status LED.
+    BatteryCheck = 6,
+    /// Enters or leaves the chord text entry mode, where combinations
+    /// of Buttons type characters according to the chord layout.
+    ToggleChordMode = 7,
+    /// Dumps the recorded trace of raw Button samples and events to the log,
+    /// so that it can be replayed on the host.
+    DumpTrace = 8,
+}
+impl SystemAction {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            SystemAction::Unspecified => "SYSTEM_ACTION_UNSPECIFIED",
+            SystemAction::EnterPairing => "SYSTEM_ACTION_ENTER_PAIRING",
+            SystemAction::ClearBonds => "SYSTEM_ACTION_CLEAR_BONDS",
+            SystemAction::CycleProfile => "SYSTEM_ACTION_CYCLE_PROFILE",
+            SystemAction::Reboot => "SYSTEM_ACTION_REBOOT",
+            SystemAction::FactoryReset => "SYSTEM_ACTION_FACTORY_RESET",
+            SystemAction::BatteryCheck => "SYSTEM_ACTION_BATTERY_CHECK",
+            SystemAction::ToggleChordMode => "SYSTEM_ACTION_TOGGLE_CHORD_MODE",
+            SystemAction::DumpTrace => "SYSTEM_ACTION_DUMP_TRACE",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "SYSTEM_ACTION_UNSPECIFIED" => Some(Self::Unspecified),
+            "SYSTEM_ACTION_ENTER_PAIRING" => Some(Self::EnterPairing),
+            "SYSTEM_ACTION_CLEAR_BONDS" => Some(Self::ClearBonds),
+            "SYSTEM_ACTION_CYCLE_PROFILE" => Some(Self::CycleProfile),
+            "SYSTEM_ACTION_REBOOT" => Some(Self::Reboot),
+            "SYSTEM_ACTION_FACTORY_RESET" => Some(Self::FactoryReset),
+            "SYSTEM_ACTION_BATTERY_CHECK" => Some(Self::BatteryCheck),
+            "SYSTEM_ACTION_TOGGLE_CHORD_MODE" => Some(Self::ToggleChordMode),
+            "SYSTEM_ACTION_DUMP_TRACE" => Some(Self::DumpTrace),
+            _ => None,
+        }
+    }
+}
+/// A touch performed on the host screen through the digitizer report,
+/// such as a tap, a long tap or a swipe.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Touch {
+    /// Where the finger touches the screen. Uses the centre of the screen when not set.
+    #[prost(message, optional, tag = "1")]
+    pub at: ::core::option::Option<touch::Point>,
+    /// Where the finger leaves the screen, swiping in a straight line
+    /// from the `at` Point. The finger does not move when not set.
+    #[prost(message, optional, tag = "2")]
+    pub to: ::core::option::Option<touch::Point>,
+    /// The time the finger stays on the screen, e.g. 1000 for a long tap.
+    /// Expressed in milliseconds, uses the firmware default of a tap,
+    /// or of a swipe, when zero.
+    #[prost(uint32, tag = "3")]
+    pub duration_millis: u32,
+}
+/// Nested message and enum types in `Touch`.
+pub mod touch {
+    /// A point on the host screen, normalised to its size.
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Point {
+        /// The distance from the left edge, in thousandths of the screen width.
+        #[prost(uint32, tag = "1")]
+        pub x_permille: u32,
+        /// The distance from the top edge, in thousandths of the screen height.
+        #[prost(uint32, tag = "2")]
+        pub y_permille: u32,
+    }
+}
+/// A two-finger gesture performed on the host screen through the digitizer
+/// report, such as a pinch to zoom a map in or out.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MultiTouch {
+    /// The motion of the two fingers.
+    #[prost(enumeration = "multi_touch::Kind", tag = "1")]
+    pub kind: i32,
+    /// The point halfway between the two fingers. Uses the centre
+    /// of the screen when not set.
+    #[prost(message, optional, tag = "2")]
+    pub center: ::core::option::Option<touch::Point>,
+    /// The distance between the two fingers when spread apart, in thousandths
+    /// of the screen width. Pinches move between a quarter of the span and
+    /// the whole span, rotations keep the whole span.
+    /// Uses the firmware default when zero.
+    #[prost(uint32, tag = "3")]
+    pub span_permille: u32,
+    /// The time the fingers stay on the screen.
+    /// Expressed in milliseconds, uses the firmware default when zero.
+    #[prost(uint32, tag = "4")]
+    pub duration_millis: u32,
+}
+/// Nested message and enum types in `MultiTouch`.
+pub mod multi_touch {
+    /// The motion of the two fingers.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Kind {
+        /// Default value, must not be used.
+        Unspecified = 0,
+        /// The fingers move towards each other, usually zooming out.
+        PinchIn = 1,
+        /// The fingers move away from each other, usually zooming in.
+        PinchOut = 2,
+        /// The fingers turn a quarter of a circle clockwise around the centre.
+        RotateClockwise = 3,
+        /// The fingers turn a quarter of a circle counter-clockwise around the centre.
+        RotateCounterClockwise = 4,
+    }
+    impl Kind {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Kind::Unspecified => "KIND_UNSPECIFIED",
+                Kind::PinchIn => "KIND_PINCH_IN",
+                Kind::PinchOut => "KIND_PINCH_OUT",
+                Kind::RotateClockwise => "KIND_ROTATE_CLOCKWISE",
+                Kind::RotateCounterClockwise => "KIND_ROTATE_COUNTER_CLOCKWISE",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "KIND_UNSPECIFIED" => Some(Self::Unspecified),
+                "KIND_PINCH_IN" => Some(Self::PinchIn),
+                "KIND_PINCH_OUT" => Some(Self::PinchOut),
+                "KIND_ROTATE_CLOCKWISE" => Some(Self::RotateClockwise),
+                "KIND_ROTATE_COUNTER_CLOCKWISE" => Some(Self::RotateCounterClockwise),
+                _ => None,
+            }
+        }
+    }
 }
 // @@protoc_insertion_point(module)
@@ -0,0 +1,52 @@
+//! Status updates of the `kontroller`, to be shown to the user through the status LED.
+
+use crate::{
+    hid::HostLeds,
+    proto::kontroller::{
+        hid::v1::KeyCode,
+        v1::{Button, SystemAction},
+    },
+};
+
+/// A status update of the `kontroller`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// The `kontroller` is advertising, waiting for a host to connect.
+    Advertising,
+    /// A host has connected to the `kontroller`.
+    Connected,
+    /// A report has been sent to the connected host.
+    Activity,
+    /// The input lock mode has been enabled: all button presses are ignored,
+    /// except for the unlock gesture.
+    Locked,
+    /// The input lock mode has been disabled.
+    Unlocked,
+    /// A dangerous [`SystemAction`] has been triggered, and must be confirmed
+    /// by triggering it again.
+    ConfirmationRequired(SystemAction),
+    /// A [`SystemAction`] waiting for confirmation has been cancelled,
+    /// either explicitly or because the confirmation timed out.
+    ConfirmationCancelled(SystemAction),
+    /// A [`SystemAction`] is being performed.
+    Action(SystemAction),
+    /// The keymap profile at the given index has been activated.
+    Profile(usize),
+    /// The battery level, in percentage.
+    Battery(u8),
+    /// The [`Button`] is stuck, i.e. held down for too long or at boot,
+    /// and has been quarantined: it is ignored until released.
+    StuckButton(Button),
+    /// The stuck [`Button`] has been released, and is no longer quarantined.
+    ButtonRecovered(Button),
+    /// The chord text entry mode has been enabled.
+    ChordModeEntered,
+    /// The chord text entry mode has been disabled.
+    ChordModeExited,
+    /// A character has been typed with a chord.
+    Character(KeyCode),
+    /// A chord not part of the layout has been pressed.
+    UnknownChord,
+    /// The connected host has set its keyboard LEDs, e.g. Caps Lock.
+    HostLeds(HostLeds),
+}
@@ -0,0 +1,57 @@
+//! Execution of the [`SystemAction`]s triggered on the `kontroller`.
+
+use embassy_time::{Duration, Timer};
+use esp_idf_svc::{hal::reset, nvs::EspDefaultNvsPartition};
+use firmware::{proto::kontroller::v1::SystemAction, status::Status};
+use futures::{
+    channel::mpsc::{Receiver, Sender},
+    SinkExt, StreamExt,
+};
+use log::info;
+
+use crate::ble;
+
+/// Time given to the status LED to show the feedback of an action,
+/// before rebooting the `kontroller`.
+const REBOOT_DELAY: Duration = Duration::from_secs(2);
+
+/// Performs the [`SystemAction`]s received from the channel, notifying
+/// the corresponding [`Status`] updates.
+///
+/// The NVS partition is erased of the state persisted by the firmware on a factory reset.
+pub async fn run(
+    mut rx: Receiver<SystemAction>,
+    mut status: Sender<Status>,
+    nvs: EspDefaultNvsPartition,
+) -> anyhow::Result<()> {
+    while let Some(action) = rx.next().await {
+        info!("performing system action: {}", action.as_str_name());
+
+        status.send(Status::Action(action)).await?;
+
+        match action {
+            SystemAction::EnterPairing => ble::Server::enter_pairing_mode()?,
+            SystemAction::ClearBonds => ble::Server::clear_bonds()?,
+            SystemAction::Reboot => reboot().await,
+            SystemAction::FactoryReset => {
+                ble::Server::factory_reset(nvs.clone())?;
+                reboot().await;
+            }
+            SystemAction::BatteryCheck => {
+                status.send(Status::Battery(ble::BATTERY_LEVEL)).await?;
+            }
+            // Profiles, chord mode and traces are handled by the Kontroller itself.
+            SystemAction::CycleProfile
+            | SystemAction::ToggleChordMode
+            | SystemAction::DumpTrace
+            | SystemAction::Unspecified => {}
+        }
+    }
+
+    Ok(())
+}
+
+async fn reboot() {
+    Timer::after(REBOOT_DELAY).await;
+    reset::restart();
+}
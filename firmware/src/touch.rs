@@ -0,0 +1,448 @@
+//! Touches performed on the host screen through the digitizer report, such as
+//! taps, swipes or two-finger pinches.
+
+use embassy_time::{Duration, Instant};
+
+use crate::{
+    hid::{Contact, DigitizerReport},
+    proto::kontroller::v1::{
+        multi_touch::Kind,
+        touch::Point,
+        MultiTouch, Touch,
+    },
+};
+
+/// Default time the finger stays on the screen for a tap, short enough
+/// for hosts not to consider it a long tap.
+pub const DEFAULT_TAP_DURATION: Duration = Duration::from_millis(50);
+
+/// Default time taken by a swipe, from touching the screen to leaving it.
+pub const DEFAULT_SWIPE_DURATION: Duration = Duration::from_millis(300);
+
+/// Default time taken by a two-finger gesture, from touching the screen to leaving it.
+pub const DEFAULT_MULTI_TOUCH_DURATION: Duration = Duration::from_millis(400);
+
+/// Default distance between the two fingers of a [`MultiTouch`] when spread apart,
+/// in thousandths of the screen width.
+pub const DEFAULT_SPAN: u32 = 400;
+
+/// Time between two positions of the fingers reported while they move.
+const MOTION_STEP: Duration = Duration::from_millis(10);
+
+/// Angle the fingers turn by during a rotation, in thousandths of a degree.
+const ROTATION: i64 = 90_000;
+
+/// The motion of the fingers of a [`Stroke`], in [`DigitizerReport`] coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Motion {
+    /// A single finger moving in a straight line, or standing still.
+    Line { from: (i64, i64), to: (i64, i64) },
+    /// Two fingers moving along the horizontal axis through the centre.
+    Pinch {
+        center: (i64, i64),
+        from: i64,
+        to: i64,
+    },
+    /// Two fingers turning around the centre, clockwise when the angle is positive.
+    Rotate {
+        center: (i64, i64),
+        span: i64,
+        angle: i64,
+    },
+}
+
+impl Motion {
+    /// Returns the position of each finger, once `progress` out of `total` of the
+    /// [`Motion`] has been performed.
+    fn positions(&self, progress: i64, total: i64) -> Vec<(i64, i64)> {
+        let lerp = |from: i64, to: i64| from + (to - from) * progress / total;
+
+        match *self {
+            Self::Line { from, to } => vec![(lerp(from.0, to.0), lerp(from.1, to.1))],
+            Self::Pinch { center, from, to } => {
+                let half = lerp(from, to) / 2;
+                vec![(center.0 - half, center.1), (center.0 + half, center.1)]
+            }
+            Self::Rotate {
+                center,
+                span,
+                angle,
+            } => {
+                let angle = lerp(0, angle);
+                let (dx, dy) = (
+                    span / 2 * sin_permille(90_000 - angle.abs()) / 1000,
+                    span / 2 * sin_permille(angle.abs()) * angle.signum() / 1000,
+                );
+                vec![(center.0 - dx, center.1 - dy), (center.0 + dx, center.1 + dy)]
+            }
+        }
+    }
+}
+
+/// A [`Touch`] or [`MultiTouch`] being performed on the host screen, from when it started.
+///
+/// The fingers touch the screen at the start of the [`Stroke`], move towards their
+/// end positions, and leave the screen once its duration has elapsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stroke {
+    motion: Motion,
+    duration: Duration,
+    started: Instant,
+}
+
+impl Stroke {
+    /// Starts performing the [`Touch`] at the specified point in time.
+    ///
+    /// Coordinates past the edges of the screen are clamped to them, and the
+    /// finger touches the centre of the screen when the [`Touch`] does not say where.
+    #[must_use]
+    pub fn new(touch: &Touch, now: Instant) -> Self {
+        let max = i64::from(DigitizerReport::MAX_COORDINATE);
+        let from = touch.at.as_ref().map_or((max / 2, max / 2), coordinates);
+        let to = touch.to.as_ref().map_or(from, coordinates);
+
+        let duration = match touch.duration_millis {
+            0 if touch.to.is_some() => DEFAULT_SWIPE_DURATION,
+            0 => DEFAULT_TAP_DURATION,
+            millis => Duration::from_millis(millis.into()),
+        };
+
+        Self {
+            motion: Motion::Line { from, to },
+            duration,
+            started: now,
+        }
+    }
+
+    /// Starts performing the [`MultiTouch`] at the specified point in time.
+    ///
+    /// Coordinates past the edges of the screen are clamped to them, which
+    /// distorts the gesture when performed close to an edge.
+    ///
+    /// Returns `None` if the [`MultiTouch`] does not specify its kind.
+    #[must_use]
+    pub fn multi_touch(multi_touch: &MultiTouch, now: Instant) -> Option<Self> {
+        let max = i64::from(DigitizerReport::MAX_COORDINATE);
+        let center = multi_touch
+            .center
+            .as_ref()
+            .map_or((max / 2, max / 2), coordinates);
+        let span = match multi_touch.span_permille {
+            0 => DEFAULT_SPAN,
+            span => span,
+        };
+        let span = i64::from(span).min(max);
+
+        let motion = match multi_touch.kind() {
+            Kind::Unspecified => return None,
+            Kind::PinchIn => Motion::Pinch {
+                center,
+                from: span,
+                to: span / 4,
+            },
+            Kind::PinchOut => Motion::Pinch {
+                center,
+                from: span / 4,
+                to: span,
+            },
+            Kind::RotateClockwise => Motion::Rotate {
+                center,
+                span,
+                angle: ROTATION,
+            },
+            Kind::RotateCounterClockwise => Motion::Rotate {
+                center,
+                span,
+                angle: -ROTATION,
+            },
+        };
+
+        Some(Self {
+            motion,
+            duration: match multi_touch.duration_millis {
+                0 => DEFAULT_MULTI_TOUCH_DURATION,
+                millis => Duration::from_millis(millis.into()),
+            },
+            started: now,
+        })
+    }
+
+    /// Returns the [`DigitizerReport`] of the fingers at the specified point in time,
+    /// no longer touching the screen once the [`Stroke`] is over.
+    #[must_use]
+    pub fn report(&self, now: Instant) -> DigitizerReport {
+        let tip = now.saturating_duration_since(self.started) < self.duration;
+        let mut report = self.lift(now);
+        for contact in report.contacts.iter_mut().flatten() {
+            contact.tip = tip;
+        }
+
+        report
+    }
+
+    /// Returns the [`DigitizerReport`] of the fingers leaving the screen at the
+    /// specified point in time, used to interrupt the [`Stroke`] before its end.
+    ///
+    /// While moving, the positions only change every [`MOTION_STEP`],
+    /// so that the host is not flooded with reports.
+    #[must_use]
+    pub fn lift(&self, now: Instant) -> DigitizerReport {
+        let elapsed = now.saturating_duration_since(self.started).min(self.duration);
+
+        let step = MOTION_STEP.as_ticks();
+        let elapsed = match elapsed.as_ticks() {
+            ticks if elapsed == self.duration => ticks,
+            ticks => ticks / step * step,
+        };
+
+        let progress = i64::try_from(elapsed).unwrap_or_default();
+        let total = i64::try_from(self.duration.as_ticks()).unwrap_or(i64::MAX).max(1);
+
+        let mut report = DigitizerReport::default();
+        for (slot, (x, y)) in report
+            .contacts
+            .iter_mut()
+            .zip(self.motion.positions(progress, total))
+        {
+            let clamp = |value: i64| {
+                u16::try_from(value.clamp(0, DigitizerReport::MAX_COORDINATE.into()))
+                    .unwrap_or_default()
+            };
+
+            *slot = Some(Contact {
+                tip: false,
+                x: clamp(x),
+                y: clamp(y),
+            });
+        }
+
+        report
+    }
+}
+
+/// Returns the coordinates of the [`Point`] in the [`DigitizerReport`] logical range.
+fn coordinates(point: &Point) -> (i64, i64) {
+    let max = i64::from(DigitizerReport::MAX_COORDINATE);
+
+    (
+        i64::from(point.x_permille).min(max),
+        i64::from(point.y_permille).min(max),
+    )
+}
+
+/// Approximates the sine of the angle, between 0 and 180 degrees, in thousandths.
+///
+/// Uses the Bhaskara I approximation, whose error is within 2 thousandths,
+/// as the positions are integers anyway.
+fn sin_permille(millidegrees: i64) -> i64 {
+    let product = millidegrees * (180_000 - millidegrees);
+    4000 * product / (40_500_000_000 - product)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x_permille: u32, y_permille: u32) -> Point {
+        Point {
+            x_permille,
+            y_permille,
+        }
+    }
+
+    fn at(millis: u64) -> Instant {
+        Instant::from_millis(millis)
+    }
+
+    /// Returns the single finger of the report, checking that no other is reported.
+    fn finger(report: DigitizerReport) -> Contact {
+        let [Some(contact), None] = report.contacts else {
+            panic!("expected a single finger, got {report:?}");
+        };
+        contact
+    }
+
+    fn contact(tip: bool, x: u16, y: u16) -> Contact {
+        Contact { tip, x, y }
+    }
+
+    #[test]
+    fn tap_touches_the_point_then_lifts() {
+        let touch = Touch {
+            at: Some(point(250, 750)),
+            ..Touch::default()
+        };
+        let stroke = Stroke::new(&touch, at(100));
+
+        assert_eq!(finger(stroke.report(at(100))), contact(true, 250, 750));
+        assert_eq!(finger(stroke.report(at(149))), contact(true, 250, 750));
+        assert_eq!(finger(stroke.report(at(150))), contact(false, 250, 750));
+    }
+
+    #[test]
+    fn long_tap_stays_for_its_duration() {
+        let touch = Touch {
+            at: Some(point(250, 750)),
+            duration_millis: 800,
+            ..Touch::default()
+        };
+        let stroke = Stroke::new(&touch, at(0));
+
+        assert_eq!(finger(stroke.report(at(799))), contact(true, 250, 750));
+        assert_eq!(finger(stroke.report(at(800))), contact(false, 250, 750));
+    }
+
+    #[test]
+    fn tap_without_point_touches_the_centre() {
+        let stroke = Stroke::new(&Touch::default(), at(0));
+
+        assert_eq!(finger(stroke.report(at(0))), contact(true, 500, 500));
+    }
+
+    #[test]
+    fn swipe_moves_by_motion_steps_and_lifts_at_the_end() {
+        let touch = Touch {
+            at: Some(point(100, 100)),
+            to: Some(point(300, 500)),
+            duration_millis: 100,
+        };
+        let stroke = Stroke::new(&touch, at(0));
+
+        assert_eq!(finger(stroke.report(at(0))), contact(true, 100, 100));
+        // Positions only change every MOTION_STEP.
+        assert_eq!(finger(stroke.report(at(9))), contact(true, 100, 100));
+        assert_eq!(finger(stroke.report(at(10))), contact(true, 120, 140));
+        assert_eq!(finger(stroke.report(at(19))), contact(true, 120, 140));
+        assert_eq!(finger(stroke.report(at(99))), contact(true, 280, 460));
+        // The finger leaves the screen at the end position.
+        assert_eq!(finger(stroke.report(at(100))), contact(false, 300, 500));
+        assert_eq!(finger(stroke.report(at(500))), contact(false, 300, 500));
+    }
+
+    #[test]
+    fn interrupted_swipe_lifts_where_the_finger_is() {
+        let touch = Touch {
+            at: Some(point(100, 100)),
+            to: Some(point(300, 500)),
+            duration_millis: 100,
+        };
+        let stroke = Stroke::new(&touch, at(0));
+
+        assert_eq!(finger(stroke.lift(at(25))), contact(false, 140, 180));
+    }
+
+    #[test]
+    fn swipe_defaults_to_the_swipe_duration() {
+        let touch = Touch {
+            at: Some(point(0, 0)),
+            to: Some(point(1000, 0)),
+            duration_millis: 0,
+        };
+        let stroke = Stroke::new(&touch, at(0));
+        let end = at(DEFAULT_SWIPE_DURATION.as_millis());
+
+        assert!(finger(stroke.report(end - MOTION_STEP)).tip);
+        assert_eq!(finger(stroke.report(end)), contact(false, 1000, 0));
+    }
+
+    fn multi_touch(kind: Kind) -> MultiTouch {
+        MultiTouch {
+            kind: kind.into(),
+            ..MultiTouch::default()
+        }
+    }
+
+    /// Returns the position of both fingers of the report, checking their tip state.
+    fn fingers(report: DigitizerReport, tip: bool) -> [(u16, u16); 2] {
+        let [Some(first), Some(second)] = report.contacts else {
+            panic!("expected two fingers, got {report:?}");
+        };
+        assert_eq!((first.tip, second.tip), (tip, tip), "{report:?}");
+        assert_eq!(report.count(), 2);
+
+        [(first.x, first.y), (second.x, second.y)]
+    }
+
+    #[test]
+    fn multi_touch_without_kind_is_not_performed() {
+        assert_eq!(
+            Stroke::multi_touch(&multi_touch(Kind::Unspecified), at(0)),
+            None
+        );
+    }
+
+    #[test]
+    fn pinch_in_moves_the_fingers_together() {
+        let stroke = Stroke::multi_touch(&multi_touch(Kind::PinchIn), at(0)).unwrap();
+        let end = at(DEFAULT_MULTI_TOUCH_DURATION.as_millis());
+
+        assert_eq!(fingers(stroke.report(at(0)), true), [(300, 500), (700, 500)]);
+        assert_eq!(fingers(stroke.report(end), false), [(450, 500), (550, 500)]);
+    }
+
+    #[test]
+    fn pinch_out_moves_the_fingers_apart() {
+        let stroke = Stroke::multi_touch(&multi_touch(Kind::PinchOut), at(0)).unwrap();
+        let end = at(DEFAULT_MULTI_TOUCH_DURATION.as_millis());
+
+        assert_eq!(fingers(stroke.report(at(0)), true), [(450, 500), (550, 500)]);
+        assert_eq!(fingers(stroke.report(end), false), [(300, 500), (700, 500)]);
+    }
+
+    #[test]
+    fn rotations_turn_the_fingers_a_quarter_of_a_circle() {
+        let clockwise = Stroke::multi_touch(&multi_touch(Kind::RotateClockwise), at(0)).unwrap();
+        let counter_clockwise =
+            Stroke::multi_touch(&multi_touch(Kind::RotateCounterClockwise), at(0)).unwrap();
+        let (half, end) = (at(200), at(400));
+
+        // The first finger starts on the left, then turns up, towards negative y,
+        // when clockwise, and down when counter-clockwise.
+        for stroke in [clockwise, counter_clockwise] {
+            assert_eq!(fingers(stroke.report(at(0)), true), [(300, 500), (700, 500)]);
+        }
+        assert_eq!(fingers(clockwise.report(half), true), [(359, 359), (641, 641)]);
+        assert_eq!(fingers(clockwise.report(end), false), [(500, 300), (500, 700)]);
+        assert_eq!(
+            fingers(counter_clockwise.report(half), true),
+            [(359, 641), (641, 359)]
+        );
+        assert_eq!(
+            fingers(counter_clockwise.report(end), false),
+            [(500, 700), (500, 300)]
+        );
+    }
+
+    #[test]
+    fn multi_touch_span_is_clamped_to_the_screen() {
+        let stroke = Stroke::multi_touch(
+            &MultiTouch {
+                kind: Kind::PinchIn.into(),
+                center: Some(point(100, 500)),
+                span_permille: 5000,
+                duration_millis: 100,
+            },
+            at(0),
+        )
+        .unwrap();
+
+        // The span is at most the screen width, and the fingers stay on the screen.
+        assert_eq!(fingers(stroke.report(at(0)), true), [(0, 500), (600, 500)]);
+        assert_eq!(fingers(stroke.report(at(100)), false), [(0, 500), (225, 500)]);
+    }
+
+    #[test]
+    fn interrupted_multi_touch_lifts_both_fingers() {
+        let stroke = Stroke::multi_touch(&multi_touch(Kind::PinchOut), at(0)).unwrap();
+
+        assert_eq!(fingers(stroke.lift(at(205)), false), [(375, 500), (625, 500)]);
+    }
+
+    #[test]
+    fn sine_approximation() {
+        assert_eq!(sin_permille(0), 0);
+        assert_eq!(sin_permille(30_000), 500);
+        assert_eq!(sin_permille(90_000), 1000);
+        assert_eq!(sin_permille(180_000), 0);
+    }
+}
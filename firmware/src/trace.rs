@@ -0,0 +1,232 @@
+//! Recording of raw [`Button`] samples and [`key::Event`]s, so that issues
+//! seen in the field can be replayed on the host.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Write,
+};
+
+use embassy_time::{Duration, Instant};
+
+use crate::{key, proto::kontroller::v1::Button};
+
+/// Default number of [`Record`]s kept by the [`Recorder`].
+pub const DEFAULT_CAPACITY: usize = 512;
+
+/// Time added before the first [`Record`] of a dump, so that the replay does not
+/// start with the [`Button`]s pressed at boot, which would be considered stuck.
+pub const DUMP_LEAD_TIME: Duration = Duration::from_millis(10);
+
+/// Number of lines of a [`Dump`] logged per poll, so that logging a whole trace
+/// to the serial console does not stall the polling of the buttons.
+pub const DUMP_LINES_PER_POLL: usize = 8;
+
+/// An entry of the trace kept by the [`Recorder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Record {
+    /// The raw level of a [`Button`] pin changed.
+    Sample {
+        /// When the level has been sampled.
+        at: Instant,
+        /// The [`Button`] sampled.
+        button: Button,
+        /// Whether the pin reads low, i.e. the [`Button`] is pressed.
+        low: bool,
+    },
+    /// A [`key::Event`] has been produced by a [`Button`].
+    Event {
+        /// The [`Button`] producing the event.
+        button: Button,
+        /// The event produced.
+        event: key::Event,
+    },
+}
+
+impl Record {
+    /// Returns the point in time the [`Record`] refers to.
+    #[must_use]
+    pub fn at(&self) -> Instant {
+        match self {
+            Self::Sample { at, .. } => *at,
+            Self::Event { event, .. } => event.at(),
+        }
+    }
+}
+
+/// Records the raw [`Button`] level changes and [`key::Event`]s in a ring buffer,
+/// overwriting the oldest [`Record`]s once full.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    capacity: usize,
+    records: VecDeque<Record>,
+    /// Levels of the pins read as low before the oldest record.
+    initial: BTreeMap<Button, bool>,
+    /// Levels of the pins read as low in the last sample.
+    levels: BTreeMap<Button, bool>,
+    dropped: usize,
+}
+
+impl Recorder {
+    /// Builds an empty [`Recorder`], keeping up to the specified number of [`Record`]s.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            records: VecDeque::with_capacity(capacity),
+            initial: BTreeMap::new(),
+            levels: BTreeMap::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Records the raw level of the [`Button`] pin, if it changed since the last sample.
+    pub fn sample(&mut self, button: Button, low: bool, at: Instant) {
+        if self.levels.get(&button).copied().unwrap_or_default() == low {
+            return;
+        }
+
+        self.levels.insert(button, low);
+        self.push(Record::Sample { at, button, low });
+    }
+
+    /// Records the [`key::Event`] produced by the [`Button`].
+    pub fn event(&mut self, button: Button, event: key::Event) {
+        self.push(Record::Event { button, event });
+    }
+
+    /// Returns the [`Record`]s kept, from the oldest to the newest.
+    pub fn records(&self) -> impl Iterator<Item = &Record> {
+        self.records.iter()
+    }
+
+    /// Dumps the trace as a simulator script, where the samples are button transitions
+    /// and the events are comments, starting after [`DUMP_LEAD_TIME`].
+    ///
+    /// [`Button`]s already pressed before the oldest [`Record`] are pressed at the start.
+    #[must_use]
+    pub fn dump(&self) -> String {
+        let mut dump = format!(
+            "# kontroller trace: {} records, {} dropped\n",
+            self.records.len(),
+            self.dropped
+        );
+
+        let Some(start) = self.records.front().map(Record::at) else {
+            return dump;
+        };
+
+        let time = |at: Instant| (at.max(start) - start + DUMP_LEAD_TIME).as_micros();
+
+        for (button, _) in self.initial.iter().filter(|(_, low)| **low) {
+            let at = (DUMP_LEAD_TIME / 2).as_micros();
+            let _ = writeln!(dump, "t={at}us {} down", name(*button));
+        }
+
+        for record in &self.records {
+            let _ = match record {
+                Record::Sample { at, button, low } => writeln!(
+                    dump,
+                    "t={}us {} {}",
+                    time(*at),
+                    name(*button),
+                    if *low { "down" } else { "up" }
+                ),
+                Record::Event { button, event } => writeln!(
+                    dump,
+                    "# t={}us event {} {}",
+                    time(event.at()),
+                    name(*button),
+                    match event {
+                        key::Event::Down { .. } => "down",
+                        key::Event::Up { .. } => "up",
+                        key::Event::Repeat { .. } => "repeat",
+                    }
+                ),
+            };
+        }
+
+        dump
+    }
+
+    fn push(&mut self, record: Record) {
+        if self.records.len() == self.capacity {
+            if let Some(Record::Sample { button, low, .. }) = self.records.pop_front() {
+                self.initial.insert(button, low);
+            }
+            self.dropped += 1;
+        }
+
+        self.records.push_back(record);
+    }
+}
+
+/// A dump of the [`Recorder`] being logged, handed out one line at a time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dump {
+    text: String,
+    /// Offset of the next line to hand out.
+    offset: usize,
+}
+
+impl Dump {
+    /// Returns the next line of the [`Dump`], if any is left.
+    pub fn next_line(&mut self) -> Option<&str> {
+        let rest = &self.text[self.offset..];
+        if rest.is_empty() {
+            return None;
+        }
+
+        let len = rest.find('\n').map_or(rest.len(), |end| end + 1);
+        self.offset += len;
+
+        Some(rest[..len].trim_end_matches('\n'))
+    }
+
+    /// Returns `true` once all the lines have been handed out.
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        self.offset == self.text.len()
+    }
+}
+
+impl From<&Recorder> for Dump {
+    fn from(recorder: &Recorder) -> Self {
+        Self {
+            text: recorder.dump(),
+            offset: 0,
+        }
+    }
+}
+
+/// Returns the name of the [`Button`] as accepted by the simulator, e.g. `UP`.
+fn name(button: Button) -> &'static str {
+    button.as_str_name().trim_start_matches("BUTTON_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_hands_out_the_lines_in_order() {
+        let mut recorder = Recorder::new(DEFAULT_CAPACITY);
+        recorder.sample(Button::Up, true, Instant::from_millis(1));
+        recorder.sample(Button::Up, false, Instant::from_millis(5));
+
+        let mut dump = Dump::from(&recorder);
+        let mut lines = Vec::new();
+        while let Some(line) = dump.next_line() {
+            lines.push(line.to_owned());
+        }
+
+        assert!(dump.is_done());
+        assert_eq!(
+            lines,
+            [
+                "# kontroller trace: 2 records, 0 dropped",
+                "t=10000us UP down",
+                "t=14000us UP up",
+            ]
+        );
+    }
+}
@@ -0,0 +1,153 @@
+//! Parser of the HID report descriptor, used to check that every report
+//! sent by the firmware is laid out the way the descriptor declares it.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, ensure};
+
+/// Item tags of the report descriptor, as `(type, tag)` pairs.
+mod item {
+    pub const INPUT: (u8, u8) = (0, 0x8);
+    pub const LOGICAL_MINIMUM: (u8, u8) = (1, 0x1);
+    pub const REPORT_SIZE: (u8, u8) = (1, 0x7);
+    pub const REPORT_ID: (u8, u8) = (1, 0x8);
+    pub const REPORT_COUNT: (u8, u8) = (1, 0x9);
+}
+
+/// Prefix of a long item, which the parser skips.
+const LONG_ITEM: u8 = 0xFE;
+
+/// A field of an input report, as declared by an `Input` main item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    /// Offset of the first value in the report, in bits.
+    pub offset: usize,
+    /// Size of each value, in bits.
+    pub size: usize,
+    /// Number of values in the field.
+    pub count: usize,
+    /// Whether the values are signed, i.e. the logical minimum is negative.
+    pub signed: bool,
+}
+
+/// The input report layouts declared by a report descriptor, by report id.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Descriptor {
+    inputs: BTreeMap<u8, Vec<Field>>,
+}
+
+impl Descriptor {
+    /// Parses the short items of the report descriptor, keeping track of the
+    /// global state needed to lay out the input reports.
+    pub fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut descriptor = Self::default();
+        let mut offsets: BTreeMap<u8, usize> = BTreeMap::new();
+        let (mut report_id, mut report_size, mut report_count) = (0, 0, 0);
+        let mut signed = false;
+
+        let mut rest = bytes;
+        while let Some((&prefix, tail)) = rest.split_first() {
+            if prefix == LONG_ITEM {
+                let size = usize::from(*tail.first().ok_or_else(|| anyhow!("truncated item"))?);
+                rest = tail
+                    .get(2 + size..)
+                    .ok_or_else(|| anyhow!("truncated long item"))?;
+                continue;
+            }
+
+            let size = match prefix & 0b11 {
+                3 => 4,
+                size => usize::from(size),
+            };
+            let data = tail
+                .get(..size)
+                .ok_or_else(|| anyhow!("truncated item {prefix:#04x}"))?;
+            rest = &tail[size..];
+
+            match ((prefix >> 2) & 0b11, prefix >> 4) {
+                item::LOGICAL_MINIMUM => signed = value(data, true) < 0,
+                item::REPORT_SIZE => report_size = usize::try_from(value(data, false))?,
+                item::REPORT_COUNT => report_count = usize::try_from(value(data, false))?,
+                item::REPORT_ID => {
+                    report_id = u8::try_from(value(data, false))?;
+                    ensure!(report_id != 0, "report id 0 is reserved");
+                }
+                item::INPUT => {
+                    let offset = offsets.entry(report_id).or_default();
+                    descriptor.inputs.entry(report_id).or_default().push(Field {
+                        offset: *offset,
+                        size: report_size,
+                        count: report_count,
+                        signed,
+                    });
+                    *offset += report_size * report_count;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(descriptor)
+    }
+
+    /// Returns the size of the input report with the given id, in bytes,
+    /// or `None` if the descriptor declares no such report.
+    pub fn input_size(&self, report_id: u8) -> Option<usize> {
+        let fields = self.inputs.get(&report_id)?;
+        let bits: usize = fields.iter().map(|field| field.size * field.count).sum();
+
+        Some(bits.div_ceil(8))
+    }
+
+    /// Decodes every value of the input report with the given id, in the
+    /// order they are declared, constant fields included.
+    ///
+    /// The report bytes must not include the report id.
+    pub fn decode(&self, report_id: u8, bytes: &[u8]) -> anyhow::Result<Vec<i64>> {
+        let Some(fields) = self.inputs.get(&report_id) else {
+            bail!("no input report with id {report_id:#04x} in the descriptor");
+        };
+
+        let size = self.input_size(report_id).unwrap_or_default();
+        ensure!(
+            bytes.len() == size,
+            "input report {report_id:#04x} is {} bytes long, the descriptor declares {size}",
+            bytes.len()
+        );
+
+        Ok(fields
+            .iter()
+            .flat_map(|field| {
+                (0..field.count).map(move |index| {
+                    read_bits(
+                        bytes,
+                        field.offset + index * field.size,
+                        field.size,
+                        field.signed,
+                    )
+                })
+            })
+            .collect())
+    }
+}
+
+/// Returns the little-endian value of the item data, sign-extended if requested.
+fn value(data: &[u8], signed: bool) -> i64 {
+    read_bits(data, 0, data.len() * 8, signed)
+}
+
+/// Reads `size` bits starting at the bit `offset`, least significant bit first.
+fn read_bits(bytes: &[u8], offset: usize, size: usize, signed: bool) -> i64 {
+    let mut value: i64 = 0;
+    for bit in 0..size {
+        let index = offset + bit;
+        if bytes[index / 8] & (1 << (index % 8)) != 0 {
+            value |= 1 << bit;
+        }
+    }
+
+    if signed && size > 0 && size < 64 && value & (1 << (size - 1)) != 0 {
+        value -= 1 << size;
+    }
+
+    value
+}
@@ -0,0 +1,212 @@
+//! Virtual I2C GPIO expanders, answering the firmware like the actual chips.
+
+use std::{cell::RefCell, rc::Rc};
+
+use firmware::{
+    expander::{self, mcp23017, Chip},
+    key,
+};
+
+use crate::pin::VirtualPin;
+
+/// Number of registers of the MCP23017, with `IOCON.BANK = 0` addressing.
+const MCP23017_REGISTERS: usize = 0x16;
+
+/// Error returned by the [`VirtualExpander`] when not addressed by a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Nack;
+
+/// A virtual I2C GPIO expander, whose pins are shorted to ground by [`VirtualPin`] switches.
+///
+/// The expander simulates the registers of the [`Chip`] as seen over the bus,
+/// including its pull-up resistors and interrupt output.
+#[derive(Debug, Clone)]
+pub struct VirtualExpander {
+    inner: Rc<RefCell<Inner>>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    chip: Chip,
+    address: u8,
+    switches: Vec<VirtualPin>,
+    /// Registers of the MCP23017, or the output latch of the PCF8574.
+    registers: [u8; MCP23017_REGISTERS],
+    /// Register accessed by the next read or write of the MCP23017.
+    pointer: u8,
+    /// Pin levels captured by the last read, clearing the interrupt.
+    captured: u16,
+}
+
+impl VirtualExpander {
+    /// Builds a [`VirtualExpander`] of the given [`Chip`], at the address,
+    /// with the registers in their power-on state.
+    pub fn new(chip: Chip, address: u8) -> Self {
+        let mut registers = [0; MCP23017_REGISTERS];
+        match chip {
+            // All pins are inputs at power-on, without pull-ups.
+            Chip::Mcp23017 => {
+                registers[usize::from(mcp23017::IODIRA)] = 0xFF;
+                registers[usize::from(mcp23017::IODIRA) + 1] = 0xFF;
+            }
+            // All pins are weakly pulled up at power-on.
+            Chip::Pcf8574 => registers[0] = 0xFF,
+        }
+
+        let mut inner = Inner {
+            chip,
+            address,
+            switches: (0..chip.pins()).map(|_| VirtualPin::default()).collect(),
+            registers,
+            pointer: 0,
+            captured: 0,
+        };
+        inner.captured = inner.levels();
+
+        Self {
+            inner: Rc::new(RefCell::new(inner)),
+        }
+    }
+
+    /// Returns the switch connected to the pin at the specified index.
+    pub fn switch(&self, index: usize) -> VirtualPin {
+        self.inner.borrow().switches[index].clone()
+    }
+
+    /// Returns the active-low interrupt output of the expander.
+    pub fn interrupt(&self) -> VirtualInterrupt {
+        VirtualInterrupt {
+            inner: Rc::clone(&self.inner),
+        }
+    }
+}
+
+impl Inner {
+    fn register(&self, register: u8) -> u16 {
+        let index = usize::from(register);
+        u16::from_le_bytes([self.registers[index], self.registers[index + 1]])
+    }
+
+    /// Returns the bitmask of the pins at high level.
+    fn levels(&self) -> u16 {
+        let pulled_up = match self.chip {
+            Chip::Mcp23017 => self.register(mcp23017::GPPUA),
+            Chip::Pcf8574 => u16::from(self.registers[0]),
+        };
+
+        self.switches
+            .iter()
+            .enumerate()
+            .filter(|(_, switch)| !key::Pin::is_low(*switch))
+            .fold(0, |levels, (index, _)| levels | (1 << index))
+            & pulled_up
+    }
+
+    /// Returns the bitmask of the pins raising an interrupt on change.
+    fn interrupt_enabled(&self) -> u16 {
+        match self.chip {
+            Chip::Mcp23017 => self.register(mcp23017::GPINTENA),
+            Chip::Pcf8574 => 0xFF,
+        }
+    }
+
+    fn check_address(&self, address: u8) -> Result<(), Nack> {
+        if address == self.address {
+            Ok(())
+        } else {
+            Err(Nack)
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        match self.chip {
+            Chip::Mcp23017 => {
+                let Some((pointer, values)) = bytes.split_first() else {
+                    return;
+                };
+
+                self.pointer = *pointer;
+                for value in values {
+                    self.registers[usize::from(self.pointer) % MCP23017_REGISTERS] = *value;
+                    self.pointer = self.pointer.wrapping_add(1);
+                }
+            }
+            Chip::Pcf8574 => {
+                if let Some(latch) = bytes.last() {
+                    self.registers[0] = *latch;
+                }
+            }
+        }
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) {
+        let levels = self.levels();
+
+        match self.chip {
+            Chip::Mcp23017 => {
+                for byte in buffer {
+                    let register = usize::from(self.pointer) % MCP23017_REGISTERS;
+                    *byte = if register == usize::from(mcp23017::GPIOA) {
+                        self.captured = levels;
+                        levels.to_le_bytes()[0]
+                    } else if register == usize::from(mcp23017::GPIOA) + 1 {
+                        self.captured = levels;
+                        levels.to_le_bytes()[1]
+                    } else {
+                        self.registers[register]
+                    };
+                    self.pointer = self.pointer.wrapping_add(1);
+                }
+            }
+            Chip::Pcf8574 => {
+                self.captured = levels;
+                buffer.fill(levels.to_le_bytes()[0]);
+            }
+        }
+    }
+}
+
+impl expander::Bus for VirtualExpander {
+    type Error = Nack;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        let mut inner = self.inner.borrow_mut();
+        inner.check_address(address)?;
+        inner.write(bytes);
+        Ok(())
+    }
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let mut inner = self.inner.borrow_mut();
+        inner.check_address(address)?;
+        inner.read(buffer);
+        Ok(())
+    }
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        let mut inner = self.inner.borrow_mut();
+        inner.check_address(address)?;
+        inner.write(bytes);
+        inner.read(buffer);
+        Ok(())
+    }
+}
+
+/// The interrupt output of a [`VirtualExpander`], pulled low when the level
+/// of an enabled pin changed since the last read.
+#[derive(Debug, Clone)]
+pub struct VirtualInterrupt {
+    inner: Rc<RefCell<Inner>>,
+}
+
+impl key::Pin for VirtualInterrupt {
+    fn is_low(&self) -> bool {
+        let inner = self.inner.borrow();
+        (inner.levels() ^ inner.captured) & inner.interrupt_enabled() != 0
+    }
+}
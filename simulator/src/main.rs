@@ -0,0 +1,695 @@
+//! Host-side simulator for the openmoto `kontroller`.
+//!
+//! Runs the firmware [`Kontroller`] logic against virtual pins and a virtual clock,
+//! driven by a scripted button timeline, and prints the resulting HID reports.
+
+#![allow(clippy::multiple_crate_versions)]
+
+use std::{collections::HashMap, fs, io::Read};
+
+use anyhow::{anyhow, bail, Context};
+use embassy_time::{Duration, Instant};
+use firmware::{
+    encoder::{self, Encoder},
+    expander::{self, Expander},
+    hid,
+    joystick::{self, Joystick},
+    key::{self, Key},
+    kontroller::{self, Kontroller, Output},
+    ladder::Ladder,
+    matrix::{self, Matrix},
+    proto::kontroller::{
+        hid::v1::KeyCode,
+        v1::{Button, Konfiguration, LadderSettings, SystemAction},
+    },
+    status::Status,
+    trace,
+};
+use prost::Message;
+
+mod descriptor;
+mod i2c;
+mod pin;
+mod replay;
+mod script;
+
+use descriptor::Descriptor;
+use i2c::VirtualExpander;
+use pin::{ChannelDriver, Driver, VirtualChannel, VirtualMatrix, VirtualPin};
+use script::{Input, Level, Script};
+
+/// All the [`Button`]s available on a `kontroller`.
+const BUTTONS: [Button; 8] = [
+    Button::Up,
+    Button::Down,
+    Button::Left,
+    Button::Right,
+    Button::Enter,
+    Button::Fn1,
+    Button::Fn2,
+    Button::Fn3,
+];
+
+/// Number of columns of the simulated key matrix: the [`BUTTONS`] are laid out
+/// in row-major order.
+const MATRIX_COLUMNS: usize = 4;
+
+/// Reading of the simulated resistor ladder with no button pressed,
+/// pulled up to the full scale of a 12-bit ADC.
+const LADDER_IDLE_READING: u16 = 4095;
+
+/// Number of records kept when recording the simulation, large enough
+/// for the trace not to wrap around.
+const REPLAY_CAPACITY: usize = 1 << 16;
+
+/// Pins the [`Button`]s are connected to.
+type Pins = Vec<(Button, Box<dyn key::Pin>)>;
+
+/// Time the simulation keeps running for after the last scripted transition, by default.
+const DEFAULT_SETTLE_TIME: Duration = Duration::from_millis(50);
+
+/// Output format of the simulated HID reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+struct Args {
+    format: Format,
+    konfiguration: Konfiguration,
+    matrix: Option<matrix::Diodes>,
+    expander: Option<expander::Chip>,
+    settle: Duration,
+    script: Script,
+    /// Whether to print the recorded trace at the end of the simulation.
+    record: bool,
+    /// Events expected when replaying a recorded trace.
+    replay: Option<Vec<replay::Expected>>,
+    /// Whether to send the reports like the firmware does once the host
+    /// selects the boot protocol mode.
+    boot_protocol: bool,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let mut format = Format::Text;
+        let mut konfiguration = None;
+        let mut matrix = None;
+        let mut expander = None;
+        let mut record = false;
+        let mut boot_protocol = false;
+        let mut replay = None;
+        let mut settle = DEFAULT_SETTLE_TIME;
+        let mut script = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--json" => format = Format::Json,
+                "--konfiguration" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow!("missing path for --konfiguration"))?;
+                    let bytes = fs::read(&path)
+                        .with_context(|| format!("failed to read konfiguration at '{path}'"))?;
+
+                    konfiguration =
+                        Some(Konfiguration::decode(bytes.as_slice()).with_context(|| {
+                            format!("failed to decode konfiguration at '{path}'")
+                        })?);
+                }
+                "--matrix" => {
+                    let diodes = args
+                        .next()
+                        .ok_or_else(|| anyhow!("missing diodes for --matrix"))?;
+                    matrix = Some(match diodes.as_str() {
+                        "col2row" => matrix::Diodes::ColumnToRow,
+                        "row2col" => matrix::Diodes::RowToColumn,
+                        "none" => matrix::Diodes::None,
+                        _ => bail!("unknown diodes '{diodes}', use 'col2row', 'row2col' or 'none'"),
+                    });
+                }
+                "--expander" => {
+                    let chip = args
+                        .next()
+                        .ok_or_else(|| anyhow!("missing chip for --expander"))?;
+                    expander = Some(match chip.as_str() {
+                        "mcp23017" => expander::Chip::Mcp23017,
+                        "pcf8574" => expander::Chip::Pcf8574,
+                        _ => bail!("unknown chip '{chip}', use 'mcp23017' or 'pcf8574'"),
+                    });
+                }
+                "--record" => record = true,
+                "--boot-protocol" => boot_protocol = true,
+                "--replay" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow!("missing path for --replay"))?;
+                    let trace: replay::Trace = fs::read_to_string(&path)
+                        .with_context(|| format!("failed to read trace at '{path}'"))?
+                        .parse()
+                        .with_context(|| format!("failed to parse trace at '{path}'"))?;
+
+                    script = Some(trace.script);
+                    replay = Some(trace.events);
+                }
+                "--settle" => {
+                    let time = args
+                        .next()
+                        .ok_or_else(|| anyhow!("missing time for --settle"))?;
+                    settle = script::parse_duration(&time)?;
+                }
+                "-" => {
+                    let mut buf = String::new();
+                    std::io::stdin().read_to_string(&mut buf)?;
+                    script = Some(buf.parse()?);
+                }
+                _ if arg.starts_with("--") => bail!("unknown option '{arg}'"),
+                _ => script = Some(arg.parse()?),
+            }
+        }
+
+        if matrix.is_some() && expander.is_some() {
+            bail!("--matrix and --expander cannot be used together");
+        }
+
+        Ok(Self {
+            format,
+            konfiguration: konfiguration.unwrap_or_else(kontroller::default_konfiguration),
+            matrix,
+            expander,
+            settle,
+            script: script
+                .ok_or_else(|| anyhow!("missing script, use '-' to read it from stdin"))?,
+            record,
+            replay,
+            boot_protocol,
+        })
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse(std::env::args().skip(1))?;
+
+    let poll_interval = Duration::from_micros(args.konfiguration.buttons_poll_interval_micros);
+    if poll_interval == Duration::MIN {
+        bail!("buttons poll interval must be greater than zero");
+    }
+
+    // The boot keyboard report has the same layout as the 6-key rollover keyboard report.
+    let descriptor = if args.boot_protocol {
+        Descriptor::parse(hid::KEYBOARD_DESCRIPTOR)
+    } else {
+        Descriptor::parse(&hid::report_descriptor(
+            &kontroller::report_types(&args.konfiguration),
+            args.konfiguration.keyboard_report_mode(),
+        ))
+    }
+    .context("invalid descriptor")?;
+
+    let mut drivers = HashMap::<Input, Driver>::new();
+    let mut channels = HashMap::new();
+    let (mut pins, scanner) = wire_buttons(args.matrix, args.expander, &mut drivers)?;
+    let ladder = args
+        .konfiguration
+        .ladder
+        .as_ref()
+        .map(|settings| wire_ladder(settings, &mut pins, &mut drivers, &mut channels));
+
+    let mut kontroller = Kontroller::new(
+        pins.into_iter()
+            .map(|(button, pin)| (button, Key::new(pin, key::Config::default()))),
+        args.konfiguration,
+    );
+    if let Some(scanner) = scanner {
+        kontroller.add_scanner(scanner);
+    }
+    if let Some(ladder) = ladder {
+        kontroller.add_scanner(ladder);
+    }
+    if args.record || args.replay.is_some() {
+        kontroller.set_recorder(trace::Recorder::new(REPLAY_CAPACITY));
+    }
+
+    let (a, b) = (VirtualPin::default(), VirtualPin::default());
+    drivers.insert(Input::EncoderA, a.driver());
+    drivers.insert(Input::EncoderB, b.driver());
+    kontroller.set_encoder(Encoder::new(
+        Box::new(a),
+        Box::new(b),
+        encoder::Config::default(),
+    ));
+
+    let (x, y) = (
+        VirtualChannel::new(joystick::DEFAULT_CALIBRATION.center),
+        VirtualChannel::new(joystick::DEFAULT_CALIBRATION.center),
+    );
+    channels.insert(Input::JoystickX, x.driver());
+    channels.insert(Input::JoystickY, y.driver());
+    kontroller.set_joystick(Joystick::new(x, y, joystick::Config::default()));
+
+    let start = Instant::from_ticks(0);
+    let end = start + args.script.duration() + args.settle;
+    let mut steps = args.script.steps.iter().peekable();
+    let mut now = start;
+
+    while now <= end {
+        while let Some(step) = steps.next_if(|step| start + step.at <= now) {
+            if let Level::Analog(reading) = step.level {
+                channels
+                    .get(&step.input)
+                    .ok_or_else(|| anyhow!("{:?} has no channel to drive", step.input))?
+                    .set(reading);
+                continue;
+            }
+
+            let driver = drivers
+                .get(&step.input)
+                .ok_or_else(|| anyhow!("{:?} has no pin to drive", step.input))?;
+            match step.level {
+                Level::Down => driver.set_low(),
+                Level::Up | Level::Analog(_) => driver.set_high(),
+            }
+        }
+
+        for output in kontroller.poll(now) {
+            match output {
+                Output::Report(report) => {
+                    let report = if args.boot_protocol {
+                        // Only the boot keyboard report is sent in the boot protocol mode.
+                        let Some(boot) = report.to_boot_keyboard() else {
+                            continue;
+                        };
+                        hid::Report::Keyboard(boot)
+                    } else {
+                        report
+                    };
+
+                    check_report(&descriptor, &report)?;
+                    print_report(args.format, now - start, &report);
+                }
+                Output::Status(status) => print_status(args.format, now - start, status),
+                Output::Action(action) => print_action(args.format, now - start, action),
+            }
+        }
+
+        now += poll_interval;
+    }
+
+    if let Some(recorder) = kontroller.recorder().filter(|_| args.record) {
+        print!("{}", recorder.dump());
+    }
+
+    match &args.replay {
+        Some(expected) => check_replay(expected, &kontroller, start, poll_interval),
+        None => Ok(()),
+    }
+}
+
+/// Checks that the replay produced the same events recorded in the trace, allowing
+/// for the different sampling times of the firmware and the simulation.
+fn check_replay(
+    expected: &[replay::Expected],
+    kontroller: &Kontroller<Box<dyn key::Pin>>,
+    start: Instant,
+    poll_interval: Duration,
+) -> anyhow::Result<()> {
+    let records = kontroller
+        .recorder()
+        .ok_or_else(|| anyhow!("replay is not being recorded"))?
+        .records();
+
+    let mismatches = replay::compare(expected, records, start, poll_interval * 2);
+    for mismatch in &mismatches {
+        eprintln!("replay mismatch: {mismatch}");
+    }
+
+    if !mismatches.is_empty() {
+        bail!(
+            "replay differs from the trace: {} mismatches",
+            mismatches.len()
+        );
+    }
+
+    eprintln!("replay matches the trace: {} events", expected.len());
+    Ok(())
+}
+
+/// Connects the [`BUTTONS`] to pins, either one pin per button, or through a simulated
+/// key matrix or I2C GPIO expander, returning the source to scan before reading them.
+fn wire_buttons(
+    matrix: Option<matrix::Diodes>,
+    expander: Option<expander::Chip>,
+    drivers: &mut HashMap<Input, Driver>,
+) -> anyhow::Result<(Pins, Option<Box<dyn key::Scan>>)> {
+    let mut pins = Pins::new();
+
+    match (matrix, expander) {
+        (None, None) => {
+            for button in BUTTONS {
+                let pin = VirtualPin::default();
+                drivers.insert(Input::Button(button), pin.driver());
+                pins.push((button, Box::new(pin)));
+            }
+
+            Ok((pins, None))
+        }
+        (Some(diodes), _) => {
+            let rows = BUTTONS.len().div_ceil(MATRIX_COLUMNS);
+            let virtual_matrix = VirtualMatrix::new(rows, MATRIX_COLUMNS, diodes);
+            let matrix = Matrix::new(
+                virtual_matrix.drive_lines(),
+                virtual_matrix.sense_lines(),
+                diodes,
+            );
+
+            for (index, button) in BUTTONS.into_iter().enumerate() {
+                let (row, column) = (index / MATRIX_COLUMNS, index % MATRIX_COLUMNS);
+                let cell = matrix
+                    .cell(row, column)
+                    .ok_or_else(|| anyhow!("{button:?} is outside of the matrix"))?;
+
+                drivers.insert(
+                    Input::Button(button),
+                    virtual_matrix.switch(row, column).driver(),
+                );
+                pins.push((button, Box::new(cell)));
+            }
+
+            Ok((pins, Some(Box::new(matrix))))
+        }
+        (None, Some(chip)) => {
+            let virtual_expander = VirtualExpander::new(chip, expander::DEFAULT_ADDRESS);
+            let expander = Expander::new(virtual_expander.clone(), chip, expander::DEFAULT_ADDRESS)
+                .with_interrupt(virtual_expander.interrupt());
+
+            for (index, button) in BUTTONS.into_iter().enumerate() {
+                let pin = expander
+                    .pin(index)
+                    .ok_or_else(|| anyhow!("{button:?} is outside of the expander"))?;
+
+                drivers.insert(
+                    Input::Button(button),
+                    virtual_expander.switch(index).driver(),
+                );
+                pins.push((button, Box::new(pin)));
+            }
+
+            Ok((pins, Some(Box::new(expander))))
+        }
+    }
+}
+
+/// Moves the buttons on the resistor ladder calibrated in the [`LadderSettings`]
+/// from their own pins to the [`Ladder`], driven by the script through its channel.
+fn wire_ladder(
+    settings: &LadderSettings,
+    pins: &mut Pins,
+    drivers: &mut HashMap<Input, Driver>,
+    channels: &mut HashMap<Input, ChannelDriver>,
+) -> Ladder {
+    let channel = VirtualChannel::new(LADDER_IDLE_READING);
+    channels.insert(Input::Ladder, channel.driver());
+
+    let ladder = Ladder::new(channel, settings);
+    for (button, pin) in pins {
+        if let Some(ladder_pin) = ladder.pin(*button) {
+            drivers.remove(&Input::Button(*button));
+            *pin = Box::new(ladder_pin);
+        }
+    }
+
+    ladder
+}
+
+/// Checks that the serialized report matches the layout declared for its report id
+/// by the [`hid::report_descriptor`], value by value.
+fn check_report(descriptor: &Descriptor, report: &hid::Report) -> anyhow::Result<()> {
+    let bits = |bitmask: u8| (0..8).map(move |bit| i64::from(bitmask >> bit & 1));
+
+    let expected: Vec<i64> = match report {
+        hid::Report::Keyboard(keyboard) => bits(keyboard.modifier)
+            .chain([i64::from(keyboard.reserved)])
+            .chain(keyboard.keycodes.iter().copied().map(i64::from))
+            .collect(),
+        hid::Report::NkroKeyboard(keyboard) => bits(keyboard.modifier)
+            .chain(keyboard.keys.iter().flat_map(|keys| bits(*keys)))
+            .collect(),
+        hid::Report::Mouse(mouse) => bits(mouse.buttons)
+            .chain([mouse.x, mouse.y, mouse.wheel, mouse.pan].map(i64::from))
+            .collect(),
+        hid::Report::Media(media) => vec![i64::from(media.usage_id)],
+        hid::Report::System(system) => vec![i64::from(system.usage_id)],
+        hid::Report::Gamepad(gamepad) => [i64::from(gamepad.hat), 0]
+            .into_iter()
+            .chain(gamepad.buttons.to_le_bytes().into_iter().flat_map(bits))
+            .collect(),
+        hid::Report::Digitizer(digitizer) => (0..)
+            .zip(&digitizer.contacts)
+            .flat_map(|(id, contact)| match contact {
+                Some(contact) => [i64::from(contact.tip)]
+                    .into_iter()
+                    .chain([0; 7])
+                    .chain([id, contact.x.into(), contact.y.into()])
+                    .collect(),
+                None => vec![0; 11],
+            })
+            .chain([i64::from(digitizer.count())])
+            .collect(),
+    };
+
+    let report_id = report.report_type() as u8;
+    let decoded = descriptor.decode(report_id, &report.to_bytes())?;
+    if decoded != expected {
+        bail!("{report:?} decodes as {decoded:?} with the descriptor, expected {expected:?}");
+    }
+
+    Ok(())
+}
+
+fn print_report(format: Format, at: Duration, report: &hid::Report) {
+    let bytes: Vec<String> = report
+        .to_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+
+    let (modifier, keycodes): (u8, Vec<u8>) = match report {
+        hid::Report::Keyboard(keyboard) => (keyboard.modifier, keyboard.keycodes.to_vec()),
+        hid::Report::NkroKeyboard(keyboard) => (
+            keyboard.modifier,
+            (0..=u8::MAX)
+                .filter(|code| keyboard.is_pressed(*code))
+                .collect(),
+        ),
+        hid::Report::Mouse(mouse) => return print_mouse_report(format, at, *mouse, &bytes),
+        hid::Report::Gamepad(gamepad) => return print_gamepad_report(format, at, *gamepad, &bytes),
+        hid::Report::Digitizer(digitizer) => {
+            return print_digitizer_report(format, at, *digitizer, &bytes)
+        }
+        hid::Report::Media(_) | hid::Report::System(_) => {
+            return print_control_report(format, at, report, &bytes)
+        }
+    };
+
+    let keycodes = keycodes
+        .iter()
+        .filter(|code| **code != KeyCode::Unspecified as u8)
+        .map(|code| {
+            KeyCode::try_from(i32::from(*code)).map_or_else(
+                |_| format!("{code:#04x}"),
+                |code| code.as_str_name().to_owned(),
+            )
+        });
+
+    match format {
+        Format::Text => println!(
+            "t={}.{:03}ms keyboard modifier={:#04x} keycodes=[{}]",
+            at.as_millis(),
+            at.as_micros() % 1000,
+            modifier,
+            keycodes
+                .map(|name| name.trim_start_matches("KEY_CODE_").to_owned())
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        Format::Json => println!(
+            "{}",
+            serde_json::json!({
+                "t_us": at.as_micros(),
+                "report": "keyboard",
+                "modifier": modifier,
+                "keycodes": keycodes.collect::<Vec<_>>(),
+                "bytes": bytes.concat(),
+            })
+        ),
+    }
+}
+
+fn print_mouse_report(format: Format, at: Duration, report: hid::MouseReport, bytes: &[String]) {
+    match format {
+        Format::Text => println!(
+            "t={}.{:03}ms mouse buttons={:#04x} x={} y={} wheel={} pan={}",
+            at.as_millis(),
+            at.as_micros() % 1000,
+            report.buttons,
+            report.x,
+            report.y,
+            report.wheel,
+            report.pan,
+        ),
+        Format::Json => println!(
+            "{}",
+            serde_json::json!({
+                "t_us": at.as_micros(),
+                "report": "mouse",
+                "buttons": report.buttons,
+                "x": report.x,
+                "y": report.y,
+                "wheel": report.wheel,
+                "pan": report.pan,
+                "bytes": bytes.concat(),
+            })
+        ),
+    }
+}
+
+fn print_gamepad_report(
+    format: Format,
+    at: Duration,
+    report: hid::GamepadReport,
+    bytes: &[String],
+) {
+    let hat = (report.hat != hid::GamepadReport::HAT_CENTERED).then_some(report.hat);
+
+    match format {
+        Format::Text => println!(
+            "t={}.{:03}ms gamepad hat={} buttons={:#06x}",
+            at.as_millis(),
+            at.as_micros() % 1000,
+            hat.map_or_else(
+                || "centered".to_owned(),
+                |hat| (u16::from(hat) * 45).to_string()
+            ),
+            report.buttons,
+        ),
+        Format::Json => println!(
+            "{}",
+            serde_json::json!({
+                "t_us": at.as_micros(),
+                "report": "gamepad",
+                "hat_degrees": hat.map(|hat| u16::from(hat) * 45),
+                "buttons": report.buttons,
+                "bytes": bytes.concat(),
+            })
+        ),
+    }
+}
+
+fn print_digitizer_report(
+    format: Format,
+    at: Duration,
+    report: hid::DigitizerReport,
+    bytes: &[String],
+) {
+    let contacts = (0..)
+        .zip(&report.contacts)
+        .filter_map(|(id, contact)| Some((id, (*contact)?)));
+
+    match format {
+        Format::Text => println!(
+            "t={}.{:03}ms digitizer contacts=[{}]",
+            at.as_millis(),
+            at.as_micros() % 1000,
+            contacts
+                .map(|(id, contact)| format!(
+                    "{id}:{} x={} y={}",
+                    if contact.tip { "touch" } else { "lift" },
+                    contact.x,
+                    contact.y,
+                ))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        Format::Json => println!(
+            "{}",
+            serde_json::json!({
+                "t_us": at.as_micros(),
+                "report": "digitizer",
+                "contacts": contacts
+                    .map(|(id, contact)| serde_json::json!({
+                        "id": id,
+                        "tip": contact.tip,
+                        "x": contact.x,
+                        "y": contact.y,
+                    }))
+                    .collect::<Vec<_>>(),
+                "bytes": bytes.concat(),
+            })
+        ),
+    }
+}
+
+fn print_control_report(format: Format, at: Duration, report: &hid::Report, bytes: &[String]) {
+    let (name, usage_id) = match report {
+        hid::Report::Media(media) => ("media", media.usage_id),
+        hid::Report::System(system) => ("system", system.usage_id.into()),
+        hid::Report::Keyboard(_)
+        | hid::Report::NkroKeyboard(_)
+        | hid::Report::Mouse(_)
+        | hid::Report::Gamepad(_)
+        | hid::Report::Digitizer(_) => return,
+    };
+
+    match format {
+        Format::Text => println!(
+            "t={}.{:03}ms {name} usage_id={usage_id:#06x}",
+            at.as_millis(),
+            at.as_micros() % 1000,
+        ),
+        Format::Json => println!(
+            "{}",
+            serde_json::json!({
+                "t_us": at.as_micros(),
+                "report": name,
+                "usage_id": usage_id,
+                "bytes": bytes.concat(),
+            })
+        ),
+    }
+}
+
+fn print_status(format: Format, at: Duration, status: Status) {
+    match format {
+        Format::Text => println!(
+            "t={}.{:03}ms status {status:?}",
+            at.as_millis(),
+            at.as_micros() % 1000,
+        ),
+        Format::Json => println!(
+            "{}",
+            serde_json::json!({
+                "t_us": at.as_micros(),
+                "status": format!("{status:?}"),
+            })
+        ),
+    }
+}
+
+fn print_action(format: Format, at: Duration, action: SystemAction) {
+    match format {
+        Format::Text => println!(
+            "t={}.{:03}ms action {}",
+            at.as_millis(),
+            at.as_micros() % 1000,
+            action.as_str_name().trim_start_matches("SYSTEM_ACTION_"),
+        ),
+        Format::Json => println!(
+            "{}",
+            serde_json::json!({
+                "t_us": at.as_micros(),
+                "action": action.as_str_name(),
+            })
+        ),
+    }
+}
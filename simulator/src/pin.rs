@@ -0,0 +1,231 @@
+//! Virtual pins, driven by the simulation instead of the hardware.
+
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use firmware::{analog, key, matrix};
+
+/// A virtual [`key::Pin`], whose level is controlled by a [`Driver`].
+#[derive(Debug, Clone, Default)]
+pub struct VirtualPin {
+    low: Rc<Cell<bool>>,
+}
+
+impl VirtualPin {
+    /// Returns a [`Driver`] that can be used to change the level of this pin.
+    pub fn driver(&self) -> Driver {
+        Driver {
+            low: Rc::clone(&self.low),
+        }
+    }
+}
+
+impl key::Pin for VirtualPin {
+    fn is_low(&self) -> bool {
+        self.low.get()
+    }
+}
+
+/// Drives the level of a [`VirtualPin`].
+#[derive(Debug, Clone)]
+pub struct Driver {
+    low: Rc<Cell<bool>>,
+}
+
+impl Driver {
+    /// Pulls the [`VirtualPin`] low, i.e. simulates a key press.
+    pub fn set_low(&self) {
+        self.low.set(true);
+    }
+
+    /// Pulls the [`VirtualPin`] high, i.e. simulates a key release.
+    pub fn set_high(&self) {
+        self.low.set(false);
+    }
+}
+
+/// A virtual [`analog::Channel`], whose reading is controlled by a [`ChannelDriver`].
+#[derive(Debug, Clone)]
+pub struct VirtualChannel {
+    reading: Rc<Cell<u16>>,
+}
+
+impl VirtualChannel {
+    /// Builds a [`VirtualChannel`] with the specified initial reading.
+    pub fn new(reading: u16) -> Self {
+        Self {
+            reading: Rc::new(Cell::new(reading)),
+        }
+    }
+
+    /// Returns a [`ChannelDriver`] that can be used to change the reading of this channel.
+    pub fn driver(&self) -> ChannelDriver {
+        ChannelDriver {
+            reading: Rc::clone(&self.reading),
+        }
+    }
+}
+
+impl analog::Channel for VirtualChannel {
+    fn read(&mut self) -> u16 {
+        self.reading.get()
+    }
+}
+
+/// Drives the reading of a [`VirtualChannel`].
+#[derive(Debug, Clone)]
+pub struct ChannelDriver {
+    reading: Rc<Cell<u16>>,
+}
+
+impl ChannelDriver {
+    /// Sets the raw ADC reading of the [`VirtualChannel`].
+    pub fn set(&self, reading: u16) {
+        self.reading.set(reading);
+    }
+}
+
+/// A virtual key matrix, whose switches are [`VirtualPin`]s closed when pulled low.
+///
+/// The matrix exposes its driven lines as [`VirtualLine`]s and its sensed lines
+/// as [`VirtualSense`] pins, simulating the current flowing through the closed switches,
+/// including the ghosting of matrices without diodes.
+#[derive(Debug, Clone)]
+pub struct VirtualMatrix {
+    inner: Rc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    rows: usize,
+    columns: usize,
+    diodes: matrix::Diodes,
+    /// Switches of the matrix, in row-major order.
+    switches: Vec<VirtualPin>,
+    /// Driven lines currently pulled low.
+    driven: RefCell<Vec<bool>>,
+}
+
+impl VirtualMatrix {
+    /// Builds a [`VirtualMatrix`] with the specified size and [`matrix::Diodes`] orientation.
+    pub fn new(rows: usize, columns: usize, diodes: matrix::Diodes) -> Self {
+        let driven = match diodes {
+            matrix::Diodes::ColumnToRow | matrix::Diodes::None => rows,
+            matrix::Diodes::RowToColumn => columns,
+        };
+
+        Self {
+            inner: Rc::new(Inner {
+                rows,
+                columns,
+                diodes,
+                switches: (0..rows * columns).map(|_| VirtualPin::default()).collect(),
+                driven: RefCell::new(vec![false; driven]),
+            }),
+        }
+    }
+
+    /// Returns the switch at the specified row and column.
+    pub fn switch(&self, row: usize, column: usize) -> &VirtualPin {
+        &self.inner.switches[row * self.inner.columns + column]
+    }
+
+    /// Returns the lines driven by the [`matrix::Matrix`] scanner.
+    pub fn drive_lines(&self) -> Vec<VirtualLine> {
+        (0..self.inner.driven.borrow().len())
+            .map(|index| VirtualLine {
+                inner: Rc::clone(&self.inner),
+                index,
+            })
+            .collect()
+    }
+
+    /// Returns the lines sensed by the [`matrix::Matrix`] scanner.
+    pub fn sense_lines(&self) -> Vec<VirtualSense> {
+        let sensed = match self.inner.diodes {
+            matrix::Diodes::ColumnToRow | matrix::Diodes::None => self.inner.columns,
+            matrix::Diodes::RowToColumn => self.inner.rows,
+        };
+
+        (0..sensed)
+            .map(|index| VirtualSense {
+                inner: Rc::clone(&self.inner),
+                index,
+            })
+            .collect()
+    }
+}
+
+impl Inner {
+    fn closed(&self, row: usize, column: usize) -> bool {
+        key::Pin::is_low(&self.switches[row * self.columns + column])
+    }
+}
+
+/// A driven line of a [`VirtualMatrix`].
+#[derive(Debug, Clone)]
+pub struct VirtualLine {
+    inner: Rc<Inner>,
+    index: usize,
+}
+
+impl matrix::Line for VirtualLine {
+    fn set_low(&mut self) {
+        self.inner.driven.borrow_mut()[self.index] = true;
+    }
+
+    fn set_high(&mut self) {
+        self.inner.driven.borrow_mut()[self.index] = false;
+    }
+}
+
+/// A sensed line of a [`VirtualMatrix`], pulled up unless connected to a driven line.
+#[derive(Debug, Clone)]
+pub struct VirtualSense {
+    inner: Rc<Inner>,
+    index: usize,
+}
+
+impl key::Pin for VirtualSense {
+    fn is_low(&self) -> bool {
+        let inner = &self.inner;
+        let driven = inner.driven.borrow();
+
+        match inner.diodes {
+            matrix::Diodes::ColumnToRow => {
+                (0..inner.rows).any(|row| driven[row] && inner.closed(row, self.index))
+            }
+            matrix::Diodes::RowToColumn => {
+                (0..inner.columns).any(|column| driven[column] && inner.closed(self.index, column))
+            }
+            matrix::Diodes::None => {
+                // Without diodes, current flows in both directions through the closed
+                // switches: look for any path from the sensed column to a driven row.
+                let mut columns = vec![false; inner.columns];
+                let mut rows = vec![false; inner.rows];
+                let mut queue = vec![self.index];
+                columns[self.index] = true;
+
+                while let Some(column) = queue.pop() {
+                    for row in (0..inner.rows).filter(|row| inner.closed(*row, column)) {
+                        if driven[row] {
+                            return true;
+                        }
+                        if !std::mem::replace(&mut rows[row], true) {
+                            for next in 0..inner.columns {
+                                if inner.closed(row, next) && !columns[next] {
+                                    columns[next] = true;
+                                    queue.push(next);
+                                }
+                            }
+                        }
+                    }
+                }
+
+                false
+            }
+        }
+    }
+}
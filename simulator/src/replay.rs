@@ -0,0 +1,162 @@
+//! Replay of the traces recorded by the firmware, checking that the same
+//! key events are produced.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context};
+use embassy_time::{Duration, Instant};
+use firmware::{key, proto::kontroller::v1::Button, trace::Record};
+
+use crate::script::{self, Script};
+
+/// First line of a trace dumped by the firmware.
+const TRACE_HEADER: &str = "# kontroller trace";
+
+/// Kind of a recorded [`key::Event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// A [`key::Event::Down`].
+    Down,
+    /// A [`key::Event::Up`].
+    Up,
+    /// A [`key::Event::Repeat`].
+    Repeat,
+}
+
+impl From<&key::Event> for Kind {
+    fn from(event: &key::Event) -> Self {
+        match event {
+            key::Event::Down { .. } => Self::Down,
+            key::Event::Up { .. } => Self::Up,
+            key::Event::Repeat { .. } => Self::Repeat,
+        }
+    }
+}
+
+/// A [`key::Event`] recorded in the trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Expected {
+    /// Time of the event, since the start of the trace.
+    pub at: Duration,
+    /// The [`Button`] producing the event.
+    pub button: Button,
+    /// The kind of event.
+    pub kind: Kind,
+}
+
+/// A trace dumped by the firmware, possibly still carrying the log prefixes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    /// The raw samples of the trace, as a button timeline.
+    pub script: Script,
+    /// The events recorded in the trace, in order.
+    pub events: Vec<Expected>,
+}
+
+impl FromStr for Trace {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Only the last dump is replayed, if the log contains more than one.
+        let dump = s.rfind(TRACE_HEADER).map_or(s, |start| &s[start..]);
+
+        let lines: Vec<&str> = dump.lines().filter_map(strip_log_prefix).collect();
+
+        let events = lines
+            .iter()
+            .filter_map(|line| line.strip_prefix("# "))
+            .filter(|line| line.contains(" event "))
+            .map(|line| parse_event(line).with_context(|| format!("invalid event '{line}'")))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            script: lines.join("\n").parse()?,
+            events,
+        })
+    }
+}
+
+/// Returns the part of the log line holding a sample or an event of the trace,
+/// if any, dropping the prefix and colors added by the logger.
+fn strip_log_prefix(line: &str) -> Option<&str> {
+    let start = line.find("t=")?;
+    let start = line[..start].strip_suffix("# ").map_or(start, str::len);
+    let line = line[start..].trim_end_matches("\x1b[0m").trim();
+
+    // Samples and events are always timed in microseconds by the firmware.
+    let time = line.trim_start_matches("# ").strip_prefix("t=")?;
+    let digits = time.find(|c: char| !c.is_ascii_digit())?;
+
+    (digits > 0 && time[digits..].starts_with("us ")).then_some(line)
+}
+
+fn parse_event(line: &str) -> anyhow::Result<Expected> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let [at, "event", button, kind] = tokens.as_slice() else {
+        bail!("expected '# t=<time> event <button> <down|up|repeat>'");
+    };
+
+    Ok(Expected {
+        at: script::parse_duration(
+            at.strip_prefix("t=")
+                .ok_or_else(|| anyhow!("time must be specified as 't=<time>'"))?,
+        )?,
+        button: script::parse_button(button)?,
+        kind: match *kind {
+            "down" => Kind::Down,
+            "up" => Kind::Up,
+            "repeat" => Kind::Repeat,
+            _ => bail!("unknown event '{kind}', expected 'down', 'up' or 'repeat'"),
+        },
+    })
+}
+
+/// Compares the events recorded during the replay with the expected ones,
+/// allowing their time to differ by the tolerance.
+///
+/// Returns a description of every mismatch found.
+pub fn compare<'a>(
+    expected: &[Expected],
+    records: impl Iterator<Item = &'a Record>,
+    start: Instant,
+    tolerance: Duration,
+) -> Vec<String> {
+    let actual: Vec<Expected> = records
+        .filter_map(|record| match record {
+            Record::Event { button, event } => Some(Expected {
+                at: event.at() - start,
+                button: *button,
+                kind: event.into(),
+            }),
+            Record::Sample { .. } => None,
+        })
+        .collect();
+
+    let mut mismatches: Vec<String> = expected
+        .iter()
+        .zip(&actual)
+        .enumerate()
+        .filter(|(_, (expected, actual))| {
+            let drift = if expected.at > actual.at {
+                expected.at - actual.at
+            } else {
+                actual.at - expected.at
+            };
+
+            (expected.button, expected.kind) != (actual.button, actual.kind) || drift > tolerance
+        })
+        .map(|(index, (expected, actual))| {
+            format!("event #{index}: expected {expected:?}, replayed {actual:?}")
+        })
+        .collect();
+
+    if expected.len() != actual.len() {
+        mismatches.push(format!(
+            "expected {} events, replayed {}",
+            expected.len(),
+            actual.len()
+        ));
+    }
+
+    mismatches
+}
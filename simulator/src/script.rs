@@ -0,0 +1,229 @@
+//! Parsing of the button timeline scripts driving the simulation.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context};
+use embassy_time::Duration;
+use firmware::proto::kontroller::v1::Button;
+
+/// The level a simulated [`Input`] is driven to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// The [`Button`] is pressed, i.e. its pin is pulled low.
+    Down,
+    /// The [`Button`] is released, i.e. its pin is pulled high.
+    Up,
+    /// The analog input reads the given raw ADC value.
+    Analog(u16),
+}
+
+/// Longest time accepted in a script, or as settle time: longer simulations
+/// would take too long to run to be of any use.
+pub const MAX_TIME: Duration = Duration::from_secs(3600);
+
+/// Time between two quadrature transitions of a simulated encoder detent.
+const ENCODER_PULSE_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A simulated input, driven by the script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Input {
+    /// The pin of a [`Button`].
+    Button(Button),
+    /// The `A` pin of the rotary encoder.
+    EncoderA,
+    /// The `B` pin of the rotary encoder.
+    EncoderB,
+    /// The horizontal axis of the joystick.
+    JoystickX,
+    /// The vertical axis of the joystick.
+    JoystickY,
+    /// The ADC line of the resistor ladder.
+    Ladder,
+}
+
+/// A single transition in the button timeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    /// Time of the transition, since the start of the simulation.
+    pub at: Duration,
+    /// The [`Input`] being driven.
+    pub input: Input,
+    /// The new level of the [`Input`].
+    pub level: Level,
+}
+
+/// A button timeline, sorted by time of the transitions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Script {
+    /// All the transitions of the timeline.
+    pub steps: Vec<Step>,
+}
+
+impl Script {
+    /// Returns the time of the last transition in the timeline.
+    pub fn duration(&self) -> Duration {
+        self.steps.last().map_or(Duration::MIN, |step| step.at)
+    }
+}
+
+impl FromStr for Script {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut steps = s
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .flat_map(|line| line.split(';'))
+            .map(str::trim)
+            .filter(|statement| !statement.is_empty())
+            .map(|statement| {
+                parse_steps(statement).with_context(|| format!("invalid statement '{statement}'"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        // Stable sort, so that transitions at the same time keep the script order.
+        steps.sort_by_key(|step| step.at);
+
+        Ok(Self { steps })
+    }
+}
+
+fn parse_steps(statement: &str) -> anyhow::Result<Vec<Step>> {
+    let tokens = statement.split_whitespace().collect::<Vec<_>>();
+
+    let (at, target, level) = match tokens.as_slice() {
+        [at, target, level] => (*at, *target, *level),
+        [at, target, x, y] if target.eq_ignore_ascii_case("joystick") => {
+            return parse_joystick_steps(parse_time(at)?, x, y);
+        }
+        _ => bail!(
+            "expected 't=<time> <button> <down|up>', 't=<time> encoder <cw|ccw>', \
+             't=<time> joystick <x> <y>' or 't=<time> ladder <reading>'"
+        ),
+    };
+
+    let at = parse_time(at)?;
+
+    if target.eq_ignore_ascii_case("encoder") {
+        return parse_encoder_steps(at, level);
+    }
+
+    if target.eq_ignore_ascii_case("ladder") {
+        return Ok(vec![Step {
+            at,
+            input: Input::Ladder,
+            level: Level::Analog(
+                level
+                    .parse()
+                    .with_context(|| format!("invalid ladder reading '{level}'"))?,
+            ),
+        }]);
+    }
+
+    Ok(vec![Step {
+        at,
+        input: Input::Button(parse_button(target)?),
+        level: match level.to_ascii_lowercase().as_str() {
+            "down" => Level::Down,
+            "up" => Level::Up,
+            _ => bail!("unknown level '{level}', expected 'down' or 'up'"),
+        },
+    }])
+}
+
+fn parse_time(at: &str) -> anyhow::Result<Duration> {
+    parse_duration(
+        at.strip_prefix("t=")
+            .ok_or_else(|| anyhow!("time must be specified as 't=<time>'"))?,
+    )
+}
+
+/// Sets both axes of the joystick to the given raw ADC readings.
+fn parse_joystick_steps(at: Duration, x: &str, y: &str) -> anyhow::Result<Vec<Step>> {
+    [(Input::JoystickX, x), (Input::JoystickY, y)]
+        .into_iter()
+        .map(|(input, reading)| {
+            Ok(Step {
+                at,
+                input,
+                level: Level::Analog(
+                    reading
+                        .parse()
+                        .with_context(|| format!("invalid joystick reading '{reading}'"))?,
+                ),
+            })
+        })
+        .collect()
+}
+
+/// Expands a single encoder detent into the four quadrature transitions
+/// of its `A` and `B` pins, starting from rest.
+fn parse_encoder_steps(at: Duration, direction: &str) -> anyhow::Result<Vec<Step>> {
+    let (first, second) = match direction.to_ascii_lowercase().as_str() {
+        "cw" => (Input::EncoderA, Input::EncoderB),
+        "ccw" => (Input::EncoderB, Input::EncoderA),
+        _ => bail!("unknown direction '{direction}', expected 'cw' or 'ccw'"),
+    };
+
+    Ok([
+        (first, Level::Down),
+        (second, Level::Down),
+        (first, Level::Up),
+        (second, Level::Up),
+    ]
+    .into_iter()
+    .zip(0..)
+    .map(|((input, level), pulse)| Step {
+        at: at + ENCODER_PULSE_INTERVAL * pulse,
+        input,
+        level,
+    })
+    .collect())
+}
+
+/// Parses a [`Duration`] in the form `<amount><unit>`, where unit is one of `us`, `ms` or `s`.
+///
+/// # Errors
+///
+/// The function fails if the amount is not a positive integer, the unit is unknown,
+/// or the time is longer than [`MAX_TIME`].
+pub fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow!("missing unit in time '{s}'"))?;
+
+    let (amount, unit) = s.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .with_context(|| format!("invalid amount in time '{s}'"))?;
+
+    let micros_per_unit = match unit {
+        "us" => 1,
+        "ms" => 1_000,
+        "s" => 1_000_000,
+        _ => bail!("unknown unit '{unit}' in time '{s}', expected 'us', 'ms' or 's'"),
+    };
+
+    amount
+        .checked_mul(micros_per_unit)
+        .filter(|micros| *micros <= MAX_TIME.as_micros())
+        .map(Duration::from_micros)
+        .ok_or_else(|| anyhow!("time '{s}' is longer than {}s", MAX_TIME.as_secs()))
+}
+
+/// Parses a [`Button`] by its name in the proto definition, e.g. `Up` or `BUTTON_UP`.
+///
+/// # Errors
+///
+/// The function fails if the name does not match any [`Button`].
+pub fn parse_button(s: &str) -> anyhow::Result<Button> {
+    let name = s.to_ascii_uppercase();
+
+    Button::from_str_name(&name)
+        .or_else(|| Button::from_str_name(&format!("BUTTON_{name}")))
+        .filter(|button| *button != Button::Unspecified)
+        .ok_or_else(|| anyhow!("unknown button '{s}'"))
+}
@@ -0,0 +1,408 @@
+//! Runs the simulator on the scenarios documented in the README, and checks the printed reports.
+
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+use firmware::{
+    kontroller,
+    proto::kontroller::{
+        hid::v1::{KeyCode, KeyboardReportMode},
+        v1::{
+            joystick_settings::Mode, keymap::entry::Action, keymap::Entry, touch::Point, Button,
+            JoystickSettings, Konfiguration, LadderSettings, LadderWindow, Touch,
+        },
+    },
+};
+use prost::Message;
+
+/// Runs the simulator with the arguments, writing the [`Konfiguration`] to a file first,
+/// and returns its output.
+fn simulate(name: &str, konfiguration: Option<&Konfiguration>, args: &[&str]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_simulator"));
+
+    if let Some(konfiguration) = konfiguration {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("simulator-{}-{name}.bin", std::process::id()));
+        fs::write(&path, konfiguration.encode_to_vec()).expect("konfiguration must be written");
+        command.arg("--konfiguration").arg(path);
+    }
+
+    command.args(args).output().expect("simulator must run")
+}
+
+/// Runs the simulator, expecting it to succeed, and returns the printed lines.
+fn lines(name: &str, konfiguration: Option<&Konfiguration>, args: &[&str]) -> Vec<String> {
+    let output = simulate(name, konfiguration, args);
+    assert!(
+        output.status.success(),
+        "simulator failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8(output.stdout)
+        .expect("output must be utf-8")
+        .lines()
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Returns the default [`Konfiguration`], with the [`Button`]s bound to the [`Action`]s
+/// instead of their default key codes.
+fn with_keymap(entries: impl IntoIterator<Item = (Button, Action)>) -> Konfiguration {
+    let mut konfiguration = kontroller::default_konfiguration();
+    let keymap = &mut konfiguration
+        .keymap
+        .get_or_insert_with(Default::default)
+        .entries;
+
+    for (button, action) in entries {
+        keymap.retain(|entry| entry.button() != button);
+        keymap.push(Entry {
+            button: button.into(),
+            action: Some(action),
+        });
+    }
+
+    konfiguration
+}
+
+const PRESS_UP: &str = "t=10ms Up down; t=50ms Up up";
+
+const PRESS_UP_REPORTS: [&str; 2] = [
+    "t=10.500ms keyboard modifier=0x00 keycodes=[UP]",
+    "t=51.000ms keyboard modifier=0x00 keycodes=[]",
+];
+
+#[test]
+fn press_and_release() {
+    assert_eq!(lines("press", None, &[PRESS_UP]), PRESS_UP_REPORTS);
+}
+
+#[test]
+fn press_and_release_through_a_matrix() {
+    for diodes in ["col2row", "row2col", "none"] {
+        assert_eq!(
+            lines("matrix", None, &["--matrix", diodes, PRESS_UP]),
+            PRESS_UP_REPORTS,
+            "diodes: {diodes}"
+        );
+    }
+}
+
+#[test]
+fn press_and_release_through_an_expander() {
+    for chip in ["mcp23017", "pcf8574"] {
+        assert_eq!(
+            lines("expander", None, &["--expander", chip, PRESS_UP]),
+            PRESS_UP_REPORTS,
+            "chip: {chip}"
+        );
+    }
+}
+
+#[test]
+fn json_reports_carry_the_serialized_bytes() {
+    assert_eq!(
+        lines("json", None, &["--json", PRESS_UP]),
+        [
+            r#"{"bytes":"0000520000000000","keycodes":["KEY_CODE_UP"],"modifier":0,"report":"keyboard","t_us":10500}"#,
+            r#"{"bytes":"0000000000000000","keycodes":[],"modifier":0,"report":"keyboard","t_us":51000}"#,
+        ]
+    );
+}
+
+#[test]
+fn buttons_held_at_boot_are_ignored_until_released() {
+    assert_eq!(
+        lines(
+            "stuck",
+            None,
+            &["t=0ms Up down; t=50ms Up up; t=60ms Up down; t=70ms Up up"]
+        ),
+        [
+            "t=0.000ms status StuckButton(Up)",
+            "t=51.000ms status ButtonRecovered(Up)",
+            "t=60.500ms keyboard modifier=0x00 keycodes=[UP]",
+            "t=71.000ms keyboard modifier=0x00 keycodes=[]",
+        ]
+    );
+}
+
+#[test]
+fn encoder_steps_tap_the_virtual_buttons() {
+    let konfiguration = with_keymap([
+        (
+            Button::EncoderCw,
+            Action::KeyCode(KeyCode::KbVolumeUp.into()),
+        ),
+        (
+            Button::EncoderCcw,
+            Action::KeyCode(KeyCode::KbVolumeDown.into()),
+        ),
+    ]);
+
+    assert_eq!(
+        lines(
+            "encoder",
+            Some(&konfiguration),
+            &["t=10ms encoder cw; t=30ms encoder ccw"]
+        ),
+        [
+            "t=13.000ms keyboard modifier=0x00 keycodes=[KB_VOLUME_UP]",
+            "t=13.000ms keyboard modifier=0x00 keycodes=[]",
+            "t=33.000ms keyboard modifier=0x00 keycodes=[KB_VOLUME_DOWN]",
+            "t=33.000ms keyboard modifier=0x00 keycodes=[]",
+        ]
+    );
+}
+
+#[test]
+fn joystick_moves_the_pointer() {
+    let konfiguration = Konfiguration {
+        joystick: Some(JoystickSettings {
+            mode: Mode::Pointer.into(),
+            ..JoystickSettings::default()
+        }),
+        ..kontroller::default_konfiguration()
+    };
+
+    let reports = lines(
+        "joystick",
+        Some(&konfiguration),
+        &[
+            "--settle",
+            "0ms",
+            "t=10ms joystick 4095 2048; t=50ms joystick 2048 2048",
+        ],
+    );
+
+    assert!(!reports.is_empty());
+    assert!(
+        reports
+            .iter()
+            .all(|line| line.contains("mouse") && line.contains("y=0")),
+        "{reports:#?}"
+    );
+    assert!(
+        reports.iter().any(|line| line.contains("x=6 ")),
+        "{reports:#?}"
+    );
+}
+
+#[test]
+fn ladder_readings_press_the_buttons_in_their_window() {
+    let konfiguration = Konfiguration {
+        ladder: Some(LadderSettings {
+            windows: vec![LadderWindow {
+                buttons: vec![Button::Up.into()],
+                min: 1000,
+                max: 1400,
+                hysteresis: 0,
+            }],
+            ..LadderSettings::default()
+        }),
+        ..kontroller::default_konfiguration()
+    };
+
+    assert_eq!(
+        lines(
+            "ladder",
+            Some(&konfiguration),
+            &["t=10ms ladder 1200; t=50ms ladder 4095"]
+        ),
+        // The reading is only trusted once settled, after the default settle samples.
+        [
+            "t=11.500ms keyboard modifier=0x00 keycodes=[UP]",
+            "t=52.000ms keyboard modifier=0x00 keycodes=[]",
+        ]
+    );
+}
+
+#[test]
+fn times_beyond_the_bound_are_rejected() {
+    for script in ["t=18446744073709551615us Up down", "t=3601s Up down"] {
+        let output = simulate("bound", None, &[script]);
+
+        assert!(!output.status.success(), "script: {script}");
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("longer than 3600s"),
+            "script: {script}"
+        );
+    }
+
+    let output = simulate("settle", None, &["--settle", "99999999999s", PRESS_UP]);
+    assert!(!output.status.success());
+}
+
+/// Trace dumped by the firmware on the serial console, with the log prefixes,
+/// where Enter bounces once while being pressed.
+const BOUNCE_TRACE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/bounce.trace");
+
+#[test]
+fn replaying_a_recorded_trace_reproduces_its_events() {
+    let output = simulate("replay", None, &["--replay", BOUNCE_TRACE]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(output.status.success(), "simulator failed: {stderr}");
+    assert!(
+        stderr.contains("replay matches the trace: 4 events"),
+        "{stderr}"
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .collect::<Vec<_>>(),
+        [
+            "t=10.500ms keyboard modifier=0x00 keycodes=[UP]",
+            "t=31.000ms keyboard modifier=0x00 keycodes=[]",
+            "t=60.500ms keyboard modifier=0x00 keycodes=[ENTER]",
+            "t=120.000ms keyboard modifier=0x00 keycodes=[]",
+        ]
+    );
+}
+
+/// Taps the screen with Fn1, long taps it with Fn2, and swipes it with Fn3.
+fn with_touches() -> Konfiguration {
+    let point = |x_permille, y_permille| {
+        Some(Point {
+            x_permille,
+            y_permille,
+        })
+    };
+
+    with_keymap([
+        (
+            Button::Fn1,
+            Action::Touch(Touch {
+                at: point(250, 750),
+                to: None,
+                duration_millis: 0,
+            }),
+        ),
+        (
+            Button::Fn2,
+            Action::Touch(Touch {
+                at: point(250, 750),
+                to: None,
+                duration_millis: 800,
+            }),
+        ),
+        (
+            Button::Fn3,
+            Action::Touch(Touch {
+                at: point(100, 100),
+                to: point(300, 500),
+                duration_millis: 100,
+            }),
+        ),
+    ])
+}
+
+#[test]
+fn touches_are_timed_on_the_digitizer() {
+    let konfiguration = with_touches();
+
+    assert_eq!(
+        lines(
+            "tap",
+            Some(&konfiguration),
+            &["t=10ms Fn1 down; t=20ms Fn1 up"]
+        ),
+        [
+            "t=10.500ms digitizer contacts=[0:touch x=250 y=750]",
+            "t=60.500ms digitizer contacts=[0:lift x=250 y=750]",
+        ]
+    );
+    assert_eq!(
+        lines(
+            "long-tap",
+            Some(&konfiguration),
+            &["--settle", "1s", "t=10ms Fn2 down; t=20ms Fn2 up"]
+        ),
+        [
+            "t=10.500ms digitizer contacts=[0:touch x=250 y=750]",
+            "t=810.500ms digitizer contacts=[0:lift x=250 y=750]",
+        ]
+    );
+}
+
+#[test]
+fn swipe_moves_every_motion_step_then_lifts_at_the_end() {
+    let swipe = lines(
+        "swipe",
+        Some(&with_touches()),
+        &["--settle", "200ms", "t=10ms Fn3 down; t=20ms Fn3 up"],
+    );
+
+    assert_eq!(swipe.len(), 11);
+    assert_eq!(
+        swipe[..2],
+        [
+            "t=10.500ms digitizer contacts=[0:touch x=100 y=100]",
+            "t=20.500ms digitizer contacts=[0:touch x=120 y=140]",
+        ]
+    );
+    assert_eq!(
+        swipe[9..],
+        [
+            "t=100.500ms digitizer contacts=[0:touch x=280 y=460]",
+            "t=110.500ms digitizer contacts=[0:lift x=300 y=500]",
+        ]
+    );
+}
+
+#[test]
+fn touch_interrupts_the_stroke_in_progress() {
+    assert_eq!(
+        lines(
+            "interrupted",
+            Some(&with_touches()),
+            &["t=10ms Fn3 down; t=20ms Fn3 up; t=40ms Fn1 down; t=45ms Fn1 up"]
+        )[3..],
+        [
+            "t=40.500ms digitizer contacts=[0:lift x=160 y=220]",
+            "t=40.500ms digitizer contacts=[0:touch x=250 y=750]",
+            "t=90.500ms digitizer contacts=[0:lift x=250 y=750]",
+        ]
+    );
+}
+
+#[test]
+fn boot_protocol_sends_boot_keyboard_reports_only() {
+    let konfiguration = Konfiguration {
+        keyboard_report_mode: KeyboardReportMode::NKey.into(),
+        ..with_keymap([(Button::Fn1, Action::MouseWheel(1))])
+    };
+
+    let reports = lines(
+        "boot-protocol",
+        Some(&konfiguration),
+        &[
+            "--boot-protocol",
+            "--json",
+            "t=10ms Up down; t=10ms Down down; t=10ms Left down; t=10ms Right down; \
+             t=10ms Enter down; t=10ms Fn2 down; t=20ms Fn3 down; \
+             t=30ms Fn3 up; t=40ms Up up; t=40ms Down up; t=40ms Left up; t=40ms Right up; \
+             t=40ms Enter up; t=40ms Fn2 up; t=50ms Fn1 down; t=60ms Fn1 up",
+        ],
+    );
+
+    let boot = r#""modifier":0,"report":"keyboard""#;
+    let six = r#"["KEY_CODE_ENTER","KEY_CODE_F6","KEY_CODE_RIGHT","KEY_CODE_LEFT","KEY_CODE_DOWN","KEY_CODE_UP"]"#;
+    let rollover = format!("[{}]", [r#""KEY_CODE_ERROR_ROLLOVER""#; 6].join(","));
+
+    // The NKRO reports are converted to the 8 bytes of the boot layout, with a rollover error past
+    // six keys, and the mouse reports scrolled by Fn1 are dropped.
+    assert_eq!(
+        reports,
+        [
+            format!(r#"{{"bytes":"0000283f4f505152","keycodes":{six},{boot},"t_us":10500}}"#),
+            format!(r#"{{"bytes":"0000010101010101","keycodes":{rollover},{boot},"t_us":20500}}"#),
+            format!(r#"{{"bytes":"0000283f4f505152","keycodes":{six},{boot},"t_us":31000}}"#),
+            format!(r#"{{"bytes":"0000000000000000","keycodes":[],{boot},"t_us":41000}}"#),
+        ]
+    );
+}